@@ -1,20 +1,21 @@
-use std::path::PathBuf;
 use std::sync::{
 	atomic::{AtomicBool, Ordering},
 	Arc, Condvar, Mutex,
 };
 
-use gelatin::image::imageops::{
-	flip_horizontal_in_place, flip_vertical_in_place, rotate180_in_place, rotate270, rotate90,
-};
+use crate::export::{self, ExportRequest};
 
-use crate::image_cache::image_loader::{
-	complex_load_image, ImageLoaderError, LoadResult, Orientation,
-};
+/// What to put on the clipboard
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ClipboardRequest {
+	/// The image described by the request as it would be exported
+	Image(ExportRequest),
+	Text(String),
+}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 enum ClipboardState {
-	Pending(PathBuf),
+	Pending(ClipboardRequest),
 	Succeeded,
 	Failed,
 }
@@ -49,7 +50,7 @@ impl ClipboardHandler {
 		ClipboardHandler { request_handle, thread_handle: Some(handle) }
 	}
 
-	pub fn request_copy(&mut self, target: PathBuf) -> bool {
+	pub fn request_copy(&mut self, target: ClipboardRequest) -> bool {
 		{
 			let mut state = self.request_handle.state.lock().unwrap();
 			if let ClipboardState::Pending(..) = &*state {
@@ -84,12 +85,12 @@ impl ClipboardHandler {
 			eprintln!("The clipboard could not be created, error was: {}", e);
 		}
 		while request_handle.run_thread.load(Ordering::Acquire) {
-			let request_path;
+			let request;
 			{
 				let mut state_guard = request_handle.state.lock().unwrap();
 				'wait_for_request: loop {
-					if let ClipboardState::Pending(path) = state_guard.clone() {
-						request_path = path;
+					if let ClipboardState::Pending(pending) = state_guard.clone() {
+						request = pending;
 						break 'wait_for_request;
 					} else {
 						if !request_handle.run_thread.load(Ordering::Acquire) {
@@ -106,59 +107,44 @@ impl ClipboardHandler {
 					}
 				}
 			}
-			let result = complex_load_image(&request_path, false, 0, |frame| {
-				if let LoadResult::Frame { mut image, orientation, .. } = frame {
-					if let Ok(clipboard) = &mut clipboard {
-						// Note: the imageops functions use clockwise rotation whereas the
-						// `Orientation` type describes counter-clockwise rotation.
-						image = match orientation {
-							Orientation::Deg0 => image,
-							Orientation::Deg0HorFlip => {
-								flip_horizontal_in_place(&mut image);
-								image
-							}
-							Orientation::Deg90 => rotate270(&image),
-							Orientation::Deg90VerFlip => {
-								let mut result = rotate270(&image);
-								flip_vertical_in_place(&mut result);
-								result
-							}
-							Orientation::Deg180 => {
-								rotate180_in_place(&mut image);
-								image
-							}
-							Orientation::Deg180HorFlip => {
-								// This is identical to just a vertical flip with no rotation.
-								flip_vertical_in_place(&mut image);
-								image
-							}
-							Orientation::Deg270 => rotate90(&image),
-							Orientation::Deg270VerFlip => {
-								let mut result = rotate90(&image);
-								flip_vertical_in_place(&mut result);
-								result
-							}
-						};
-						let (w, h) = image.dimensions();
-						let cb_image = arboard::ImageData {
-							width: w as usize,
-							height: h as usize,
-							bytes: image.into_raw().into(),
-						};
-						if let Err(e) = clipboard.set_image(cb_image) {
-							eprintln!("Could not set the clipboard image, error was: {}", e);
-						} else {
-							return Ok(());
-						}
-					}
+			let clipboard = match &mut clipboard {
+				Ok(clipboard) => clipboard,
+				Err(_) => {
+					*request_handle.state.lock().unwrap() = ClipboardState::Failed;
+					continue;
 				}
-				Err(ImageLoaderError { description: "Could not set the clipboard image.".into() })
-			});
+			};
+			let result = match request {
+				ClipboardRequest::Image(request) => Self::copy_image(clipboard, &request),
+				ClipboardRequest::Text(text) => clipboard.set_text(text).map_err(|e| {
+					eprintln!("Could not set the clipboard text, error was: {}", e);
+				}),
+			};
 			let mut state = request_handle.state.lock().unwrap();
 			*state =
 				if result.is_ok() { ClipboardState::Succeeded } else { ClipboardState::Failed };
 		}
 	}
+
+	fn copy_image(clipboard: &mut arboard::Clipboard, request: &ExportRequest) -> Result<(), ()> {
+		match export::render(request) {
+			Ok(image) => {
+				let (w, h) = image.dimensions();
+				let cb_image = arboard::ImageData {
+					width: w as usize,
+					height: h as usize,
+					bytes: image.into_raw().into(),
+				};
+				clipboard.set_image(cb_image).map_err(|e| {
+					eprintln!("Could not set the clipboard image, error was: {}", e);
+				})
+			}
+			Err(e) => {
+				eprintln!("Could not load {:?} for the clipboard: {}", request.source, e);
+				Err(())
+			}
+		}
+	}
 }
 
 impl Default for ClipboardHandler {
@@ -4,6 +4,8 @@ use std::path::Path;
 
 pub struct Args {
 	pub file_path: Option<String>,
+	/// Shown next to `file_path` in compare mode
+	pub compare_path: Option<String>,
 	pub displayed_folders: Option<u32>,
 }
 
@@ -45,19 +47,27 @@ pub fn parse_args(config_path: &Path, cache_path: &Path) -> Args {
 				.num_args(0)
 				.conflicts_with("FOLDER_COUNT"),
 		)
+		.arg(
+			Arg::new("COMPARE_PATH")
+				.long("compare")
+				.short('c')
+				.help("Start in compare mode, showing this image next to PATH")
+				.num_args(1),
+		)
 		.arg(Arg::new("PATH").help("The file path of the image").index(1))
 		.get_matches();
 
 	let file_path = matches.get_one::<String>("PATH").cloned();
+	let compare_path = matches.get_one::<String>("COMPARE_PATH").cloned();
 
 	let is_absolute = matches.value_source("absolute") == Some(ValueSource::CommandLine);
 	let displayed_folders = if is_absolute {
 		// Subtract one because we later want to add one to this value, and we don't want
 		// an overflow
-		Some(std::u32::MAX - 1)
+		Some(u32::MAX - 1)
 	} else {
 		matches.get_one::<u32>("FOLDER_COUNT").copied()
 	};
 
-	Args { file_path, displayed_folders }
+	Args { file_path, compare_path, displayed_folders }
 }
@@ -10,7 +10,7 @@
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use serde::{Deserialize, Serialize};
@@ -28,6 +28,14 @@ impl Theme {
 			Theme::Light => Theme::Dark,
 		}
 	}
+
+	/// The color of the text of the bottom bar and the other widgets on the window background
+	pub fn text_color(self) -> [f32; 4] {
+		match self {
+			Theme::Light => [0.2, 0.2, 0.2, 1.0],
+			Theme::Dark => [0.85, 0.85, 0.85, 1.0],
+		}
+	}
 }
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -39,15 +47,47 @@ pub enum Antialias {
 	Never,
 }
 
+/// What's drawn behind the transparent parts of the image
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundMode {
+	/// A checkerboard matching the light or dark theme
+	#[default]
+	Theme,
+	/// A checkerboard with the size and colors from the `[background]` section
+	Checkerboard,
+	/// The color from the `[background]` section
+	Solid,
+}
+impl BackgroundMode {
+	pub fn next(self) -> Self {
+		match self {
+			BackgroundMode::Theme => BackgroundMode::Checkerboard,
+			BackgroundMode::Checkerboard => BackgroundMode::Solid,
+			BackgroundMode::Solid => BackgroundMode::Theme,
+		}
+	}
+}
+
 #[derive(Debug, Default, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct CacheImageSection {
 	pub fit_stretches: bool,
 	pub antialiasing: Antialias,
+	#[serde(default)]
+	pub background: BackgroundMode,
 }
 
-#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
 pub struct ConfigImageSection {
 	pub antialiasing: Option<String>,
+	/// Keep the rotation and mirroring applied with the `view_*` actions when moving to
+	/// another image. By default it's reset.
+	pub keep_view_orientation: Option<bool>,
+	/// Makes `rotate_cw`, `rotate_ccw` and `flip_h` change the file too. By default they only
+	/// change the view like the `view_*` actions.
+	pub save_rotation: Option<bool>,
+	/// The zoom level above which the pixel grid starts to fade in, eg `8` for 800%
+	pub pixel_grid_zoom: Option<f32>,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
@@ -70,6 +110,9 @@ pub struct ConfigWindowSection {
 	pub start_fullscreen: Option<bool>,
 	pub start_maximized: Option<bool>,
 	pub show_bottom_bar: Option<bool>,
+	pub show_filmstrip: Option<bool>,
+	/// The text shown in the bottom bar, with the same placeholders as the title format
+	pub status_format: Option<String>,
 	pub theme: Option<Theme>,
 	pub use_last_window_area: Option<bool>,
 	pub win_w: Option<u32>,
@@ -177,12 +220,107 @@ pub struct Command {
 	pub envs: Option<Vec<EnvVar>>,
 }
 
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferMode {
+	#[default]
+	Move,
+	Copy,
+}
+
+/// Determines what happens when the destination folder already has a file with
+/// the same name as the one being moved or copied.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+	/// Append a number to the file name, eg `IMG_0042 (1).jpg`
+	#[default]
+	Rename,
+	/// Leave both files untouched
+	Skip,
+}
+
+/// A folder that the current image can be sent to with a key press.
+///
+/// A relative `path` is interpreted relative to the folder of the current image.
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
+pub struct Destination {
+	pub input: Vec<String>,
+	pub path: PathBuf,
+	pub mode: Option<TransferMode>,
+	pub conflict: Option<ConflictPolicy>,
+}
+
+/// The colors used by the background modes. Colors are written as `#RRGGBB`.
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
+pub struct BackgroundSection {
+	/// The size of the checkerboard squares in logical pixels
+	pub checkerboard_size: Option<f32>,
+	pub checkerboard_colors: Option<[String; 2]>,
+	/// The color of the `solid` background mode
+	pub color: Option<String>,
+}
+
+/// Parses a color written as `#RRGGBB` or `#RRGGBBAA`
+pub fn parse_color(color: &str) -> Option<[f32; 4]> {
+	let hex = color.trim().strip_prefix('#')?;
+	if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+		return None;
+	}
+	let mut result = [1.0; 4];
+	for (i, channel) in result.iter_mut().enumerate().take(hex.len() / 2) {
+		let value = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+		*channel = value as f32 / 255.0;
+	}
+	Some(result)
+}
+
+/// Settings of the `export` action
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
+pub struct ExportSection {
+	/// The file extension of the output format, eg `png`, `jpg` or `webp`
+	pub format: Option<String>,
+	/// JPEG quality between 1 and 100. Other values are clamped to that range.
+	pub quality: Option<i64>,
+	/// Only export the part of the image that's visible in the window
+	pub visible_region: Option<bool>,
+}
+
+/// What the right mouse button does
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RightButton {
+	/// Opens the context menu when released
+	Menu,
+	/// Shows that the left button pans the image while the right button is held
+	PanHint,
+	Nothing,
+	/// Runs the action with this name when pressed, eg `img_next`
+	Action(String),
+}
+
+#[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
+pub struct MouseSection {
+	/// `"pan_hint"`, `"menu"`, `"none"` or the name of an action. The default is `"pan_hint"`.
+	pub right_button: Option<String>,
+}
+
 #[derive(Debug, Default, Eq, PartialEq, Clone, Deserialize)]
 pub struct TitleSection {
 	pub displayed_folders: Option<u32>,
 	pub show_program_name: Option<bool>,
+	/// The title with placeholders like `${filename}`, see `template::TemplateValues`
+	pub format: Option<String>,
 }
 impl TitleSection {
+	/// The configured format, or the template of the default title which respects
+	/// `show_program_name`
+	pub fn template(&self) -> Cow<'_, str> {
+		match &self.format {
+			Some(format) => format.into(),
+			None => format!("${{name}}${{playback}}{}", self.format_program_name()).into(),
+		}
+	}
+
 	pub fn format_file_path<'a>(&self, file_path: &'a Path) -> Cow<'a, str> {
 		match self.displayed_folders {
 			Some(0) | None => file_path.file_name().unwrap().to_string_lossy(),
@@ -215,14 +353,21 @@ impl TitleSection {
 	}
 }
 
+/// Shows the position of the image in the folder and its size, eg `37 / 512 · 6000×4000`
+const DEFAULT_STATUS_FORMAT: &str = "${index} / ${total} · ${width}×${height}";
+
 #[derive(Debug, Default, Clone, Deserialize)]
 pub struct Configuration {
 	pub bindings: Option<BTreeMap<String, Vec<String>>>,
 	pub commands: Option<Vec<Command>>,
+	pub destinations: Option<Vec<Destination>>,
+	pub export: Option<ExportSection>,
+	pub background: Option<BackgroundSection>,
 	pub updates: Option<ConfigUpdateSection>,
 	pub title: Option<TitleSection>,
 	pub image: Option<ConfigImageSection>,
 	pub window: Option<ConfigWindowSection>,
+	pub mouse: Option<MouseSection>,
 }
 impl Configuration {
 	pub fn load<P: AsRef<Path>>(file_path: P) -> Result<Configuration, String> {
@@ -233,4 +378,22 @@ impl Configuration {
 		//println!("Read config from file:\n{:#?}", result);
 		Ok(result)
 	}
+
+	pub fn right_button(&self) -> RightButton {
+		let right_button = self.mouse.as_ref().and_then(|m| m.right_button.as_deref());
+		match right_button.unwrap_or("pan_hint") {
+			"menu" => RightButton::Menu,
+			"pan_hint" => RightButton::PanHint,
+			"none" => RightButton::Nothing,
+			action => RightButton::Action(action.to_owned()),
+		}
+	}
+
+	/// The configured text of the bottom bar status, or the default one
+	pub fn status_format(&self) -> &str {
+		match &self.window {
+			Some(ConfigWindowSection { status_format: Some(format), .. }) => format,
+			_ => DEFAULT_STATUS_FORMAT,
+		}
+	}
 }
@@ -0,0 +1,267 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::configuration::{ConflictPolicy, Destination, TransferMode};
+
+/// A file that was moved out of the current folder and can be moved back.
+#[derive(Debug, Clone)]
+pub struct MovedFile {
+	pub from: PathBuf,
+	pub to: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum TransferOutcome {
+	Moved(MovedFile),
+	Copied,
+	/// The target already existed and the conflict policy was `skip`
+	Skipped(PathBuf),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransferError {
+	#[error("the image path has no parent folder: {0:?}")]
+	NoParent(PathBuf),
+
+	#[error("the image path has no file name: {0:?}")]
+	NoFileName(PathBuf),
+
+	#[error("could not find a free file name in {0:?}")]
+	NoFreeName(PathBuf),
+
+	#[error("IO error while transferring the file: {0}")]
+	Io(#[from] io::Error),
+}
+
+/// Moves or copies `img_path` into the folder described by `destination`.
+///
+/// The destination folder is created if it doesn't exist yet.
+pub fn transfer(
+	img_path: &Path,
+	destination: &Destination,
+) -> Result<TransferOutcome, TransferError> {
+	let img_folder = img_path.parent().ok_or_else(|| TransferError::NoParent(img_path.into()))?;
+	let file_name =
+		img_path.file_name().ok_or_else(|| TransferError::NoFileName(img_path.into()))?;
+	let target_folder = img_folder.join(&destination.path);
+	fs::create_dir_all(&target_folder)?;
+
+	let mut target = target_folder.join(file_name);
+	if target.exists() {
+		match destination.conflict.unwrap_or_default() {
+			ConflictPolicy::Skip => return Ok(TransferOutcome::Skipped(target)),
+			ConflictPolicy::Rename => target = free_path(&target)?,
+		}
+	}
+	match destination.mode.unwrap_or_default() {
+		TransferMode::Move => {
+			move_file(img_path, &target)?;
+			Ok(TransferOutcome::Moved(MovedFile { from: img_path.into(), to: target }))
+		}
+		TransferMode::Copy => {
+			fs::copy(img_path, &target)?;
+			Ok(TransferOutcome::Copied)
+		}
+	}
+}
+
+/// Moves the file back to where it was before the transfer.
+pub fn undo_move(moved: &MovedFile) -> Result<(), TransferError> {
+	if moved.from.exists() {
+		return Err(io::Error::new(
+			io::ErrorKind::AlreadyExists,
+			format!("{:?} already exists", moved.from),
+		)
+		.into());
+	}
+	move_file(&moved.to, &moved.from)
+}
+
+fn move_file(from: &Path, to: &Path) -> Result<(), TransferError> {
+	match fs::rename(from, to) {
+		Ok(()) => Ok(()),
+		// Renaming fails when the target is on a different file system,
+		// in which case fall back to copying.
+		Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+			fs::copy(from, to)?;
+			fs::remove_file(from)?;
+			Ok(())
+		}
+		Err(e) => Err(e.into()),
+	}
+}
+
+/// Returns a path that doesn't exist yet by appending a number to the file stem,
+/// eg `IMG_0042.jpg` becomes `IMG_0042 (1).jpg`
+pub fn free_path(path: &Path) -> Result<PathBuf, TransferError> {
+	let folder = path.parent().ok_or_else(|| TransferError::NoParent(path.into()))?;
+	let stem = path.file_stem().ok_or_else(|| TransferError::NoFileName(path.into()))?;
+	let extension = path.extension();
+	for i in 1..10000 {
+		let mut name = stem.to_owned();
+		name.push(format!(" ({})", i));
+		if let Some(extension) = extension {
+			name.push(".");
+			name.push(extension);
+		}
+		let candidate = folder.join(name);
+		if !candidate.exists() {
+			return Ok(candidate);
+		}
+	}
+	Err(TransferError::NoFreeName(folder.into()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// An empty folder that's removed at the end of the test
+	struct TempDir(PathBuf);
+	impl TempDir {
+		fn new(name: &str) -> TempDir {
+			let path = std::env::temp_dir().join(format!(
+				"emulsion-destinations-{}-{}",
+				name,
+				std::process::id()
+			));
+			let _ = fs::remove_dir_all(&path);
+			fs::create_dir_all(&path).unwrap();
+			TempDir(path)
+		}
+	}
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = fs::remove_dir_all(&self.0);
+		}
+	}
+
+	fn destination(mode: TransferMode, conflict: ConflictPolicy) -> Destination {
+		Destination {
+			input: vec!["1".into()],
+			path: "keep".into(),
+			mode: Some(mode),
+			conflict: Some(conflict),
+		}
+	}
+
+	#[test]
+	fn free_path_appends_the_first_unused_number() {
+		let dir = TempDir::new("free-path");
+		let path = dir.0.join("IMG_0042.jpg");
+		fs::write(&path, "a").unwrap();
+		assert_eq!(free_path(&path).unwrap(), dir.0.join("IMG_0042 (1).jpg"));
+		fs::write(dir.0.join("IMG_0042 (1).jpg"), "b").unwrap();
+		assert_eq!(free_path(&path).unwrap(), dir.0.join("IMG_0042 (2).jpg"));
+	}
+
+	#[test]
+	fn free_path_without_extension() {
+		let dir = TempDir::new("free-path-no-ext");
+		let path = dir.0.join("image");
+		fs::write(&path, "a").unwrap();
+		assert_eq!(free_path(&path).unwrap(), dir.0.join("image (1)"));
+	}
+
+	#[test]
+	fn transfer_moves_into_a_new_folder() {
+		let dir = TempDir::new("move");
+		let img = dir.0.join("a.png");
+		fs::write(&img, "a").unwrap();
+		let outcome =
+			transfer(&img, &destination(TransferMode::Move, ConflictPolicy::Rename)).unwrap();
+		let target = dir.0.join("keep").join("a.png");
+		match outcome {
+			TransferOutcome::Moved(moved) => {
+				assert_eq!(moved.from, img);
+				assert_eq!(moved.to, target);
+			}
+			other => panic!("unexpected outcome {:?}", other),
+		}
+		assert!(!img.exists());
+		assert_eq!(fs::read_to_string(&target).unwrap(), "a");
+	}
+
+	#[test]
+	fn transfer_copies() {
+		let dir = TempDir::new("copy");
+		let img = dir.0.join("a.png");
+		fs::write(&img, "a").unwrap();
+		let outcome =
+			transfer(&img, &destination(TransferMode::Copy, ConflictPolicy::Rename)).unwrap();
+		assert!(matches!(outcome, TransferOutcome::Copied));
+		assert!(img.exists());
+		assert_eq!(fs::read_to_string(dir.0.join("keep").join("a.png")).unwrap(), "a");
+	}
+
+	#[test]
+	fn transfer_renames_on_conflict() {
+		let dir = TempDir::new("conflict-rename");
+		let img = dir.0.join("a.png");
+		fs::write(&img, "new").unwrap();
+		fs::create_dir(dir.0.join("keep")).unwrap();
+		fs::write(dir.0.join("keep").join("a.png"), "old").unwrap();
+		let outcome =
+			transfer(&img, &destination(TransferMode::Move, ConflictPolicy::Rename)).unwrap();
+		let target = dir.0.join("keep").join("a (1).png");
+		assert!(
+			matches!(outcome, TransferOutcome::Moved(MovedFile { ref to, .. }) if *to == target)
+		);
+		assert_eq!(fs::read_to_string(dir.0.join("keep").join("a.png")).unwrap(), "old");
+		assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+	}
+
+	#[test]
+	fn transfer_skips_on_conflict() {
+		let dir = TempDir::new("conflict-skip");
+		let img = dir.0.join("a.png");
+		fs::write(&img, "new").unwrap();
+		fs::create_dir(dir.0.join("keep")).unwrap();
+		let existing = dir.0.join("keep").join("a.png");
+		fs::write(&existing, "old").unwrap();
+		let outcome =
+			transfer(&img, &destination(TransferMode::Move, ConflictPolicy::Skip)).unwrap();
+		assert!(matches!(outcome, TransferOutcome::Skipped(ref target) if *target == existing));
+		assert_eq!(fs::read_to_string(&img).unwrap(), "new");
+		assert_eq!(fs::read_to_string(&existing).unwrap(), "old");
+	}
+
+	#[test]
+	fn undo_move_restores_the_file() {
+		let dir = TempDir::new("undo");
+		let img = dir.0.join("a.png");
+		fs::write(&img, "a").unwrap();
+		let moved = match transfer(&img, &destination(TransferMode::Move, ConflictPolicy::Rename)) {
+			Ok(TransferOutcome::Moved(moved)) => moved,
+			other => panic!("unexpected outcome {:?}", other),
+		};
+		undo_move(&moved).unwrap();
+		assert_eq!(fs::read_to_string(&img).unwrap(), "a");
+		assert!(!moved.to.exists());
+	}
+
+	#[test]
+	fn undo_move_keeps_a_file_that_took_the_place() {
+		let dir = TempDir::new("undo-conflict");
+		let img = dir.0.join("a.png");
+		fs::write(&img, "a").unwrap();
+		let moved = match transfer(&img, &destination(TransferMode::Move, ConflictPolicy::Rename)) {
+			Ok(TransferOutcome::Moved(moved)) => moved,
+			other => panic!("unexpected outcome {:?}", other),
+		};
+		fs::write(&img, "b").unwrap();
+		assert!(undo_move(&moved).is_err());
+		assert_eq!(fs::read_to_string(&img).unwrap(), "b");
+		assert_eq!(fs::read_to_string(&moved.to).unwrap(), "a");
+	}
+
+	#[test]
+	fn move_file_reports_a_missing_folder() {
+		let dir = TempDir::new("missing-folder");
+		let img = dir.0.join("a.png");
+		fs::write(&img, "a").unwrap();
+		assert!(move_file(&img, &dir.0.join("missing").join("a.png")).is_err());
+		assert!(img.exists());
+	}
+}
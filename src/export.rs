@@ -0,0 +1,220 @@
+//! Saving a copy of the image as it's displayed, without modifying the original file.
+
+use std::fs;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+
+use gelatin::image::{self, codecs::jpeg::JpegEncoder, DynamicImage, ImageFormat, RgbaImage};
+
+use crate::configuration::ExportSection;
+use crate::destinations;
+use crate::file_transform::apply_orientation;
+use crate::image_cache::image_loader::{
+	complex_load_image, ImageLoaderError, LoadResult, Orientation,
+};
+
+const DEFAULT_JPEG_QUALITY: i64 = 90;
+
+/// A rectangle in image pixels
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PixelRect {
+	pub x: u32,
+	pub y: u32,
+	pub w: u32,
+	pub h: u32,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+	#[error("{0:?} is not a supported export format")]
+	UnknownFormat(String),
+
+	#[error("the image has no frames")]
+	NoFrames,
+
+	#[error("the region to export is outside of the image")]
+	EmptyRegion,
+
+	#[error("{0}")]
+	Load(#[from] ImageLoaderError),
+
+	#[error("{0}")]
+	Image(#[from] image::ImageError),
+
+	#[error("IO error while exporting the image: {0}")]
+	Io(#[from] io::Error),
+}
+
+/// The output format and quality of exported images
+#[derive(Debug, Clone)]
+pub struct ExportSettings {
+	pub format: ImageFormat,
+	/// Only used by lossy formats
+	pub quality: u8,
+}
+
+impl ExportSettings {
+	pub fn from_config(config: Option<&ExportSection>) -> Result<ExportSettings, ExportError> {
+		let format = config.and_then(|c| c.format.clone()).unwrap_or_else(|| "png".into());
+		let quality = config.and_then(|c| c.quality).unwrap_or(DEFAULT_JPEG_QUALITY);
+		let clamped = quality.clamp(1, 100);
+		if clamped != quality {
+			log::warn!(
+				"The export quality {} is not between 1 and 100, using {}",
+				quality,
+				clamped
+			);
+		}
+		match ImageFormat::from_extension(&format) {
+			Some(format) if format.writing_enabled() => {
+				Ok(ExportSettings { format, quality: clamped as u8 })
+			}
+			_ => Err(ExportError::UnknownFormat(format)),
+		}
+	}
+
+	fn extension(&self) -> &'static str {
+		self.format.extensions_str().first().copied().unwrap_or("png")
+	}
+}
+
+/// Describes what to export
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ExportRequest {
+	pub source: PathBuf,
+	/// Applied on top of the orientation stored in the file
+	pub view_orientation: Orientation,
+	/// The part of the oriented image to export. The whole image is exported if this is `None`
+	pub region: Option<PixelRect>,
+}
+
+/// Loads the first frame of the source image and applies the orientation and the cropping of
+/// the request to it.
+pub fn render(request: &ExportRequest) -> Result<RgbaImage, ExportError> {
+	let mut result = None;
+	complex_load_image(&request.source, false, 0, |frame| {
+		if let LoadResult::Frame { image, orientation, .. } = frame {
+			if result.is_none() {
+				result = Some((image, orientation));
+			}
+		}
+		Ok(())
+	})?;
+	let (image, orientation) = result.ok_or(ExportError::NoFrames)?;
+	let orientation = orientation.followed_by(request.view_orientation);
+	let image = DynamicImage::ImageRgba8(Arc::unwrap_or_clone(image));
+	let mut image = apply_orientation(image, orientation);
+	if let Some(region) = request.region {
+		if region.x >= image.width() || region.y >= image.height() {
+			return Err(ExportError::EmptyRegion);
+		}
+		let w = region.w.min(image.width() - region.x);
+		let h = region.h.min(image.height() - region.y);
+		if w == 0 || h == 0 {
+			return Err(ExportError::EmptyRegion);
+		}
+		image = image.crop_imm(region.x, region.y, w, h);
+	}
+	Ok(image.into_rgba8())
+}
+
+/// Creates an empty file next to `source` for the exported image, eg `IMG_0042-edited.png`, and
+/// returns its path. A number is appended to the name if the file already exists.
+///
+/// The file is created so that two exports running at the same time can't pick the same name.
+pub fn reserve_target_path(source: &Path, settings: &ExportSettings) -> io::Result<PathBuf> {
+	let mut name = source.file_stem().unwrap_or_default().to_owned();
+	name.push("-edited.");
+	name.push(settings.extension());
+	let base = source.with_file_name(name);
+	let mut target = base.clone();
+	loop {
+		match fs::OpenOptions::new().write(true).create_new(true).open(&target) {
+			Ok(_) => return Ok(target),
+			Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+				target = destinations::free_path(&base).map_err(io::Error::other)?;
+			}
+			Err(e) => return Err(e),
+		}
+	}
+}
+
+pub fn save(image: RgbaImage, target: &Path, settings: &ExportSettings) -> Result<(), ExportError> {
+	match settings.format {
+		ImageFormat::Jpeg => {
+			let writer = BufWriter::new(fs::File::create(target)?);
+			let encoder = JpegEncoder::new_with_quality(writer, settings.quality);
+			// JPEG has no alpha channel
+			DynamicImage::ImageRgba8(image).into_rgb8().write_with_encoder(encoder)?;
+		}
+		format => image.save_with_format(target, format)?,
+	}
+	Ok(())
+}
+
+/// The source of an export and the path of the exported image
+pub type ExportResult = (PathBuf, Result<PathBuf, ExportError>);
+
+/// Renders and saves the image on a separate thread. The result is sent to `results`.
+pub fn export_in_background(
+	request: ExportRequest,
+	settings: ExportSettings,
+	results: Sender<ExportResult>,
+) {
+	thread::spawn(move || {
+		let result = reserve_target_path(&request.source, &settings).map_err(ExportError::from);
+		let result = result.and_then(|target| {
+			let saved = render(&request).and_then(|image| save(image, &target, &settings));
+			if saved.is_err() {
+				let _ = fs::remove_file(&target);
+			}
+			saved.map(|_| target)
+		});
+		// The receiver is gone if the window was closed
+		let _ = results.send((request.source, result));
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn settings(quality: Option<i64>) -> ExportSettings {
+		let config = ExportSection { format: Some("jpg".into()), quality, visible_region: None };
+		ExportSettings::from_config(Some(&config)).unwrap()
+	}
+
+	#[test]
+	fn quality_is_clamped() {
+		assert_eq!(settings(None).quality, 90);
+		assert_eq!(settings(Some(75)).quality, 75);
+		assert_eq!(settings(Some(0)).quality, 1);
+		assert_eq!(settings(Some(101)).quality, 100);
+		assert_eq!(settings(Some(300)).quality, 100);
+		assert_eq!(settings(Some(-5)).quality, 1);
+	}
+
+	#[test]
+	fn quality_above_255_parses() {
+		let config: ExportSection = toml::from_str("quality = 300").unwrap();
+		assert_eq!(config.quality, Some(300));
+	}
+
+	#[test]
+	fn reserved_paths_are_distinct() {
+		let dir = std::env::temp_dir().join(format!("emulsion-export-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		let source = dir.join("IMG_0042.png");
+		let settings = settings(None);
+		let first = reserve_target_path(&source, &settings).unwrap();
+		let second = reserve_target_path(&source, &settings).unwrap();
+		assert_eq!(first, dir.join("IMG_0042-edited.jpg"));
+		assert_eq!(second, dir.join("IMG_0042-edited (1).jpg"));
+		assert!(first.exists() && second.exists());
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
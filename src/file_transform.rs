@@ -0,0 +1,495 @@
+//! Rotating and flipping image files on disk.
+//!
+//! JPEG and TIFF files are not re-encoded, only their orientation tag is changed (or
+//! inserted). Other formats are decoded, rotated and encoded again in the same format, which
+//! drops their metadata. Animated images and formats that can't be encoded are refused.
+
+use std::convert::{TryFrom, TryInto};
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+
+use gelatin::image::{
+	self,
+	codecs::{gif::GifDecoder, png::PngDecoder},
+	AnimationDecoder, DynamicImage, ImageError, ImageFormat,
+};
+
+use crate::image_cache::image_loader::{detect_format, detect_orientation, ImgFormat, Orientation};
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const ORIENTATION_TAG: u16 = 0x0112;
+const TYPE_SHORT: u16 = 3;
+const IFD_ENTRY_LEN: usize = 12;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FileTransform {
+	RotateCw,
+	RotateCcw,
+	FlipHorizontal,
+}
+
+impl FileTransform {
+	fn apply(self, orientation: Orientation) -> Orientation {
+		match self {
+			FileTransform::RotateCw => orientation.rotated_cw(),
+			FileTransform::RotateCcw => orientation.rotated_ccw(),
+			FileTransform::FlipHorizontal => orientation.flipped_h(),
+		}
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransformError {
+	#[error("{0} images can not be saved")]
+	Unsupported(String),
+
+	#[error("animated images can not be rotated")]
+	Animated,
+
+	#[error("{0}")]
+	Image(#[from] ImageError),
+
+	#[error("the JPEG file is malformed: {0}")]
+	InvalidJpeg(&'static str),
+
+	#[error("the TIFF data is malformed")]
+	InvalidTiff,
+
+	#[error("the Exif data does not fit into a single JPEG segment")]
+	ExifTooLarge,
+
+	#[error("IO error while transforming the file: {0}")]
+	Io(#[from] io::Error),
+}
+
+/// Rotates or flips the image file at `path` relative to its current Exif orientation.
+///
+/// See the module documentation for how each format is handled.
+pub fn transform_file(path: &Path, transform: FileTransform) -> Result<(), TransformError> {
+	let format = match detect_format(path) {
+		Ok(ImgFormat::Image(format)) => format,
+		Ok(ImgFormat::Svg) => return Err(TransformError::Unsupported("SVG".into())),
+		Err(e) => return Err(io::Error::other(e.to_string()).into()),
+	};
+	let orientation = transform.apply(detect_orientation(path).unwrap_or_default());
+	let data = match format {
+		ImageFormat::Jpeg => set_jpeg_orientation(&fs::read(path)?, orientation.to_exif())?,
+		ImageFormat::Tiff => set_tiff_orientation(&fs::read(path)?, orientation.to_exif())?,
+		format => reencode(&fs::read(path)?, format, orientation)?,
+	};
+	replace_file(path, &data)
+}
+
+/// Decodes the image, applies the orientation to its pixels and encodes it in the same
+/// format. The result has no orientation tag, so it's displayed the same way everywhere.
+fn reencode(
+	data: &[u8],
+	format: ImageFormat,
+	orientation: Orientation,
+) -> Result<Vec<u8>, TransformError> {
+	if is_animated(data, format)? {
+		return Err(TransformError::Animated);
+	}
+	let mut reader = image::ImageReader::with_format(Cursor::new(data), format);
+	reader.no_limits();
+	let image = apply_orientation(reader.decode()?, orientation);
+	let mut result = Vec::new();
+	image.write_to(&mut Cursor::new(&mut result), format).map_err(|e| match e {
+		ImageError::Unsupported(_) => TransformError::Unsupported(format!("{:?}", format)),
+		e => e.into(),
+	})?;
+	Ok(result)
+}
+
+/// Returns true if the image has more than one frame. Only the first frame would be kept by
+/// re-encoding it.
+fn is_animated(data: &[u8], format: ImageFormat) -> Result<bool, ImageError> {
+	match format {
+		ImageFormat::Gif => {
+			let frames = GifDecoder::new(Cursor::new(data))?.into_frames();
+			Ok(frames.take(2).count() > 1)
+		}
+		ImageFormat::Png => PngDecoder::new(Cursor::new(data))?.is_apng(),
+		_ => Ok(false),
+	}
+}
+
+/// Writes `data` into a temporary file next to `path` and then moves it in place of the
+/// original, so that the original is left intact if writing fails.
+fn replace_file(path: &Path, data: &[u8]) -> Result<(), TransformError> {
+	let tmp_path = tmp_path_for(path);
+	let result = fs::write(&tmp_path, data).and_then(|_| fs::rename(&tmp_path, path));
+	if result.is_err() {
+		let _ = fs::remove_file(&tmp_path);
+	}
+	Ok(result?)
+}
+
+fn tmp_path_for(path: &Path) -> PathBuf {
+	let mut name = std::ffi::OsString::from(".");
+	name.push(path.file_name().unwrap_or_default());
+	name.push(".emulsion-tmp");
+	path.with_file_name(name)
+}
+
+/// Returns the image as it's displayed with the given orientation.
+pub fn apply_orientation(image: DynamicImage, orientation: Orientation) -> DynamicImage {
+	match orientation {
+		Orientation::Deg0 => image,
+		Orientation::Deg0HorFlip => image.fliph(),
+		Orientation::Deg90 => image.rotate270(),
+		Orientation::Deg90VerFlip => image.rotate270().flipv(),
+		Orientation::Deg180 => image.rotate180(),
+		Orientation::Deg180HorFlip => image.flipv(),
+		Orientation::Deg270 => image.rotate90(),
+		Orientation::Deg270VerFlip => image.rotate90().flipv(),
+	}
+}
+
+#[derive(Copy, Clone)]
+enum ByteOrder {
+	Little,
+	Big,
+}
+
+impl ByteOrder {
+	fn read_u16(self, bytes: &[u8], at: usize) -> Option<u16> {
+		let bytes: [u8; 2] = bytes.get(at..at + 2)?.try_into().ok()?;
+		Some(match self {
+			ByteOrder::Little => u16::from_le_bytes(bytes),
+			ByteOrder::Big => u16::from_be_bytes(bytes),
+		})
+	}
+
+	fn read_u32(self, bytes: &[u8], at: usize) -> Option<u32> {
+		let bytes: [u8; 4] = bytes.get(at..at + 4)?.try_into().ok()?;
+		Some(match self {
+			ByteOrder::Little => u32::from_le_bytes(bytes),
+			ByteOrder::Big => u32::from_be_bytes(bytes),
+		})
+	}
+
+	fn u16_bytes(self, value: u16) -> [u8; 2] {
+		match self {
+			ByteOrder::Little => value.to_le_bytes(),
+			ByteOrder::Big => value.to_be_bytes(),
+		}
+	}
+
+	fn u32_bytes(self, value: u32) -> [u8; 4] {
+		match self {
+			ByteOrder::Little => value.to_le_bytes(),
+			ByteOrder::Big => value.to_be_bytes(),
+		}
+	}
+}
+
+/// Returns a copy of the JPEG file with the orientation tag set to `exif_orientation`.
+///
+/// The tag is inserted into the Exif data if it's missing and an Exif segment is inserted if
+/// the file doesn't have one.
+fn set_jpeg_orientation(data: &[u8], exif_orientation: u16) -> Result<Vec<u8>, TransformError> {
+	if data.get(0..2) != Some(&[0xFF, 0xD8]) {
+		return Err(TransformError::InvalidJpeg("missing start of image marker"));
+	}
+	// The new Exif segment is placed after the JFIF segment if there's one.
+	let mut insert_at = 2;
+	// The start and the end of the existing Exif segment
+	let mut exif_segment = None;
+	let mut pos = 2;
+	// The segments are checked up to the image data even after the Exif segment is found, so
+	// that a truncated file is not written back.
+	loop {
+		if data.get(pos) != Some(&0xFF) {
+			return Err(TransformError::InvalidJpeg("expected a marker"));
+		}
+		let marker = *data.get(pos + 1).ok_or(TransformError::InvalidJpeg("unexpected end"))?;
+		if marker == 0xFF {
+			// Fill byte
+			pos += 1;
+			continue;
+		}
+		// Start of scan or end of image. There's no Exif data before the image data.
+		if marker == 0xDA || marker == 0xD9 {
+			break;
+		}
+		let len = ByteOrder::Big
+			.read_u16(data, pos + 2)
+			.ok_or(TransformError::InvalidJpeg("unexpected end"))? as usize;
+		let segment_end = pos + 2 + len;
+		if len < 2 || segment_end > data.len() {
+			return Err(TransformError::InvalidJpeg("invalid segment length"));
+		}
+		let payload = &data[pos + 4..segment_end];
+		if marker == 0xE1 && payload.starts_with(EXIF_HEADER) && exif_segment.is_none() {
+			exif_segment = Some((pos, segment_end));
+		}
+		if marker == 0xE0 && insert_at == pos {
+			insert_at = segment_end;
+		}
+		pos = segment_end;
+	}
+
+	if let Some((start, end)) = exif_segment {
+		let payload = &data[start + 4..end];
+		let tiff = set_tiff_orientation(&payload[EXIF_HEADER.len()..], exif_orientation)?;
+		let mut result = Vec::with_capacity(data.len() + IFD_ENTRY_LEN);
+		result.extend_from_slice(&data[..start]);
+		write_exif_segment(&mut result, &tiff)?;
+		result.extend_from_slice(&data[end..]);
+		return Ok(result);
+	}
+
+	// Minimal big endian TIFF structure with a single IFD holding only the orientation
+	let mut tiff = b"MM\0\x2A".to_vec();
+	tiff.extend_from_slice(&8u32.to_be_bytes());
+	tiff.extend_from_slice(&1u16.to_be_bytes());
+	tiff.extend_from_slice(&orientation_entry(ByteOrder::Big, exif_orientation));
+	tiff.extend_from_slice(&0u32.to_be_bytes());
+
+	let mut result = Vec::with_capacity(data.len() + tiff.len() + 10);
+	result.extend_from_slice(&data[..insert_at]);
+	write_exif_segment(&mut result, &tiff)?;
+	result.extend_from_slice(&data[insert_at..]);
+	Ok(result)
+}
+
+fn write_exif_segment(out: &mut Vec<u8>, tiff: &[u8]) -> Result<(), TransformError> {
+	let len = 2 + EXIF_HEADER.len() + tiff.len();
+	let len = u16::try_from(len).map_err(|_| TransformError::ExifTooLarge)?;
+	out.extend_from_slice(&[0xFF, 0xE1]);
+	out.extend_from_slice(&len.to_be_bytes());
+	out.extend_from_slice(EXIF_HEADER);
+	out.extend_from_slice(tiff);
+	Ok(())
+}
+
+fn orientation_entry(order: ByteOrder, exif_orientation: u16) -> [u8; IFD_ENTRY_LEN] {
+	let mut entry = [0; IFD_ENTRY_LEN];
+	entry[0..2].copy_from_slice(&order.u16_bytes(ORIENTATION_TAG));
+	entry[2..4].copy_from_slice(&order.u16_bytes(TYPE_SHORT));
+	entry[4..8].copy_from_slice(&order.u32_bytes(1));
+	entry[8..10].copy_from_slice(&order.u16_bytes(exif_orientation));
+	entry
+}
+
+/// Returns a copy of the TIFF structure with the orientation of the first IFD set to
+/// `exif_orientation`.
+///
+/// An existing orientation entry is overwritten in place. Otherwise the first IFD is rewritten
+/// with the new entry at the end of the data, because growing it in place would move the data
+/// after it and break the offsets pointing there, including the ones inside of maker notes
+/// that can't be fixed up. The previous copy of the IFD is left in place but not referenced
+/// anymore.
+fn set_tiff_orientation(tiff: &[u8], exif_orientation: u16) -> Result<Vec<u8>, TransformError> {
+	const INVALID: TransformError = TransformError::InvalidTiff;
+	let order = match tiff.get(0..2) {
+		Some(b"II") => ByteOrder::Little,
+		Some(b"MM") => ByteOrder::Big,
+		_ => return Err(INVALID),
+	};
+	if order.read_u16(tiff, 2) != Some(42) {
+		return Err(INVALID);
+	}
+	let ifd_start = order.read_u32(tiff, 4).ok_or(INVALID)? as usize;
+	let entry_count = order.read_u16(tiff, ifd_start).ok_or(INVALID)? as usize;
+	let entries_start = ifd_start + 2;
+	let entries_end = entries_start + entry_count * IFD_ENTRY_LEN;
+	let next_ifd = order.read_u32(tiff, entries_end).ok_or(INVALID)?;
+
+	let mut result = tiff.to_vec();
+	for i in 0..entry_count {
+		let entry = entries_start + i * IFD_ENTRY_LEN;
+		if order.read_u16(tiff, entry) == Some(ORIENTATION_TAG) {
+			result[entry..entry + IFD_ENTRY_LEN]
+				.copy_from_slice(&orientation_entry(order, exif_orientation));
+			return Ok(result);
+		}
+	}
+
+	// Entries have to be sorted by their tag
+	let mut entries: Vec<&[u8]> = tiff[entries_start..entries_end].chunks(IFD_ENTRY_LEN).collect();
+	let new_entry = orientation_entry(order, exif_orientation);
+	let insert_idx = entries
+		.iter()
+		.position(|e| order.read_u16(e, 0).is_some_and(|tag| tag > ORIENTATION_TAG))
+		.unwrap_or(entries.len());
+	entries.insert(insert_idx, &new_entry);
+
+	// IFDs must start on a word boundary
+	if !result.len().is_multiple_of(2) {
+		result.push(0);
+	}
+	let new_ifd_start = u32::try_from(result.len()).map_err(|_| TransformError::ExifTooLarge)?;
+	result.extend_from_slice(&order.u16_bytes(entries.len() as u16));
+	for entry in entries {
+		result.extend_from_slice(entry);
+	}
+	result.extend_from_slice(&order.u32_bytes(next_ifd));
+	result[4..8].copy_from_slice(&order.u32_bytes(new_ifd_start));
+	Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const IMAGE_WIDTH_TAG: u16 = 0x0100;
+	const RESOLUTION_UNIT_TAG: u16 = 0x0128;
+
+	/// A TIFF structure with a single IFD that holds `entries` of the `SHORT` type
+	fn tiff(order: ByteOrder, entries: &[(u16, u16)]) -> Vec<u8> {
+		let mut tiff = match order {
+			ByteOrder::Little => b"II".to_vec(),
+			ByteOrder::Big => b"MM".to_vec(),
+		};
+		tiff.extend_from_slice(&order.u16_bytes(42));
+		tiff.extend_from_slice(&order.u32_bytes(8));
+		tiff.extend_from_slice(&order.u16_bytes(entries.len() as u16));
+		for &(tag, value) in entries {
+			tiff.extend_from_slice(&order.u16_bytes(tag));
+			tiff.extend_from_slice(&order.u16_bytes(TYPE_SHORT));
+			tiff.extend_from_slice(&order.u32_bytes(1));
+			tiff.extend_from_slice(&order.u16_bytes(value));
+			tiff.extend_from_slice(&[0, 0]);
+		}
+		tiff.extend_from_slice(&order.u32_bytes(0));
+		tiff
+	}
+
+	/// A JPEG file with a JFIF segment, the Exif segment if `tiff` is given and a dummy scan
+	fn jpeg(tiff: Option<&[u8]>) -> Vec<u8> {
+		let mut jpeg = vec![0xFF, 0xD8];
+		jpeg.extend_from_slice(&[0xFF, 0xE0, 0, 16]);
+		jpeg.extend_from_slice(b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+		if let Some(tiff) = tiff {
+			write_exif_segment(&mut jpeg, tiff).unwrap();
+		}
+		jpeg.extend_from_slice(&[0xFF, 0xDA, 0, 4, 0, 0, 0x12, 0x34, 0xFF, 0xD9]);
+		jpeg
+	}
+
+	fn sos_pos(jpeg: &[u8]) -> usize {
+		jpeg.windows(2).position(|w| w == [0xFF, 0xDA]).unwrap()
+	}
+
+	fn field(exif: &exif::Exif, tag: exif::Tag) -> Option<u32> {
+		exif.get_field(tag, exif::In::PRIMARY).and_then(|f| f.value.get_uint(0))
+	}
+
+	fn read_tiff(tiff: &[u8]) -> exif::Exif {
+		exif::Reader::new().read_raw(tiff.to_vec()).unwrap()
+	}
+
+	fn read_jpeg(jpeg: &[u8]) -> exif::Exif {
+		exif::Reader::new().read_from_container(&mut io::Cursor::new(jpeg)).unwrap()
+	}
+
+	#[test]
+	fn tiff_orientation_is_replaced_in_place() {
+		for order in [ByteOrder::Little, ByteOrder::Big] {
+			let original = tiff(order, &[(IMAGE_WIDTH_TAG, 640), (ORIENTATION_TAG, 1)]);
+			let result = set_tiff_orientation(&original, 6).unwrap();
+			assert_eq!(result.len(), original.len());
+			let exif = read_tiff(&result);
+			assert_eq!(field(&exif, exif::Tag::Orientation), Some(6));
+			assert_eq!(field(&exif, exif::Tag::ImageWidth), Some(640));
+			// Only the value of the orientation entry changed
+			let changed = original.iter().zip(&result).filter(|(a, b)| a != b).count();
+			assert_eq!(changed, 1);
+		}
+	}
+
+	#[test]
+	fn missing_tiff_orientation_is_inserted() {
+		for order in [ByteOrder::Little, ByteOrder::Big] {
+			let original = tiff(order, &[(IMAGE_WIDTH_TAG, 640), (RESOLUTION_UNIT_TAG, 2)]);
+			let result = set_tiff_orientation(&original, 8).unwrap();
+			assert!(result.starts_with(&original[..4]));
+			let exif = read_tiff(&result);
+			assert_eq!(field(&exif, exif::Tag::Orientation), Some(8));
+			assert_eq!(field(&exif, exif::Tag::ImageWidth), Some(640));
+			assert_eq!(field(&exif, exif::Tag::ResolutionUnit), Some(2));
+			// The entries stay sorted by their tag
+			let ifd_start = order.read_u32(&result, 4).unwrap() as usize;
+			let tags = (0..3)
+				.map(|i| order.read_u16(&result, ifd_start + 2 + i * IFD_ENTRY_LEN).unwrap())
+				.collect::<Vec<_>>();
+			assert_eq!(tags, [IMAGE_WIDTH_TAG, ORIENTATION_TAG, RESOLUTION_UNIT_TAG]);
+		}
+	}
+
+	#[test]
+	fn truncated_tiff_is_an_error() {
+		for order in [ByteOrder::Little, ByteOrder::Big] {
+			let original = tiff(order, &[(IMAGE_WIDTH_TAG, 640), (ORIENTATION_TAG, 1)]);
+			for len in 0..original.len() {
+				assert!(set_tiff_orientation(&original[..len], 3).is_err(), "length {}", len);
+			}
+		}
+	}
+
+	#[test]
+	fn jpeg_orientation_is_replaced() {
+		for order in [ByteOrder::Little, ByteOrder::Big] {
+			let original = jpeg(Some(&tiff(order, &[(ORIENTATION_TAG, 1)])));
+			let result = set_jpeg_orientation(&original, 3).unwrap();
+			assert_eq!(result.len(), original.len());
+			assert_eq!(field(&read_jpeg(&result), exif::Tag::Orientation), Some(3));
+			assert_eq!(result[sos_pos(&result)..], original[sos_pos(&original)..]);
+		}
+	}
+
+	#[test]
+	fn missing_jpeg_orientation_is_inserted() {
+		for order in [ByteOrder::Little, ByteOrder::Big] {
+			let original = jpeg(Some(&tiff(order, &[(IMAGE_WIDTH_TAG, 640)])));
+			let result = set_jpeg_orientation(&original, 5).unwrap();
+			let exif = read_jpeg(&result);
+			assert_eq!(field(&exif, exif::Tag::Orientation), Some(5));
+			assert_eq!(field(&exif, exif::Tag::ImageWidth), Some(640));
+			assert_eq!(result[sos_pos(&result)..], original[sos_pos(&original)..]);
+		}
+	}
+
+	#[test]
+	fn missing_exif_segment_is_inserted_after_jfif() {
+		let original = jpeg(None);
+		let result = set_jpeg_orientation(&original, 6).unwrap();
+		assert_eq!(field(&read_jpeg(&result), exif::Tag::Orientation), Some(6));
+		// SOI and the JFIF segment come first
+		assert_eq!(result[..20], original[..20]);
+		assert_eq!(result[20..22], [0xFF, 0xE1]);
+		assert_eq!(result[sos_pos(&result)..], original[sos_pos(&original)..]);
+	}
+
+	#[test]
+	fn truncated_jpeg_is_an_error() {
+		let original = jpeg(Some(&tiff(ByteOrder::Big, &[(ORIENTATION_TAG, 1)])));
+		for len in 0..=sos_pos(&original) + 1 {
+			assert!(set_jpeg_orientation(&original[..len], 3).is_err(), "length {}", len);
+		}
+	}
+
+	#[test]
+	fn png_is_rotated_and_reencoded() {
+		let mut png = Vec::new();
+		let image = image::RgbaImage::from_fn(2, 1, |x, _| image::Rgba([x as u8, 0, 0, 255]));
+		image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap();
+		let orientation = FileTransform::RotateCw.apply(Orientation::Deg0);
+		let result = reencode(&png, ImageFormat::Png, orientation).unwrap();
+		let rotated = image::load_from_memory_with_format(&result, ImageFormat::Png).unwrap();
+		let rotated = rotated.into_rgba8();
+		assert_eq!(rotated.dimensions(), (1, 2));
+		// The left pixel is at the top after rotating clockwise
+		assert_eq!(rotated.get_pixel(0, 0)[0], 0);
+		assert_eq!(rotated.get_pixel(0, 1)[0], 1);
+	}
+
+	#[test]
+	fn not_a_jpeg_is_an_error() {
+		let tiff = tiff(ByteOrder::Big, &[(ORIENTATION_TAG, 1)]);
+		assert!(set_jpeg_orientation(&tiff, 3).is_err());
+	}
+}
@@ -0,0 +1,182 @@
+//! The distribution of the channel values of images.
+
+use std::path::PathBuf;
+
+use gelatin::image::{GrayImage, Luma, RgbaImage};
+
+use crate::export::{self, ExportRequest};
+use crate::image_cache::image_loader::Orientation;
+
+/// The red, green, blue and luma channels, in the order they are stored in a `Histogram`
+pub const CHANNEL_NAMES: [&str; 4] = ["R", "G", "B", "L"];
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ChannelStats {
+	pub min: u8,
+	pub max: u8,
+	pub mean: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+	/// The number of pixels with each value, per channel
+	pub bins: [[u32; 256]; 4],
+	pub stats: [ChannelStats; 4],
+	/// The percentage of pixels with at least one color channel at 0
+	pub clipped_shadows: f64,
+	/// The percentage of pixels with at least one color channel at 255
+	pub clipped_highlights: f64,
+}
+
+impl Histogram {
+	/// Fully transparent pixels are ignored
+	pub fn from_image(image: &RgbaImage) -> Histogram {
+		let mut bins = [[0u32; 256]; 4];
+		let mut shadows = 0u64;
+		let mut highlights = 0u64;
+		let mut count = 0u64;
+		for pixel in image.pixels() {
+			let [r, g, b, a] = pixel.0;
+			if a == 0 {
+				continue;
+			}
+			let luma = (2126 * r as u32 + 7152 * g as u32 + 722 * b as u32 + 5000) / 10000;
+			for (channel, value) in bins.iter_mut().zip([r, g, b, luma as u8]) {
+				channel[value as usize] += 1;
+			}
+			if r == 0 || g == 0 || b == 0 {
+				shadows += 1;
+			}
+			if r == 255 || g == 255 || b == 255 {
+				highlights += 1;
+			}
+			count += 1;
+		}
+		let mut stats = [ChannelStats::default(); 4];
+		for (stats, channel) in stats.iter_mut().zip(bins.iter()) {
+			let mut values = channel.iter().enumerate().filter(|(_, &n)| n > 0).map(|(v, _)| v);
+			stats.min = values.next().unwrap_or(0) as u8;
+			stats.max = values.next_back().map_or(stats.min, |v| v as u8);
+			let sum: u64 = channel.iter().enumerate().map(|(v, &n)| v as u64 * n as u64).sum();
+			stats.mean = sum as f64 / count.max(1) as f64;
+		}
+		let percent = |n: u64| 100.0 * n as f64 / count.max(1) as f64;
+		Histogram {
+			bins,
+			stats,
+			clipped_shadows: percent(shadows),
+			clipped_highlights: percent(highlights),
+		}
+	}
+
+	/// Draws the histogram of a channel as a 256 pixel wide mask, filled below the curve.
+	///
+	/// All channels use the same scale. The first and the last value are left out from the
+	/// scale, otherwise clipped images would only show a spike at the edges.
+	pub fn channel_mask(&self, channel: usize, height: u32) -> GrayImage {
+		let inner_peak =
+			self.bins.iter().flat_map(|c| c[1..255].iter()).max().copied().unwrap_or(0);
+		let peak = if inner_peak > 0 {
+			inner_peak
+		} else {
+			self.bins.iter().flat_map(|c| c.iter()).max().copied().unwrap_or(0)
+		};
+		let mut mask = GrayImage::new(256, height);
+		for (x, &n) in self.bins[channel].iter().enumerate() {
+			let bar = (n as f64 / peak.max(1) as f64).min(1.0) * height as f64;
+			let bar = bar.ceil() as u32;
+			for y in height - bar..height {
+				mask.put_pixel(x as u32, y, Luma([255]));
+			}
+		}
+		mask
+	}
+}
+
+/// The output of the worker thread: the image file and its histogram
+pub type HistogramOutput = (PathBuf, Result<Histogram, String>);
+
+/// Loads the first frame of the image and computes its histogram, meant to be run by a
+/// `ParallelAction`. The image loader computes the histograms of the images it decodes
+/// while the histogram is shown, this is only needed for the images that were loaded
+/// before.
+pub fn histogram_worker(path: PathBuf) -> HistogramOutput {
+	let request =
+		ExportRequest { source: path.clone(), view_orientation: Orientation::Deg0, region: None };
+	let result = export::render(&request).map(|image| Histogram::from_image(&image));
+	(path, result.map_err(|e| e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use gelatin::image::Rgba;
+
+	#[test]
+	fn transparent_image_is_empty() {
+		let histogram = Histogram::from_image(&RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 0])));
+		assert!(histogram.bins.iter().all(|channel| channel.iter().all(|&n| n == 0)));
+		assert_eq!(histogram.stats, [ChannelStats::default(); 4]);
+		assert_eq!(histogram.clipped_shadows, 0.0);
+		assert_eq!(histogram.clipped_highlights, 0.0);
+
+		let mask = histogram.channel_mask(0, 10);
+		assert_eq!(mask.dimensions(), (256, 10));
+		assert!(mask.pixels().all(|p| p.0 == [0]));
+	}
+
+	#[test]
+	fn single_value_image() {
+		let histogram =
+			Histogram::from_image(&RgbaImage::from_pixel(3, 2, Rgba([100, 100, 100, 255])));
+		for (channel, stats) in histogram.bins.iter().zip(histogram.stats.iter()) {
+			assert_eq!(channel[100], 6);
+			assert_eq!(channel.iter().sum::<u32>(), 6);
+			assert_eq!(*stats, ChannelStats { min: 100, max: 100, mean: 100.0 });
+		}
+		assert_eq!(histogram.clipped_shadows, 0.0);
+		assert_eq!(histogram.clipped_highlights, 0.0);
+
+		for channel in 0..4 {
+			let mask = histogram.channel_mask(channel, 8);
+			for (x, _, pixel) in mask.enumerate_pixels() {
+				assert_eq!(pixel.0[0], if x == 100 { 255 } else { 0 }, "column {}", x);
+			}
+		}
+	}
+
+	#[test]
+	fn transparent_pixels_are_ignored() {
+		let mut image = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 0]));
+		image.put_pixel(0, 0, Rgba([255, 10, 0, 255]));
+		image.put_pixel(1, 0, Rgba([20, 30, 40, 128]));
+		let histogram = Histogram::from_image(&image);
+		assert_eq!(histogram.bins[0].iter().sum::<u32>(), 2);
+		assert_eq!(histogram.stats[0], ChannelStats { min: 20, max: 255, mean: 137.5 });
+		assert_eq!(histogram.stats[2], ChannelStats { min: 0, max: 40, mean: 20.0 });
+		// The luma of the first pixel is 0.2126 * 255 + 0.7152 * 10 rounded
+		assert_eq!(histogram.bins[3][61], 1);
+		assert_eq!(histogram.clipped_shadows, 50.0);
+		assert_eq!(histogram.clipped_highlights, 50.0);
+	}
+
+	#[test]
+	fn mask_scale_leaves_out_the_edges() {
+		let mut image = RgbaImage::from_pixel(10, 1, Rgba([255, 255, 255, 255]));
+		image.put_pixel(0, 0, Rgba([128, 128, 128, 255]));
+		image.put_pixel(1, 0, Rgba([128, 128, 128, 255]));
+		image.put_pixel(2, 0, Rgba([64, 64, 64, 255]));
+		let histogram = Histogram::from_image(&image);
+		let mask = histogram.channel_mask(0, 4);
+		let bar = |x: u32| (0..4).filter(|&y| mask.get_pixel(x, y).0[0] == 255).count();
+		// The 7 clipped pixels are above the peak of 2 and fill the whole height
+		assert_eq!(bar(255), 4);
+		assert_eq!(bar(128), 4);
+		assert_eq!(bar(64), 2);
+		assert_eq!(bar(0), 0);
+		// The bars are filled from the bottom
+		assert_eq!(mask.get_pixel(64, 3).0[0], 255);
+		assert_eq!(mask.get_pixel(64, 0).0[0], 0);
+	}
+}
@@ -31,8 +31,7 @@ impl From<std::io::Error> for Error {
 }
 impl From<Error> for std::io::Error {
 	fn from(value: Error) -> Self {
-		use std::io::ErrorKind;
-		std::io::Error::new(ErrorKind::Other, format!("directory::Error: {}", value))
+		std::io::Error::other(format!("directory::Error: {}", value))
 	}
 }
 
@@ -207,6 +206,15 @@ impl Directory {
 		Some(self.img_i_to_file_i.len())
 	}
 
+	/// Returns the paths of the images in the order of their indices, or `None` when the
+	/// folder hasn't finished filtering
+	pub fn image_paths(&mut self) -> Option<Vec<PathBuf>> {
+		if !self.check_filter_ready() {
+			return None;
+		}
+		Some(self.img_i_to_file_i.iter().map(|&i| self.files[i].path.clone()).collect())
+	}
+
 	/// Return None if the number of images haven't been calculated yet
 	pub fn image_by_index(&mut self, idx: usize) -> Option<&DirItem> {
 		if !self.check_filter_ready() {
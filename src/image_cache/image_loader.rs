@@ -10,10 +10,14 @@ use std::thread;
 use gelatin::image::{
 	self,
 	codecs::{gif::GifDecoder, png::PngDecoder},
-	AnimationDecoder, ImageFormat,
+	imageops, AnimationDecoder, DynamicImage, ImageFormat,
 };
+use log::debug;
 use usvg::fontdb;
 
+use crate::file_transform::apply_orientation;
+use crate::histogram::Histogram;
+
 #[derive(Debug, thiserror::Error)]
 #[error("error happened in the image_loader: {description}")]
 pub struct ImageLoaderError {
@@ -42,7 +46,7 @@ pub type Result<T> = std::result::Result<T, ImageLoaderError>;
 /// which will only carry out the request if the focused request id matches their request or
 /// if the focused is set to `NON_EXISTENT_REQUEST_ID`
 pub static PRIORITY_REQUEST_ID: AtomicU32 = AtomicU32::new(0); // The first request usually
-pub const NON_EXISTENT_REQUEST_ID: u32 = std::u32::MAX;
+pub const NON_EXISTENT_REQUEST_ID: u32 = u32::MAX;
 
 pub enum ImgFormat {
 	Image(ImageFormat),
@@ -55,7 +59,7 @@ pub enum ImgFormat {
 /// image. This is represented by the value `Deg0`. All other cases must be interpreted as relative
 /// to this. The rotation part is counter-clockwise. When there's a flip it's always interpreted as
 /// if it happened after the rotation.
-#[derive(Default, Debug, Copy, Clone)]
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Orientation {
 	/// Exif 1
 	#[default]
@@ -83,6 +87,99 @@ pub enum Orientation {
 	Deg90,
 }
 
+impl Orientation {
+	const ALL: [Orientation; 8] = [
+		Orientation::Deg0,
+		Orientation::Deg0HorFlip,
+		Orientation::Deg180,
+		Orientation::Deg180HorFlip,
+		Orientation::Deg90VerFlip,
+		Orientation::Deg270,
+		Orientation::Deg270VerFlip,
+		Orientation::Deg90,
+	];
+
+	/// According to page 30 of http://www.cipa.jp/std/documents/e/DC-008-2012_E.pdf
+	pub fn from_exif(value: u16) -> Option<Orientation> {
+		Self::ALL.get((value as usize).checked_sub(1)?).copied()
+	}
+
+	pub fn to_exif(self) -> u16 {
+		Self::ALL.iter().position(|&o| o == self).unwrap() as u16 + 1
+	}
+
+	/// Returns the orientation which displays the image rotated by 90 degrees clockwise
+	/// compared to how `self` displays it.
+	pub fn rotated_cw(self) -> Orientation {
+		self.then([[0, -1], [1, 0]])
+	}
+
+	/// Returns the orientation which displays the image rotated by 90 degrees
+	/// counter-clockwise compared to how `self` displays it.
+	pub fn rotated_ccw(self) -> Orientation {
+		self.then([[0, 1], [-1, 0]])
+	}
+
+	/// Returns the orientation which displays the image mirrored horizontally compared to how
+	/// `self` displays it.
+	pub fn flipped_h(self) -> Orientation {
+		self.then([[-1, 0], [0, 1]])
+	}
+
+	/// Returns the orientation which displays the image mirrored vertically compared to how
+	/// `self` displays it.
+	pub fn flipped_v(self) -> Orientation {
+		self.then([[1, 0], [0, -1]])
+	}
+
+	/// Returns the orientation which is equivalent to first applying `self` and then `other`
+	pub fn followed_by(self, other: Orientation) -> Orientation {
+		self.then(other.matrix())
+	}
+
+	/// Maps the pixel at (`x`, `y`) of the displayed image to the pixel of the stored image
+	/// that's displayed there. `w` and `h` are the dimensions of the stored image.
+	pub fn displayed_to_stored(self, x: u32, y: u32, w: u32, h: u32) -> (u32, u32) {
+		let m = self.matrix();
+		let (disp_w, disp_h) = if m[0][0] == 0 { (h, w) } else { (w, h) };
+		// Doubled coordinates of the pixel center relative to the center of the image so that
+		// they are always integers.
+		let dx = 2 * x as i64 + 1 - disp_w as i64;
+		let dy = 2 * y as i64 + 1 - disp_h as i64;
+		// The matrix is orthogonal so its inverse is its transpose
+		let sx = m[0][0] as i64 * dx + m[1][0] as i64 * dy;
+		let sy = m[0][1] as i64 * dx + m[1][1] as i64 * dy;
+		(((sx + w as i64 - 1) / 2) as u32, ((sy + h as i64 - 1) / 2) as u32)
+	}
+
+	/// Row-major matrix that maps the position of a stored pixel to its displayed position.
+	/// Positions are relative to the center of the image with the y axis pointing downwards.
+	fn matrix(self) -> [[i8; 2]; 2] {
+		match self {
+			Orientation::Deg0 => [[1, 0], [0, 1]],
+			Orientation::Deg0HorFlip => [[-1, 0], [0, 1]],
+			Orientation::Deg180 => [[-1, 0], [0, -1]],
+			Orientation::Deg180HorFlip => [[1, 0], [0, -1]],
+			Orientation::Deg90VerFlip => [[0, 1], [1, 0]],
+			Orientation::Deg270 => [[0, -1], [1, 0]],
+			Orientation::Deg270VerFlip => [[0, -1], [-1, 0]],
+			Orientation::Deg90 => [[0, 1], [-1, 0]],
+		}
+	}
+
+	/// Applies the `transform` matrix after this orientation
+	fn then(self, transform: [[i8; 2]; 2]) -> Orientation {
+		let m = self.matrix();
+		let mut product = [[0; 2]; 2];
+		for (row, product_row) in product.iter_mut().enumerate() {
+			for (col, value) in product_row.iter_mut().enumerate() {
+				*value = transform[row][0] * m[0][col] + transform[row][1] * m[1][col];
+			}
+		}
+		*Self::ALL.iter().find(|o| o.matrix() == product).unwrap()
+	}
+}
+
 /// Detects the format of an image file. It looks at the first 512 bytes;
 /// if that fails, it uses the file ending.
 pub fn detect_format(path: &Path) -> Result<ImgFormat> {
@@ -111,21 +208,10 @@ pub fn detect_orientation(path: &Path) -> std::result::Result<Orientation, exif:
 	if let Some(orientation) = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY) {
 		if let exif::Value::Short(ref shorts) = orientation.value {
 			if let Some(&exif_orientation) = shorts.first() {
-				// According to page 30 of http://www.cipa.jp/std/documents/e/DC-008-2012_E.pdf
-				match exif_orientation {
-					1 => Ok(Orientation::Deg0),
-					2 => Ok(Orientation::Deg0HorFlip),
-					3 => Ok(Orientation::Deg180),
-					4 => Ok(Orientation::Deg180HorFlip),
-					5 => Ok(Orientation::Deg90VerFlip),
-					6 => Ok(Orientation::Deg270),
-					7 => Ok(Orientation::Deg270VerFlip),
-					8 => Ok(Orientation::Deg90),
-					_ => {
-						eprintln!("Invalid Exif orientation. Using default orientation.");
-						Ok(Orientation::Deg0)
-					}
-				}
+				Ok(Orientation::from_exif(exif_orientation).unwrap_or_else(|| {
+					eprintln!("Invalid Exif orientation. Using default orientation.");
+					Orientation::Deg0
+				}))
 			} else {
 				Ok(Orientation::Deg0)
 			}
@@ -218,16 +304,31 @@ where
 				}
 			} else {
 				let image = simple_load_image(path, ImageFormat::Png)?;
-				process_image(LoadResult::Frame { req_id, image, delay_nano: 0, orientation })?;
+				process_image(LoadResult::Frame {
+					req_id,
+					image: Arc::new(image),
+					delay_nano: 0,
+					orientation,
+				})?;
 			}
 		}
 		ImgFormat::Image(image_format) => {
 			let image = simple_load_image(path, image_format)?;
-			process_image(LoadResult::Frame { req_id, image, delay_nano: 0, orientation })?;
+			process_image(LoadResult::Frame {
+				req_id,
+				image: Arc::new(image),
+				delay_nano: 0,
+				orientation,
+			})?;
 		}
 		ImgFormat::Svg => {
 			let image = load_svg(path)?;
-			process_image(LoadResult::Frame { req_id, image, delay_nano: 0, orientation })?;
+			process_image(LoadResult::Frame {
+				req_id,
+				image: Arc::new(image),
+				delay_nano: 0,
+				orientation,
+			})?;
 		}
 	}
 
@@ -251,7 +352,7 @@ fn load_animation(
 			}
 			let numerator_nano = numerator_ms as u64 * 1_000_000;
 			let delay_nano = numerator_nano / (denom as u64);
-			let image = frame.into_buffer();
+			let image = Arc::new(frame.into_buffer());
 			LoadResult::Frame { req_id, image, delay_nano, orientation: Orientation::Deg0 }
 		})?)
 	})
@@ -288,12 +389,25 @@ pub enum LoadResult {
 	},
 	Frame {
 		req_id: u32,
-		image: image::RgbaImage,
+		/// Shared so that the loader can make the thumbnail and the histogram of the first
+		/// frame after sending it
+		image: Arc<image::RgbaImage>,
 		delay_nano: u64,
 
 		/// How much does the image need to be rotated counter-clockwise to be shown correctly
 		orientation: Orientation,
 	},
+	/// The downscaled first frame of the image with its orientation applied
+	Thumbnail {
+		req_id: u32,
+		path: PathBuf,
+		image: image::RgbaImage,
+	},
+	/// The histogram of the first frame
+	Histogram {
+		req_id: u32,
+		histogram: Box<Histogram>,
+	},
 	Done {
 		req_id: u32,
 	},
@@ -307,14 +421,47 @@ impl LoadResult {
 		match self {
 			LoadResult::Start { req_id, .. } => *req_id,
 			LoadResult::Frame { req_id, .. } => *req_id,
+			LoadResult::Thumbnail { req_id, .. } => *req_id,
+			LoadResult::Histogram { req_id, .. } => *req_id,
 			LoadResult::Done { req_id, .. } => *req_id,
 			LoadResult::Failed { req_id, .. } => *req_id,
 		}
 	}
 }
 
+/// Applies the orientation to the image and downscales it to fit in a `size`×`size` square
+pub fn make_thumbnail(
+	image: &image::RgbaImage,
+	orientation: Orientation,
+	size: u32,
+) -> image::RgbaImage {
+	let (w, h) = image.dimensions();
+	let scale = (size as f32 / w.max(h).max(1) as f32).min(1.0);
+	let thumb_w = ((w as f32 * scale).round() as u32).max(1);
+	let thumb_h = ((h as f32 * scale).round() as u32).max(1);
+	let thumbnail = DynamicImage::ImageRgba8(imageops::thumbnail(image, thumb_w, thumb_h));
+	apply_orientation(thumbnail, orientation).into_rgba8()
+}
+
+/// Decodes the first frame of the image and makes a thumbnail of it
+pub fn load_thumbnail(path: &Path, size: u32) -> Result<image::RgbaImage> {
+	let mut thumbnail = None;
+	complex_load_image(path, false, 0, |result| {
+		if let LoadResult::Frame { image, orientation, .. } = result {
+			thumbnail = Some(make_thumbnail(&image, orientation, size));
+		}
+		Ok(())
+	})?;
+	thumbnail.ok_or_else(|| ImageLoaderError { description: "the image has no frames".into() })
+}
+
 pub struct ImageLoader {
 	running: Arc<AtomicBool>,
+	/// The size of the thumbnails sent along with the first frame of each image, zero if
+	/// thumbnails shouldn't be sent
+	thumbnail_size: Arc<AtomicU32>,
+	/// Whether the histogram of the first frame of each image should be sent
+	histograms: Arc<AtomicBool>,
 	join_handles: Option<Vec<thread::JoinHandle<()>>>,
 	image_rx: Receiver<LoadResult>,
 	path_tx: Sender<LoadRequest>,
@@ -324,7 +471,19 @@ impl ImageLoader {
 	/// # Arguemnts
 	/// * `capacity` - Number of bytes. The last image loaded will be the one at which the allocated memory reaches or exceeds capacity
 	pub fn new(threads: u32) -> ImageLoader {
+		Self::with_mode(threads, 0, false)
+	}
+
+	/// Creates a loader that only sends a `LoadResult::Thumbnail` for each request, or
+	/// `LoadResult::Failed`. Its requests are never held back by `PRIORITY_REQUEST_ID`.
+	pub fn new_thumbnail_loader(threads: u32, size: u32) -> ImageLoader {
+		Self::with_mode(threads, size, true)
+	}
+
+	fn with_mode(threads: u32, thumbnail_size: u32, thumbnails_only: bool) -> ImageLoader {
 		let running = Arc::new(AtomicBool::from(true));
+		let thumbnail_size = Arc::new(AtomicU32::new(thumbnail_size));
+		let histograms = Arc::new(AtomicBool::new(false));
 		let (load_request_tx, load_request_rx) = channel();
 		let load_request_rx = Arc::new(Mutex::new(load_request_rx));
 
@@ -333,16 +492,28 @@ impl ImageLoader {
 		let mut join_handles = Vec::new();
 		for _ in 0..threads {
 			let running = running.clone();
+			let thumbnail_size = thumbnail_size.clone();
+			let histograms = histograms.clone();
 			let request_recv = load_request_rx.clone();
 			let request_send = load_request_tx.clone();
 			let img_sender = loaded_img_tx.clone();
 			join_handles.push(thread::spawn(move || {
-				Self::thread_loop(running, request_recv, request_send, img_sender);
+				Self::thread_loop(
+					running,
+					request_recv,
+					request_send,
+					img_sender,
+					thumbnail_size,
+					histograms,
+					thumbnails_only,
+				);
 			}));
 		}
 
 		ImageLoader {
 			running,
+			thumbnail_size,
+			histograms,
 			join_handles: Some(join_handles),
 
 			image_rx: loaded_img_rx,
@@ -355,6 +526,9 @@ impl ImageLoader {
 		request_recv: Arc<Mutex<Receiver<LoadRequest>>>,
 		request_send: Sender<LoadRequest>,
 		img_sender: Sender<LoadResult>,
+		thumbnail_size: Arc<AtomicU32>,
+		histograms: Arc<AtomicBool>,
+		thumbnails_only: bool,
 	) {
 		// The size was an arbitrary choice made with the argument that this should be
 		// enough to fit enough image file info to determine the format.
@@ -367,8 +541,9 @@ impl ImageLoader {
 				let load_request = request_recv.lock().unwrap();
 				let priority = PRIORITY_REQUEST_ID.load(Ordering::SeqCst);
 				request = load_request.recv().unwrap();
-				let focus_test_passed =
-					priority == request.req_id || priority == NON_EXISTENT_REQUEST_ID;
+				let focus_test_passed = thumbnails_only
+					|| priority == request.req_id
+					|| priority == NON_EXISTENT_REQUEST_ID;
 				if !focus_test_passed {
 					//println!("Priority test failed, priority was {}", priority);
 					//DEBUG_FAIL_COUNT += 1;
@@ -378,7 +553,15 @@ impl ImageLoader {
 					continue;
 				}
 			};
-			Self::load_and_send(&img_sender, request);
+			if !running.load(Ordering::Acquire) {
+				break;
+			}
+			let thumbnail_size = thumbnail_size.load(Ordering::Relaxed);
+			if thumbnails_only {
+				Self::load_thumbnail_and_send(&img_sender, request, thumbnail_size);
+			} else {
+				Self::load_and_send(&img_sender, request, thumbnail_size, &histograms);
+			}
 		}
 	}
 
@@ -390,19 +573,66 @@ impl ImageLoader {
 		self.path_tx.send(request).unwrap();
 	}
 
-	fn load_and_send(img_sender: &Sender<LoadResult>, request: LoadRequest) {
-		fn try_load_and_send(img_sender: &Sender<LoadResult>, request: &LoadRequest) -> Result<()> {
+	/// Makes the loader send a `LoadResult::Thumbnail` after the first frame of each image.
+	/// Zero turns the thumbnails off.
+	pub fn set_thumbnail_size(&self, size: u32) {
+		self.thumbnail_size.store(size, Ordering::Relaxed);
+	}
+
+	/// Makes the loader send a `LoadResult::Histogram` after the first frame of each image
+	pub fn set_histograms(&self, enabled: bool) {
+		self.histograms.store(enabled, Ordering::Relaxed);
+	}
+
+	fn load_and_send(
+		img_sender: &Sender<LoadResult>,
+		request: LoadRequest,
+		thumbnail_size: u32,
+		histograms: &AtomicBool,
+	) {
+		fn try_load_and_send(
+			img_sender: &Sender<LoadResult>,
+			request: &LoadRequest,
+			thumbnail_size: u32,
+			histograms: &AtomicBool,
+		) -> Result<()> {
 			let metadata = fs::metadata(&request.path)?;
 			img_sender.send(LoadResult::Start { req_id: request.req_id, metadata }).unwrap();
+			let mut first_frame = true;
 			complex_load_image(&request.path, true, request.req_id, |frame| {
+				let first = match &frame {
+					LoadResult::Frame { image, orientation, .. } if first_frame => {
+						Some((image.clone(), *orientation))
+					}
+					_ => None,
+				};
 				img_sender.send(frame).unwrap();
+				// Made after the frame was sent so that they don't delay showing the image
+				if let Some((image, orientation)) = first {
+					first_frame = false;
+					if thumbnail_size > 0 {
+						let thumbnail = LoadResult::Thumbnail {
+							req_id: request.req_id,
+							path: request.path.clone(),
+							image: make_thumbnail(&image, orientation, thumbnail_size),
+						};
+						img_sender.send(thumbnail).unwrap();
+					}
+					// Only while the histogram panel is open
+					if histograms.load(Ordering::Relaxed) {
+						let histogram = Box::new(Histogram::from_image(&image));
+						img_sender
+							.send(LoadResult::Histogram { req_id: request.req_id, histogram })
+							.unwrap();
+					}
+				}
 				Ok(())
 			})?;
 			Ok(())
 		}
 
 		img_sender
-			.send(match try_load_and_send(img_sender, &request) {
+			.send(match try_load_and_send(img_sender, &request, thumbnail_size, histograms) {
 				Ok(()) => LoadResult::Done { req_id: request.req_id },
 				Err(error) => {
 					eprintln!(
@@ -414,6 +644,20 @@ impl ImageLoader {
 			})
 			.unwrap();
 	}
+
+	fn load_thumbnail_and_send(img_sender: &Sender<LoadResult>, request: LoadRequest, size: u32) {
+		let result = match load_thumbnail(&request.path, size) {
+			Ok(image) => {
+				LoadResult::Thumbnail { req_id: request.req_id, path: request.path, image }
+			}
+			Err(error) => {
+				debug!("Failed to load the thumbnail of {:?}: {}", request.path, error);
+				LoadResult::Failed { req_id: request.req_id }
+			}
+		};
+		// The receiver is gone when the loader is being dropped
+		let _ = img_sender.send(result);
+	}
 }
 
 impl Drop for ImageLoader {
@@ -432,3 +676,99 @@ impl Drop for ImageLoader {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ROTATE_CW: [[i8; 2]; 2] = [[0, -1], [1, 0]];
+	const ROTATE_CCW: [[i8; 2]; 2] = [[0, 1], [-1, 0]];
+	const FLIP_H: [[i8; 2]; 2] = [[-1, 0], [0, 1]];
+	const FLIP_V: [[i8; 2]; 2] = [[1, 0], [0, -1]];
+
+	/// The product `a * b`, which is `b` followed by `a`
+	fn mul(a: [[i8; 2]; 2], b: [[i8; 2]; 2]) -> [[i8; 2]; 2] {
+		let mut product = [[0; 2]; 2];
+		for (row, product_row) in product.iter_mut().enumerate() {
+			for (col, value) in product_row.iter_mut().enumerate() {
+				*value = a[row][0] * b[0][col] + a[row][1] * b[1][col];
+			}
+		}
+		product
+	}
+
+	/// Where the stored pixel at `(x, y)` relative to the center is displayed
+	fn apply(m: [[i8; 2]; 2], (x, y): (i8, i8)) -> (i8, i8) {
+		(m[0][0] * x + m[0][1] * y, m[1][0] * x + m[1][1] * y)
+	}
+
+	#[test]
+	fn displayed_to_stored_matches_the_displayed_image() {
+		use crate::file_transform::apply_orientation;
+		let (w, h) = (3, 2);
+		// Every pixel has a different value
+		let stored =
+			image::RgbaImage::from_fn(w, h, |x, y| image::Rgba([x as u8, y as u8, 0, 255]));
+		for orientation in Orientation::ALL {
+			let displayed =
+				apply_orientation(image::DynamicImage::ImageRgba8(stored.clone()), orientation)
+					.into_rgba8();
+			for (x, y, pixel) in displayed.enumerate_pixels() {
+				let (sx, sy) = orientation.displayed_to_stored(x, y, w, h);
+				assert!(sx < w && sy < h, "{:?} maps ({}, {}) outside", orientation, x, y);
+				assert_eq!(stored.get_pixel(sx, sy), pixel, "{:?} at ({}, {})", orientation, x, y);
+			}
+		}
+	}
+
+	#[test]
+	fn exif_values_round_trip() {
+		for value in 1..=8 {
+			assert_eq!(Orientation::from_exif(value).unwrap().to_exif(), value);
+		}
+		assert_eq!(Orientation::from_exif(0), None);
+		assert_eq!(Orientation::from_exif(9), None);
+	}
+
+	#[test]
+	fn exif_values_match_the_matrices() {
+		// Where the top right corner of the stored image is displayed, according to the Exif
+		// specification. The y axis points downwards.
+		let top_right = [(1, -1), (-1, -1), (-1, 1), (1, 1), (-1, 1), (1, 1), (1, -1), (-1, -1)];
+		for (value, expected) in (1..=8).zip(top_right) {
+			let orientation = Orientation::from_exif(value).unwrap();
+			assert_eq!(apply(orientation.matrix(), (1, -1)), expected, "Exif {}", value);
+		}
+	}
+
+	#[test]
+	fn matrices_are_distinct() {
+		for (i, a) in Orientation::ALL.iter().enumerate() {
+			for b in &Orientation::ALL[i + 1..] {
+				assert_ne!(a.matrix(), b.matrix());
+			}
+		}
+	}
+
+	#[test]
+	fn then_multiplies_the_matrices() {
+		for orientation in Orientation::ALL {
+			for transform in [ROTATE_CW, ROTATE_CCW, FLIP_H, FLIP_V] {
+				let result = orientation.then(transform);
+				assert_eq!(result.matrix(), mul(transform, orientation.matrix()));
+			}
+			assert_eq!(orientation.rotated_cw().matrix(), mul(ROTATE_CW, orientation.matrix()));
+			assert_eq!(orientation.rotated_cw().rotated_ccw(), orientation);
+			assert_eq!(orientation.flipped_h().flipped_h(), orientation);
+			assert_eq!(orientation.flipped_v().flipped_v(), orientation);
+			assert_eq!(
+				orientation.rotated_cw().rotated_cw().rotated_cw().rotated_cw(),
+				orientation
+			);
+			for other in Orientation::ALL {
+				let combined = orientation.followed_by(other);
+				assert_eq!(combined.matrix(), mul(other.matrix(), orientation.matrix()));
+			}
+		}
+	}
+}
@@ -1,6 +1,7 @@
 use std::{
 	borrow::Cow,
-	collections::BTreeMap,
+	cell::RefCell,
+	collections::{BTreeMap, HashMap},
 	ffi::{OsStr, OsString},
 	fs, mem,
 	path::{Path, PathBuf},
@@ -20,6 +21,8 @@ use gelatin::{
 	image,
 };
 
+use crate::histogram::Histogram;
+
 pub mod image_loader;
 
 use self::{directory::DirItem, image_loader::*};
@@ -30,6 +33,9 @@ use pending_requests::PendingRequests;
 pub mod directory;
 use directory::Directory;
 
+pub mod thumbnails;
+use thumbnails::{ThumbnailCache, THUMBNAIL_SIZE};
+
 #[derive(Debug, thiserror::Error)]
 pub enum TextureError {
 	#[error("ImageCache is waiting for the image loader to send result")]
@@ -126,9 +132,6 @@ pub struct AnimationFrameTexture {
 	/// of the cell at the 3rd column and 2nd row is
 	/// (3*cell_step_size, 2*cell_step_size)
 	pub cell_step_size: u32,
-	pub grid_rows: u32,
-	pub grid_cols: u32,
-
 	pub delay_nano: u64,
 	pub orientation: Orientation,
 
@@ -142,12 +145,12 @@ pub struct AnimationFrameTexture {
 impl AnimationFrameTexture {
 	pub fn from_image(
 		display: &gelatin::Display,
-		image: image::RgbaImage,
+		image: &image::RgbaImage,
 		delay_nano: u64,
 		orientation: Orientation,
 	) -> TextureResult<Self> {
 		let (w, h) = image.dimensions();
-		let img_bytes = image.into_raw();
+		let img_bytes = image.as_raw();
 		let mut tex_grid = Vec::new();
 
 		// The reasoning behind dividing by 2 and taking the min with 4*1024, is
@@ -170,7 +173,7 @@ impl AnimationFrameTexture {
 				let cell_w = (w - offset_x).min(max_size);
 				let cell_h = (h - offset_y).min(max_size);
 				let tex = texture_from_img_rect(
-					display, w, h, &img_bytes, offset_x, offset_y, cell_w, cell_h,
+					display, w, h, img_bytes, offset_x, offset_y, cell_w, cell_h,
 				)?;
 				let item = TextureGridItem { tex, col, row };
 				tex_grid.push(item);
@@ -184,11 +187,33 @@ impl AnimationFrameTexture {
 			w,
 			h,
 			cell_step_size: max_size,
-			grid_rows,
-			grid_cols,
 		})
 	}
 
+	/// Reads the color of a pixel back from the GPU. The coordinates are in the oriented image.
+	///
+	/// Returns `None` if the coordinates are outside of the image.
+	pub fn read_pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+		let (oriented_w, oriented_h) = self.oriented_dimensions();
+		if x >= oriented_w || y >= oriented_h {
+			return None;
+		}
+		let (x, y) = self.orientation.displayed_to_stored(x, y, self.w, self.h);
+		let (col, row) = (x / self.cell_step_size, y / self.cell_step_size);
+		let cell = self.tex_grid.iter().find(|cell| cell.col == col && cell.row == row)?;
+		let rect = glium::Rect {
+			left: x - col * self.cell_step_size,
+			// The first row of the uploaded image data is the bottom row of the texture
+			bottom: y - row * self.cell_step_size,
+			width: 1,
+			height: 1,
+		};
+		let image = cell.tex.main_level().first_layer().into_image(None)?;
+		let pixels: Vec<Vec<(u8, u8, u8, u8)>> = image.raw_read(&rect);
+		let (r, g, b, a) = *pixels.first()?.first()?;
+		Some([r, g, b, a])
+	}
+
 	pub fn oriented_dimensions(&self) -> (u32, u32) {
 		use Orientation::*;
 		match self.orientation {
@@ -210,17 +235,16 @@ fn texture_from_img_rect(
 	cell_w: u32,
 	cell_h: u32,
 ) -> TextureResult<SrgbTexture2d> {
-	let raw_image;
-	if img_w == cell_w {
+	let raw_image = if img_w == cell_w {
 		assert!(offset_x == 0);
 		let start = (offset_y as usize * img_w as usize) * 4;
 		let end = start + (cell_h as usize * cell_w as usize * 4);
-		raw_image = RawImage2d {
+		RawImage2d {
 			data: Cow::Borrowed(&img_bytes[start..end]),
 			format: glium::texture::ClientFormat::U8U8U8U8,
 			width: cell_w,
 			height: cell_h,
-		};
+		}
 	} else {
 		let cell_size = cell_w as usize * cell_h as usize * 4;
 		let mut cell_pixels = Vec::with_capacity(cell_size);
@@ -231,8 +255,8 @@ fn texture_from_img_rect(
 			let end = start + (cell_w as usize * 4);
 			cell_pixels.extend_from_slice(&img_bytes[start..end]);
 		}
-		raw_image = RawImage2d::from_raw_rgba(cell_pixels, (cell_w, cell_h));
-	}
+		RawImage2d::from_raw_rgba(cell_pixels, (cell_w, cell_h))
+	};
 
 	let x_pow = 31 - img_w.leading_zeros();
 	let y_pow = 31 - img_h.leading_zeros();
@@ -269,6 +293,9 @@ struct CachedTexture {
 	/// image uploaded to the GPU. If the target file is an animated image like a gif,
 	/// these the frames
 	frames: Vec<AnimationFrameTexture>,
+
+	/// The histogram of the first frame if the loader was asked to compute it
+	histogram: Option<Box<Histogram>>,
 }
 
 /// The process of loading an image (or animation frame) consists of the following steps.
@@ -298,6 +325,11 @@ pub struct ImageCache {
 	pending_requests: PendingRequests,
 	texture_cache: BTreeMap<u32, CachedTexture>,
 	loader: ImageLoader,
+
+	/// Receives the thumbnails made by the loader from the images it decodes
+	thumbnails: Option<Rc<RefCell<ThumbnailCache>>>,
+	/// The files of the requests whose thumbnail hasn't arrived yet
+	thumbnail_paths: HashMap<u32, PathBuf>,
 }
 
 /// This is a store for the supported images loaded from a folder
@@ -322,9 +354,42 @@ impl ImageCache {
 			pending_requests: PendingRequests::new(),
 			texture_cache: BTreeMap::new(),
 			loader: ImageLoader::new(threads),
+
+			thumbnails: None,
+			thumbnail_paths: HashMap::new(),
 		}
 	}
 
+	/// Makes the loader create a thumbnail from the first frame of every image it decodes
+	/// and hand it to `thumbnails`, so that the thumbnails of the prefetched images don't
+	/// have to be decoded a second time.
+	pub fn set_thumbnails(&mut self, thumbnails: Rc<RefCell<ThumbnailCache>>) {
+		self.loader.set_thumbnail_size(THUMBNAIL_SIZE);
+		self.thumbnails = Some(thumbnails);
+	}
+
+	/// Makes the loader compute the histogram of every image it decodes
+	pub fn set_histograms(&self, enabled: bool) {
+		self.loader.set_histograms(enabled);
+	}
+
+	/// The current file and its histogram, if the loader computed it
+	pub fn current_histogram(&self) -> Option<(&Path, &Histogram)> {
+		let desc = self.dir.curr_descriptor()?;
+		let histogram = self.texture_cache.get(&desc.request_id)?.histogram.as_deref()?;
+		Some((&desc.path, histogram))
+	}
+
+	/// Returns true while the current image is being decoded. Its histogram may still arrive
+	/// in the meantime.
+	pub fn current_loading(&self) -> bool {
+		let desc = match self.dir.curr_descriptor() {
+			Some(desc) => desc,
+			None => return false,
+		};
+		self.texture_cache.get(&desc.request_id).is_some_and(|tex| !tex.fully_loaded)
+	}
+
 	pub fn current_filename(&self) -> Option<OsString> {
 		self.dir.curr_filename()
 	}
@@ -343,6 +408,11 @@ impl ImageCache {
 		self.dir.image_count()
 	}
 
+	/// Returns `None` when the directory hasn't finished filtering image files.
+	pub fn image_paths(&mut self) -> Option<Vec<PathBuf>> {
+		self.dir.image_paths()
+	}
+
 	fn curr_dir_item(&self) -> Option<DirItem> {
 		self.dir.curr_descriptor().cloned()
 	}
@@ -359,6 +429,27 @@ impl ImageCache {
 		false
 	}
 
+	/// The index of the shown frame of the current image and the number of its frames that
+	/// are loaded
+	pub fn current_frame(&self) -> Option<(usize, usize)> {
+		let desc = self.dir.curr_descriptor()?;
+		let img = self.texture_cache.get(&desc.request_id)?;
+		Some((self.current_frame_idx, img.frames.len()))
+	}
+
+	/// Drops the texture of the current image so that it gets decoded again on the next load
+	/// request, even if the modification time of the file didn't change.
+	pub fn invalidate_current(&mut self) {
+		if let Some(desc) = self.dir.curr_descriptor() {
+			if let Some(texture) = self.texture_cache.remove(&desc.request_id) {
+				self.remaining_capacity += get_anim_size_estimate(&texture.frames);
+			}
+		}
+		if let (Some(path), Some(thumbnails)) = (self.current_file_path(), &self.thumbnails) {
+			thumbnails.borrow_mut().invalidate(&path);
+		}
+	}
+
 	/// Fetches the contents of the folder and stores the list of image filenames to know which
 	/// files will be the next and previous.
 	///
@@ -378,6 +469,9 @@ impl ImageCache {
 		for texture in self.texture_cache.values_mut() {
 			texture.needs_update = true;
 		}
+		if let Some(thumbnails) = &self.thumbnails {
+			thumbnails.borrow_mut().check_modified();
+		}
 
 		Ok(())
 	}
@@ -627,12 +721,8 @@ impl ImageCache {
 			if get_from_cache {
 				let count = tex.frames.len() as isize;
 				if tex.fully_loaded || (frame_id >= 0 && frame_id < count) {
-					let wrapped_id;
-					if frame_id < 0 {
-						wrapped_id = count + (frame_id % count);
-					} else {
-						wrapped_id = frame_id % count;
-					}
+					let wrapped_id =
+						if frame_id < 0 { count + (frame_id % count) } else { frame_id % count };
 					if let Some(frame) = tex.frames.get(wrapped_id as usize) {
 						self.current_frame_idx = wrapped_id as usize;
 						return Ok(frame.clone());
@@ -676,6 +766,7 @@ impl ImageCache {
 							mod_time: curr_mod_time,
 							failed: false,
 							frames: Vec::new(),
+							histogram: None,
 						});
 					}
 					Entry::Occupied(mut entry) => {
@@ -693,6 +784,7 @@ impl ImageCache {
 							self.remaining_capacity += old_size_estimate;
 							let mut_entry = entry.get_mut();
 							mut_entry.frames.clear();
+							mut_entry.histogram = None;
 							mut_entry.mod_time = curr_mod_time;
 						}
 					}
@@ -709,18 +801,36 @@ impl ImageCache {
 				}
 				let size_estimate = get_image_size_estimate(image.width(), image.height());
 				if let Some(entry) = self.texture_cache.get_mut(&req_id) {
-					let anim_frame =
-						AnimationFrameTexture::from_image(display, image, delay_nano, orientation)?;
+					let anim_frame = AnimationFrameTexture::from_image(
+						display,
+						&image,
+						delay_nano,
+						orientation,
+					)?;
 					entry.frames.push(anim_frame.clone());
 					self.remaining_capacity -= size_estimate;
 					return Ok(Some(anim_frame));
 				}
 				Ok(None)
 			}
+			LoadResult::Thumbnail { req_id, path, image } => {
+				self.thumbnail_paths.remove(&req_id);
+				if let Some(thumbnails) = &self.thumbnails {
+					thumbnails.borrow_mut().insert(path, image);
+				}
+				Ok(None)
+			}
+			LoadResult::Histogram { req_id, histogram } => {
+				if let Some(entry) = self.texture_cache.get_mut(&req_id) {
+					entry.histogram = Some(histogram);
+				}
+				Ok(None)
+			}
 			LoadResult::Done { req_id } => {
 				if let Some(tex) = self.texture_cache.get_mut(&req_id) {
 					tex.fully_loaded = true;
 				}
+				self.forget_thumbnail(req_id);
 				let _ = PRIORITY_REQUEST_ID.compare_exchange(
 					req_id,
 					NON_EXISTENT_REQUEST_ID,
@@ -735,6 +845,7 @@ impl ImageCache {
 					tex.fully_loaded = true;
 					tex.failed = true;
 				}
+				self.forget_thumbnail(req_id);
 				let _ = PRIORITY_REQUEST_ID.compare_exchange(
 					req_id,
 					NON_EXISTENT_REQUEST_ID,
@@ -823,12 +934,26 @@ impl ImageCache {
 		if self.pending_requests.contains(&req_id) {
 			return false;
 		}
+		if let Some(thumbnails) = &self.thumbnails {
+			thumbnails.borrow_mut().expect(&file_path);
+			self.thumbnail_paths.insert(req_id, file_path.clone());
+		}
 		let request = LoadRequest { req_id, path: file_path };
 		self.pending_requests.add_request(request.clone());
 		self.loader.send_load_request(request);
 		true
 	}
 
+	/// Lets the thumbnail cache load the thumbnail of the request itself, in case the loader
+	/// didn't send one
+	fn forget_thumbnail(&mut self, req_id: u32) {
+		if let (Some(path), Some(thumbnails)) =
+			(self.thumbnail_paths.remove(&req_id), &self.thumbnails)
+		{
+			thumbnails.borrow_mut().forget(&path);
+		}
+	}
+
 	fn change_directory(&mut self, dir_path: &Path) -> directory::Result<()> {
 		if self.dir.path() == dir_path {
 			return Ok(());
@@ -895,10 +1020,7 @@ fn get_file_name_and_parent(path: &Path) -> std::io::Result<(OsString, PathBuf)>
 	let file_name = match path.file_name() {
 		Some(f) => f.to_owned(),
 		None => {
-			return Err(io::Error::new(
-				io::ErrorKind::Other,
-				format!("Could not get file name from path {:?}", path),
-			))
+			return Err(io::Error::other(format!("Could not get file name from path {:?}", path)))
 		}
 	};
 	let parent = match path.parent() {
@@ -912,10 +1034,10 @@ fn get_file_name_and_parent(path: &Path) -> std::io::Result<(OsString, PathBuf)>
 		None => {
 			let mut path = path.canonicalize()?;
 			if !path.pop() {
-				return Err(io::Error::new(
-					io::ErrorKind::Other,
-					format!("Could not get parent directory of {:?}", path),
-				));
+				return Err(io::Error::other(format!(
+					"Could not get parent directory of {:?}",
+					path
+				)));
 			}
 			path
 		}
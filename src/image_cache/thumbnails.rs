@@ -0,0 +1,219 @@
+//! Small previews of images, decoded and downscaled by the `ImageLoader` threads.
+//!
+//! The images that the `ImageCache` prefetches arrive here as a by-product of decoding them,
+//! everything else is loaded by a separate loader that only makes thumbnails.
+
+use std::{
+	collections::HashMap,
+	fs,
+	path::{Path, PathBuf},
+	rc::Rc,
+	time::SystemTime,
+};
+
+use gelatin::{image::RgbaImage, picture::Picture};
+
+use super::image_loader::{ImageLoader, LoadRequest, LoadResult};
+
+/// The size of the longer side of the thumbnails in pixels
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// The number of thumbnails that are kept in memory
+const CAPACITY: usize = 600;
+
+/// The number of requests sent to the loader at once. Thumbnails that are requested while
+/// this many are being loaded are only sent to the loader once they are requested again.
+/// This way the thumbnails that are on the screen are always loaded first, even after
+/// quickly scrolling over a lot of images.
+const MAX_PENDING: usize = 8;
+
+enum Thumbnail {
+	/// Requested from the thumbnail loader with this request id
+	Pending(u32),
+	/// Being decoded by the `ImageCache`, which will insert the thumbnail
+	Expected,
+	Loaded(Rc<Picture>),
+	Failed,
+}
+
+struct CachedThumbnail {
+	thumbnail: Thumbnail,
+	/// The value of `ThumbnailCache::use_counter` when this was last requested
+	last_used: u64,
+	/// The modification time of the file when the thumbnail was requested
+	mod_time: Option<SystemTime>,
+	/// Set by `check_modified`, the modification time is compared on the next `get`
+	needs_check: bool,
+}
+
+impl CachedThumbnail {
+	fn new(thumbnail: Thumbnail, last_used: u64, path: &Path) -> Self {
+		CachedThumbnail { thumbnail, last_used, mod_time: mod_time(path), needs_check: false }
+	}
+
+	fn is_loading(&self) -> bool {
+		matches!(self.thumbnail, Thumbnail::Pending(_) | Thumbnail::Expected)
+	}
+}
+
+/// The thumbnails of the files that were requested most recently
+pub struct ThumbnailCache {
+	loader: ImageLoader,
+	/// The paths of the requests that haven't been answered by the loader yet
+	pending: HashMap<u32, PathBuf>,
+	next_req_id: u32,
+	thumbnails: HashMap<PathBuf, CachedThumbnail>,
+	use_counter: u64,
+	/// Incremented whenever a thumbnail arrives
+	generation: u64,
+}
+
+impl ThumbnailCache {
+	pub fn new() -> ThumbnailCache {
+		ThumbnailCache {
+			loader: ImageLoader::new_thumbnail_loader(2, THUMBNAIL_SIZE),
+			pending: HashMap::new(),
+			next_req_id: 0,
+			thumbnails: HashMap::new(),
+			use_counter: 0,
+			generation: 0,
+		}
+	}
+
+	/// Returns the thumbnail of the file if it's loaded, otherwise requests it from the loader
+	/// threads.
+	pub fn get(&mut self, path: &Path) -> Option<Rc<Picture>> {
+		self.use_counter += 1;
+		if let Some(cached) = self.thumbnails.get_mut(path) {
+			let mut modified = false;
+			if cached.needs_check && !cached.is_loading() {
+				cached.needs_check = false;
+				modified = mod_time(path) != cached.mod_time;
+			}
+			if !modified {
+				cached.last_used = self.use_counter;
+				return match &cached.thumbnail {
+					Thumbnail::Loaded(picture) => Some(picture.clone()),
+					Thumbnail::Pending(_) | Thumbnail::Expected | Thumbnail::Failed => None,
+				};
+			}
+			self.thumbnails.remove(path);
+		}
+		if self.pending.len() < MAX_PENDING {
+			let req_id = self.next_req_id;
+			self.next_req_id = self.next_req_id.wrapping_add(1);
+			self.pending.insert(req_id, path.to_owned());
+			self.loader.send_load_request(LoadRequest { req_id, path: path.to_owned() });
+			let cached = CachedThumbnail::new(Thumbnail::Pending(req_id), self.use_counter, path);
+			self.thumbnails.insert(path.to_owned(), cached);
+		}
+		None
+	}
+
+	/// Marks the thumbnail of the file as one that will be inserted by the caller, so that it
+	/// isn't loaded again in the meantime.
+	pub fn expect(&mut self, path: &Path) {
+		self.use_counter += 1;
+		if !self.thumbnails.contains_key(path) {
+			let cached = CachedThumbnail::new(Thumbnail::Expected, self.use_counter, path);
+			self.thumbnails.insert(path.to_owned(), cached);
+		}
+	}
+
+	/// Makes the next `get` load the thumbnail if it's still expected.
+	pub fn forget(&mut self, path: &Path) {
+		if let Some(CachedThumbnail { thumbnail: Thumbnail::Expected, .. }) =
+			self.thumbnails.get(path)
+		{
+			self.thumbnails.remove(path);
+		}
+	}
+
+	pub fn insert(&mut self, path: PathBuf, image: RgbaImage) {
+		self.use_counter += 1;
+		let thumbnail = Thumbnail::Loaded(Rc::new(Picture::from_image(image)));
+		let cached = CachedThumbnail::new(thumbnail, self.use_counter, &path);
+		self.thumbnails.insert(path, cached);
+		self.generation += 1;
+		self.evict();
+	}
+
+	/// Drops the thumbnail of the file, so that the next `get` loads it again. Used after the
+	/// file was changed by emulsion.
+	pub fn invalidate(&mut self, path: &Path) {
+		if self.thumbnails.remove(path).is_some() {
+			self.generation += 1;
+		}
+	}
+
+	/// Makes the next `get` of each thumbnail compare the modification time of its file to
+	/// the time when the thumbnail was made and load it again if it changed. Used when the
+	/// folder is read again, the same way the textures of the `ImageCache` are checked.
+	pub fn check_modified(&mut self) {
+		for cached in self.thumbnails.values_mut() {
+			cached.needs_check = true;
+		}
+	}
+
+	/// Returns true if some of the requested thumbnails haven't arrived yet
+	pub fn is_loading(&self) -> bool {
+		!self.pending.is_empty()
+	}
+
+	/// A number that changes whenever a thumbnail arrives. Can be used to tell if anything
+	/// needs to be redrawn.
+	pub fn generation(&self) -> u64 {
+		self.generation
+	}
+
+	/// Receives the thumbnails that were loaded since the last call. Returns true if any
+	/// arrived.
+	pub fn update(&mut self) -> bool {
+		let mut received = false;
+		while let Ok(result) = self.loader.try_recv_prefetched() {
+			let req_id = result.req_id();
+			let path = match self.pending.remove(&req_id) {
+				Some(path) => path,
+				None => continue,
+			};
+			let thumbnail = match result {
+				LoadResult::Thumbnail { image, .. } => {
+					Thumbnail::Loaded(Rc::new(Picture::from_image(image)))
+				}
+				_ => Thumbnail::Failed,
+			};
+			// The thumbnail may have been invalidated and requested again in the meantime
+			if let Some(cached) = self.thumbnails.get_mut(&path) {
+				if matches!(cached.thumbnail, Thumbnail::Pending(id) if id == req_id) {
+					cached.thumbnail = thumbnail;
+				}
+			}
+			received = true;
+		}
+		if received {
+			self.generation += 1;
+			self.evict();
+		}
+		received
+	}
+
+	/// Drops the least recently used thumbnails above the capacity
+	fn evict(&mut self) {
+		while self.thumbnails.len() > CAPACITY {
+			let oldest = self
+				.thumbnails
+				.iter()
+				.filter(|(_, cached)| !cached.is_loading())
+				.min_by_key(|(_, cached)| cached.last_used)
+				.map(|(path, _)| path.clone());
+			match oldest {
+				Some(path) => self.thumbnails.remove(&path),
+				None => break,
+			};
+		}
+	}
+}
+
+fn mod_time(path: &Path) -> Option<SystemTime> {
+	fs::metadata(path).ok().and_then(|m| m.modified().ok())
+}
@@ -0,0 +1,130 @@
+//! Measuring how much an image differs from a reference image.
+
+use std::path::{Path, PathBuf};
+
+use gelatin::image::RgbaImage;
+
+use crate::export::{self, ExportError, ExportRequest};
+use crate::image_cache::image_loader::Orientation;
+
+/// Summary of the per channel differences between two images of the same size
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DiffStats {
+	/// The largest difference of any channel of any pixel
+	pub max: u8,
+	/// The mean absolute difference over all channels of all pixels
+	pub mean: f64,
+	/// Peak signal-to-noise ratio in decibels, infinite if the images are identical
+	pub psnr: f64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DiffError {
+	#[error("the sizes differ ({0}×{1} and {2}×{3})")]
+	SizeMismatch(u32, u32, u32, u32),
+
+	#[error("{0}")]
+	Load(#[from] ExportError),
+}
+
+/// Compares the RGBA values of the images
+pub fn diff_stats(reference: &RgbaImage, image: &RgbaImage) -> Result<DiffStats, DiffError> {
+	if reference.dimensions() != image.dimensions() {
+		let (ref_w, ref_h) = reference.dimensions();
+		let (w, h) = image.dimensions();
+		return Err(DiffError::SizeMismatch(ref_w, ref_h, w, h));
+	}
+	let mut max = 0;
+	let mut sum = 0u64;
+	let mut squared_sum = 0u64;
+	for (a, b) in reference.as_raw().iter().zip(image.as_raw().iter()) {
+		let diff = a.abs_diff(*b);
+		max = max.max(diff);
+		sum += diff as u64;
+		squared_sum += diff as u64 * diff as u64;
+	}
+	let count = reference.as_raw().len().max(1) as f64;
+	let mean_squared = squared_sum as f64 / count;
+	let psnr = if mean_squared == 0.0 {
+		f64::INFINITY
+	} else {
+		10.0 * (255.0 * 255.0 / mean_squared).log10()
+	};
+	Ok(DiffStats { max, mean: sum as f64 / count, psnr })
+}
+
+/// Loads the first frame of the file with its Exif orientation applied
+fn load(source: &Path) -> Result<RgbaImage, ExportError> {
+	export::render(&ExportRequest {
+		source: source.to_owned(),
+		view_orientation: Orientation::Deg0,
+		region: None,
+	})
+}
+
+/// The input of the worker thread: the image to compare to the reference
+pub type DiffInput = PathBuf;
+
+/// The output of the worker thread: the compared image and the result
+pub type DiffOutput = (PathBuf, Result<DiffStats, String>);
+
+/// Returns the function that compares images to `reference`, meant to be run by a
+/// `ParallelAction`. The reference is only loaded by the first comparison and then kept for
+/// the following ones.
+pub fn diff_worker(reference: PathBuf) -> impl FnMut(DiffInput) -> DiffOutput {
+	let mut reference_image: Option<Result<RgbaImage, String>> = None;
+	move |image| {
+		let reference_image =
+			reference_image.get_or_insert_with(|| load(&reference).map_err(|e| e.to_string()));
+		let result = match reference_image {
+			Ok(reference_image) if image == reference => {
+				diff_stats(reference_image, reference_image)
+			}
+			Ok(reference_image) => load(&image)
+				.map_err(DiffError::from)
+				.and_then(|image| diff_stats(reference_image, &image)),
+			Err(e) => return (image, Err(e.clone())),
+		};
+		(image, result.map_err(|e| e.to_string()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use gelatin::image::Rgba;
+
+	#[test]
+	fn identical_images_have_infinite_psnr() {
+		let image = RgbaImage::from_fn(4, 3, |x, y| Rgba([x as u8 * 50, y as u8 * 80, 7, 255]));
+		let stats = diff_stats(&image, &image).unwrap();
+		assert_eq!(stats.max, 0);
+		assert_eq!(stats.mean, 0.0);
+		assert_eq!(stats.psnr, f64::INFINITY);
+	}
+
+	#[test]
+	fn size_mismatch_is_an_error() {
+		let reference = RgbaImage::new(4, 3);
+		let image = RgbaImage::new(3, 4);
+		match diff_stats(&reference, &image) {
+			Err(DiffError::SizeMismatch(4, 3, 3, 4)) => (),
+			other => panic!("unexpected result {:?}", other),
+		}
+	}
+
+	#[test]
+	fn known_difference() {
+		// Two pixels, eight channels. One channel differs by 40 and another one by 8.
+		let reference = RgbaImage::from_raw(2, 1, vec![10, 20, 30, 255, 0, 0, 0, 255]).unwrap();
+		let image = RgbaImage::from_raw(2, 1, vec![50, 20, 30, 255, 0, 8, 0, 255]).unwrap();
+		let stats = diff_stats(&reference, &image).unwrap();
+		assert_eq!(stats.max, 40);
+		assert_eq!(stats.mean, 48.0 / 8.0);
+		let mean_squared = (40.0 * 40.0 + 8.0 * 8.0) / 8.0;
+		let psnr = 10.0 * (255.0f64 * 255.0 / mean_squared).log10();
+		assert!((stats.psnr - psnr).abs() < 1e-9);
+		// The order of the images doesn't matter
+		assert_eq!(diff_stats(&image, &reference).unwrap(), stats);
+	}
+}
@@ -1,11 +1,13 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::process::Command;
 use std::rc::Rc;
 
-use crate::configuration::Configuration;
+use crate::configuration::{self, Configuration, RightButton, TransferMode};
+use crate::export::PixelRect;
 use gelatin::winit::keyboard::ModifiersState;
 use lazy_static::lazy_static;
+use log::warn;
 
 pub static TOGGLE_FULLSCREEN_NAME: &str = "toggle_fullscreen";
 pub static ESCAPE_NAME: &str = "escape";
@@ -14,8 +16,27 @@ pub static IMG_PREV_NAME: &str = "img_prev";
 pub static IMG_ORIG_NAME: &str = "img_orig";
 pub static IMG_FIT_NAME: &str = "img_fit";
 pub static IMG_FIT_BEST_NAME: &str = "img_fit_best";
+pub static IMG_FIT_WIDTH_NAME: &str = "img_fit_width";
+pub static IMG_FIT_HEIGHT_NAME: &str = "img_fit_height";
+/// Takes the zoom percentage as an argument, eg `zoom_to:200`
+pub static ZOOM_TO_NAME: &str = "zoom_to";
+pub static LOCK_VIEW_NAME: &str = "lock_view";
 pub static IMG_DEL_NAME: &str = "img_del";
 pub static IMG_COPY_NAME: &str = "img_copy";
+pub static UNDO_NAME: &str = "undo";
+pub static RENAME_NAME: &str = "rename";
+// These modify the file when `save_rotation` is on, so their default keys need a modifier
+pub static ROTATE_CW_NAME: &str = "rotate_cw";
+pub static ROTATE_CCW_NAME: &str = "rotate_ccw";
+pub static FLIP_H_NAME: &str = "flip_h";
+pub static EXPORT_NAME: &str = "export";
+pub static SELECT_NAME: &str = "select";
+pub static INSPECT_NAME: &str = "inspect";
+pub static COPY_COLOR_NAME: &str = "copy_color";
+pub static VIEW_ROTATE_CW_NAME: &str = "view_rotate_cw";
+pub static VIEW_ROTATE_CCW_NAME: &str = "view_rotate_ccw";
+pub static VIEW_FLIP_H_NAME: &str = "view_flip_h";
+pub static VIEW_FLIP_V_NAME: &str = "view_flip_v";
 pub static PAN_NAME: &str = "pan";
 pub static PAN_VERT_NAME: &str = "pan_vert"; // Vertical panning
 pub static PAN_HOR_NAME: &str = "pan_hor"; // Horizontal panning
@@ -23,6 +44,23 @@ pub static PLAY_ANIM_NAME: &str = "play_anim";
 pub static PLAY_PRESENT_NAME: &str = "play_present";
 pub static PLAY_PRESENT_RND_NAME: &str = "play_present_rnd";
 pub static TOGGLE_ANTIALIAS_NAME: &str = "toggle_antialias";
+pub static PIXEL_GRID_NAME: &str = "pixel_grid";
+pub static CYCLE_BACKGROUND_NAME: &str = "cycle_background";
+pub static COMPARE_NAME: &str = "compare";
+pub static COMPARE_LAYOUT_NAME: &str = "compare_layout";
+pub static COMPARE_NEXT_NAME: &str = "compare_next";
+pub static COMPARE_PREV_NAME: &str = "compare_prev";
+pub static PIN_REFERENCE_NAME: &str = "pin_reference";
+pub static REFERENCE_MODE_NAME: &str = "reference_mode";
+pub static HISTOGRAM_NAME: &str = "histogram";
+pub static CYCLE_CHANNEL_NAME: &str = "cycle_channel";
+pub static PREMULTIPLIED_ALPHA_NAME: &str = "premultiplied_alpha";
+pub static GALLERY_NAME: &str = "gallery";
+pub static FILMSTRIP_NAME: &str = "filmstrip";
+pub static METADATA_NAME: &str = "metadata";
+pub static HELP_NAME: &str = "help";
+pub static COMMAND_PALETTE_NAME: &str = "command_palette";
+pub static OPEN_FOLDER_NAME: &str = "open_folder";
 pub static SET_AUTOMATIC_ANTIALIAS_NAME: &str = "automatic_antialias";
 pub static ZOOM_IN_NAME: &str = "zoom_in";
 pub static ZOOM_OUT_NAME: &str = "zoom_out";
@@ -39,22 +77,409 @@ lazy_static! {
 		m.insert(ESCAPE_NAME, vec!["Escape"]);
 		m.insert(IMG_NEXT_NAME, vec!["D", "Right", "PageDown"]);
 		m.insert(IMG_PREV_NAME, vec!["A", "Left", "PageUp"]);
-		m.insert(IMG_ORIG_NAME, vec!["1"]);
+		m.insert(IMG_ORIG_NAME, vec!["0"]);
 		m.insert(IMG_FIT_NAME, vec!["F"]);
 		m.insert(IMG_FIT_BEST_NAME, vec!["E"]);
+		m.insert(LOCK_VIEW_NAME, vec!["L"]);
+		m.insert(IMG_FIT_WIDTH_NAME, vec!["W"]);
+		m.insert(IMG_FIT_HEIGHT_NAME, vec!["Alt+W"]);
+		m.insert("zoom_to:25", vec!["1"]);
+		m.insert("zoom_to:50", vec!["2"]);
+		m.insert("zoom_to:100", vec!["3"]);
+		m.insert("zoom_to:200", vec!["4"]);
+		m.insert("zoom_to:400", vec!["5"]);
 		m.insert(IMG_DEL_NAME, vec!["Delete"]);
 		m.insert(IMG_COPY_NAME, vec!["CmdCtrl+C"]);
+		m.insert(UNDO_NAME, vec!["CmdCtrl+Z"]);
+		m.insert(RENAME_NAME, vec!["F2"]);
+		m.insert(EXPORT_NAME, vec!["CmdCtrl+E"]);
+		m.insert(SELECT_NAME, vec!["C"]);
+		m.insert(INSPECT_NAME, vec!["I"]);
+		m.insert(COPY_COLOR_NAME, vec!["Alt+C"]);
+		m.insert(VIEW_ROTATE_CW_NAME, vec!["R"]);
+		m.insert(VIEW_ROTATE_CCW_NAME, vec!["Alt+R"]);
+		m.insert(VIEW_FLIP_H_NAME, vec!["H"]);
+		m.insert(VIEW_FLIP_V_NAME, vec!["V"]);
+		m.insert(ROTATE_CW_NAME, vec!["CmdCtrl+R"]);
+		m.insert(ROTATE_CCW_NAME, vec!["CmdCtrl+Alt+R"]);
+		m.insert(FLIP_H_NAME, vec!["CmdCtrl+Alt+F"]);
 		m.insert(PAN_NAME, vec!["Space"]);
 		m.insert(PLAY_ANIM_NAME, vec!["Alt+A", "Alt+V"]);
 		m.insert(PLAY_PRESENT_NAME, vec!["P"]);
 		m.insert(PLAY_PRESENT_RND_NAME, vec!["Alt+P"]);
 		m.insert(TOGGLE_ANTIALIAS_NAME, vec!["S"]);
 		m.insert(SET_AUTOMATIC_ANTIALIAS_NAME, vec!["Alt+S"]);
+		m.insert(PIXEL_GRID_NAME, vec!["G"]);
+		m.insert(CYCLE_BACKGROUND_NAME, vec!["B"]);
+		m.insert(COMPARE_NAME, vec!["X"]);
+		m.insert(COMPARE_LAYOUT_NAME, vec!["Alt+X"]);
+		m.insert(COMPARE_NEXT_NAME, vec!["Alt+Right"]);
+		m.insert(COMPARE_PREV_NAME, vec!["Alt+Left"]);
+		m.insert(PIN_REFERENCE_NAME, vec!["K"]);
+		m.insert(REFERENCE_MODE_NAME, vec!["Alt+K"]);
+		m.insert(HISTOGRAM_NAME, vec!["Alt+H"]);
+		m.insert(CYCLE_CHANNEL_NAME, vec!["N"]);
+		m.insert(PREMULTIPLIED_ALPHA_NAME, vec!["Alt+N"]);
+		m.insert(GALLERY_NAME, vec!["T"]);
+		m.insert(FILMSTRIP_NAME, vec!["Alt+T"]);
+		m.insert(METADATA_NAME, vec!["M"]);
+		m.insert(HELP_NAME, vec!["F1"]);
+		m.insert(COMMAND_PALETTE_NAME, vec!["CmdCtrl+P"]);
 		m.insert(EXIT, vec!["Q"]);
 		m
 	};
 }
 
+/// An action that can be bound to keys
+pub struct ActionInfo {
+	pub name: &'static str,
+	/// The heading the action is listed under on the help screen
+	pub category: &'static str,
+	/// For actions that take an argument, `{}` is replaced by the argument
+	pub description: &'static str,
+}
+
+lazy_static! {
+	/// Every action in the order they are listed on the help screen
+	pub static ref ACTIONS: Vec<ActionInfo> = {
+		let action = |category, name, description| ActionInfo { name, category, description };
+		vec![
+			action("General", HELP_NAME, "Show or hide the help"),
+			action("General", COMMAND_PALETTE_NAME, "Search for a command and run it"),
+			action("General", TOGGLE_FULLSCREEN_NAME, "Toggle fullscreen"),
+			action("General", ESCAPE_NAME, "Leave the current mode, fullscreen or emulsion"),
+			action("General", EXIT, "Quit"),
+			action("Navigation", IMG_NEXT_NAME, "Next image"),
+			action("Navigation", IMG_PREV_NAME, "Previous image"),
+			action("Navigation", GALLERY_NAME, "Thumbnails of the folder"),
+			action("Navigation", FILMSTRIP_NAME, "Show or hide the filmstrip"),
+			action("Zoom", IMG_ORIG_NAME, "Original size"),
+			action("Zoom", IMG_FIT_BEST_NAME, "Best fit, original size if it fits"),
+			action("Zoom", IMG_FIT_NAME, "Fit to the window"),
+			action("Zoom", IMG_FIT_WIDTH_NAME, "Fit the width"),
+			action("Zoom", IMG_FIT_HEIGHT_NAME, "Fit the height"),
+			action("Zoom", ZOOM_TO_NAME, "Zoom to {}%"),
+			action("Zoom", ZOOM_IN_NAME, "Zoom in"),
+			action("Zoom", ZOOM_OUT_NAME, "Zoom out"),
+			action("Zoom", LOCK_VIEW_NAME, "Keep the zoom and position for the next images"),
+			action("Panning", PAN_NAME, "Pan with the mouse while held"),
+			action("Panning", PAN_VERT_NAME, "Pan vertically with the mouse while held"),
+			action("Panning", PAN_HOR_NAME, "Pan horizontally with the mouse while held"),
+			action("Panning", PAN_LEFT_NAME, "Pan left"),
+			action("Panning", PAN_RIGHT_NAME, "Pan right"),
+			action("Panning", PAN_UP_NAME, "Pan up"),
+			action("Panning", PAN_DOWN_NAME, "Pan down"),
+			action("View", VIEW_ROTATE_CW_NAME, "Rotate the view clockwise"),
+			action("View", VIEW_ROTATE_CCW_NAME, "Rotate the view counterclockwise"),
+			action("View", VIEW_FLIP_H_NAME, "Mirror the view horizontally"),
+			action("View", VIEW_FLIP_V_NAME, "Mirror the view vertically"),
+			action("View", TOGGLE_ANTIALIAS_NAME, "Toggle antialiasing"),
+			action("View", SET_AUTOMATIC_ANTIALIAS_NAME, "Automatic antialiasing"),
+			action("View", PIXEL_GRID_NAME, "Show or hide the pixel grid"),
+			action("View", CYCLE_BACKGROUND_NAME, "Change the background of transparent images"),
+			action("View", CYCLE_CHANNEL_NAME, "Show the next channel"),
+			action("View", PREMULTIPLIED_ALPHA_NAME, "Toggle premultiplied alpha"),
+			action("Playback", PLAY_ANIM_NAME, "Play or pause the animation"),
+			action("Playback", PLAY_PRESENT_NAME, "Presentation"),
+			action("Playback", PLAY_PRESENT_RND_NAME, "Shuffled presentation"),
+			action("File", IMG_COPY_NAME, "Copy the image to the clipboard"),
+			action("File", IMG_DEL_NAME, "Move the image to the trash"),
+			action("File", RENAME_NAME, "Rename the file"),
+			action("File", OPEN_FOLDER_NAME, "Open the containing folder"),
+			action("File", UNDO_NAME, "Undo the last move"),
+			action("File", EXPORT_NAME, "Export a copy with the view rotation"),
+			action("File", ROTATE_CW_NAME, "Rotate clockwise, in the file too with `save_rotation`"),
+			action("File", ROTATE_CCW_NAME, "Rotate counterclockwise, in the file too with `save_rotation`"),
+			action("File", FLIP_H_NAME, "Mirror horizontally, in the file too with `save_rotation`"),
+			action("Inspection", SELECT_NAME, "Selection mode"),
+			action("Inspection", INSPECT_NAME, "Pixel inspector"),
+			action("Inspection", COPY_COLOR_NAME, "Copy the color under the cursor"),
+			action("Inspection", HISTOGRAM_NAME, "Show or hide the histogram"),
+			action("Inspection", METADATA_NAME, "Show or hide the metadata"),
+			action("Compare", COMPARE_NAME, "Compare mode"),
+			action("Compare", COMPARE_LAYOUT_NAME, "Switch between side by side and swipe"),
+			action("Compare", COMPARE_NEXT_NAME, "Next image in the second pane"),
+			action("Compare", COMPARE_PREV_NAME, "Previous image in the second pane"),
+			action("Compare", PIN_REFERENCE_NAME, "Pin or unpin the reference"),
+			action("Compare", REFERENCE_MODE_NAME, "Change how the reference is compared"),
+		]
+	};
+}
+
+/// The description of an action on the help screen
+pub fn action_description(action_name: &str) -> &'static str {
+	ACTIONS.iter().find(|a| a.name == action_name).map_or("", |a| a.description)
+}
+
+/// The actions that last while their key is held down, they are left out of the command
+/// palette
+static HELD_ACTIONS: &[&str] = &[
+	PAN_NAME,
+	PAN_VERT_NAME,
+	PAN_HOR_NAME,
+	PAN_LEFT_NAME,
+	PAN_RIGHT_NAME,
+	PAN_UP_NAME,
+	PAN_DOWN_NAME,
+	ZOOM_IN_NAME,
+	ZOOM_OUT_NAME,
+];
+
+/// The mouse gestures listed on the help screen, they can't be rebound
+static MOUSE_GESTURES: &[(&str, &str)] = &[
+	("Pan", "Drag with the left mouse button"),
+	("Zoom", "Scroll"),
+	("Toggle fullscreen", "Double click"),
+	("Jump to an image in the folder", "Click or drag the bar at the bottom"),
+	("Change the theme", "Click the sun or the moon at the bottom"),
+];
+
+/// A line of the help screen
+pub struct HelpEntry {
+	pub category: String,
+	pub description: String,
+	/// The key combinations as written in the config file, or a mouse gesture
+	pub keys: Vec<String>,
+}
+
+/// Lists the actions with the keys they are bound to in `config`, followed by the custom
+/// commands, the destinations and the mouse gestures.
+pub fn help_entries(config: &Configuration) -> Vec<HelpEntry> {
+	let mut entries = Vec::new();
+	for action in ACTIONS.iter() {
+		if action.description.contains("{}") {
+			for (argument, keys) in argument_bindings(config, action.name) {
+				let description = action.description.replace("{}", &argument);
+				entries.push(HelpEntry { category: action.category.into(), description, keys });
+			}
+		} else {
+			entries.push(HelpEntry {
+				category: action.category.into(),
+				description: action.description.into(),
+				keys: action_keys(config, action.name),
+			});
+		}
+	}
+	for command in config.commands.iter().flatten() {
+		entries.push(HelpEntry {
+			category: "Commands".into(),
+			description: command_description(command),
+			keys: command.input.clone(),
+		});
+	}
+	for destination in config.destinations.iter().flatten() {
+		let verb = match destination.mode.unwrap_or_default() {
+			TransferMode::Move => "Move",
+			TransferMode::Copy => "Copy",
+		};
+		entries.push(HelpEntry {
+			category: "Destinations".into(),
+			description: format!("{} to {}", verb, destination.path.display()),
+			keys: destination.input.clone(),
+		});
+	}
+	for (description, gesture) in MOUSE_GESTURES {
+		entries.push(HelpEntry {
+			category: "Mouse".into(),
+			description: description.to_string(),
+			keys: vec![gesture.to_string()],
+		});
+	}
+	let right_button = match config.right_button() {
+		RightButton::Menu => Some("Open the context menu".to_owned()),
+		RightButton::PanHint | RightButton::Nothing => None,
+		RightButton::Action(name) => Some(action_description(&name).replace("{}", "")),
+	};
+	if let Some(description) = right_button {
+		entries.push(HelpEntry {
+			category: "Mouse".into(),
+			description,
+			keys: vec!["Right click".into()],
+		});
+	}
+	entries
+}
+
+/// What an item of the command palette runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteAction {
+	/// The name of an action, followed by a colon and the argument for actions that take one
+	Action(String),
+	/// The index of a custom command in the config file
+	Command(usize),
+}
+
+pub struct PaletteItem {
+	pub action: PaletteAction,
+	pub description: String,
+	/// The key combinations as written in the config file
+	pub keys: Vec<String>,
+}
+
+/// Lists the actions that can be run from the command palette with the keys they are bound to
+/// in `config`, followed by the custom commands.
+pub fn palette_items(config: &Configuration) -> Vec<PaletteItem> {
+	let mut items = Vec::new();
+	for action in ACTIONS.iter().filter(|a| !HELD_ACTIONS.contains(&a.name)) {
+		if action.description.contains("{}") {
+			for (argument, keys) in argument_bindings(config, action.name) {
+				items.push(PaletteItem {
+					action: PaletteAction::Action(format!("{}:{}", action.name, argument)),
+					description: action.description.replace("{}", &argument),
+					keys,
+				});
+			}
+		} else {
+			items.push(PaletteItem {
+				action: PaletteAction::Action(action.name.into()),
+				description: action.description.into(),
+				keys: action_keys(config, action.name),
+			});
+		}
+	}
+	for (index, command) in config.commands.iter().flatten().enumerate() {
+		items.push(PaletteItem {
+			action: PaletteAction::Command(index),
+			description: command_description(command),
+			keys: command.input.clone(),
+		});
+	}
+	items
+}
+
+/// The program of a custom command followed by its arguments
+fn command_description(command: &configuration::Command) -> String {
+	let mut description = command.program.clone();
+	for arg in command.args.iter().flatten() {
+		description.push(' ');
+		description.push_str(arg);
+	}
+	description
+}
+
+/// What runs the actions, either a key press or an item picked from the command palette
+#[derive(Debug, Clone, Copy)]
+pub enum Trigger<'a> {
+	/// Runs every action and command bound to the key
+	Key {
+		input_key: &'a str,
+		modifiers: ModifiersState,
+	},
+	Palette(&'a PaletteAction),
+}
+
+impl Trigger<'_> {
+	pub fn action(&self, config: &Rc<RefCell<Configuration>>, action_name: &str) -> bool {
+		match *self {
+			Trigger::Key { input_key, modifiers } => {
+				action_triggered(config, action_name, input_key, modifiers)
+			}
+			Trigger::Palette(PaletteAction::Action(name)) => name == action_name,
+			Trigger::Palette(PaletteAction::Command(_)) => false,
+		}
+	}
+
+	/// See `triggered_arguments`
+	pub fn arguments(&self, config: &Rc<RefCell<Configuration>>, action_name: &str) -> Vec<String> {
+		match *self {
+			Trigger::Key { input_key, modifiers } => {
+				triggered_arguments(config, action_name, input_key, modifiers)
+			}
+			Trigger::Palette(PaletteAction::Action(name)) => name
+				.strip_prefix(action_name)
+				.and_then(|rest| rest.strip_prefix(':'))
+				.map(|argument| vec![argument.to_owned()])
+				.unwrap_or_default(),
+			Trigger::Palette(PaletteAction::Command(_)) => Vec::new(),
+		}
+	}
+
+	fn command(&self, index: usize, command: &configuration::Command) -> bool {
+		match *self {
+			Trigger::Key { input_key, modifiers } => {
+				keys_triggered(&command.input, input_key, modifiers)
+			}
+			Trigger::Palette(PaletteAction::Command(i)) => *i == index,
+			Trigger::Palette(PaletteAction::Action(_)) => false,
+		}
+	}
+}
+
+/// The keys `action_name` is bound to. A binding in the config file replaces the default one.
+/// Default keys that are used by a destination are left out, the destination takes precedence.
+pub fn action_keys(config: &Configuration, action_name: &str) -> Vec<String> {
+	match config.bindings.as_ref().and_then(|b| b.get(action_name)) {
+		Some(keys) => keys.clone(),
+		None => DEFAULT_BINDINGS
+			.get(action_name)
+			.map(|keys| default_keys(config, keys))
+			.unwrap_or_default(),
+	}
+}
+
+/// The default keys that aren't used by any destination
+fn default_keys(config: &Configuration, keys: &[&str]) -> Vec<String> {
+	let destinations = config.destinations.as_deref().unwrap_or_default();
+	keys.iter()
+		.filter(|key| !destinations.iter().any(|d| d.input.iter().any(|k| same_keys(k, key))))
+		.map(|key| key.to_string())
+		.collect()
+}
+
+/// Prints a warning for every destination key that is also bound to an action or a command in
+/// the config file. Both run when the key is pressed.
+pub fn warn_destination_clashes(config: &Configuration) {
+	let destinations = config.destinations.as_deref().unwrap_or_default();
+	let user_bindings = config.bindings.iter().flatten().map(|(name, keys)| (name.clone(), keys));
+	let commands = config.commands.iter().flatten().map(|c| (c.program.clone(), &c.input));
+	let user_bindings = user_bindings.chain(commands).collect::<Vec<_>>();
+	for destination in destinations {
+		for key in destination.input.iter() {
+			for (name, keys) in user_bindings.iter() {
+				if keys.iter().any(|k| same_keys(k, key)) {
+					warn!(
+						"The key {:?} of the destination {:?} is also bound to {:?}",
+						key, destination.path, name
+					);
+				}
+			}
+		}
+	}
+}
+
+/// The bindings of an action that takes an argument by the argument, see
+/// `triggered_arguments`
+pub fn argument_bindings(
+	config: &Configuration,
+	action_name: &str,
+) -> BTreeMap<String, Vec<String>> {
+	let prefix = format!("{}:", action_name);
+	let mut bindings = BTreeMap::new();
+	for (name, keys) in DEFAULT_BINDINGS.iter() {
+		if let Some(argument) = name.strip_prefix(&prefix) {
+			let keys = default_keys(config, keys);
+			if !keys.is_empty() {
+				bindings.insert(argument.to_owned(), keys);
+			}
+		}
+	}
+	if let Some(config_bindings) = &config.bindings {
+		for (name, keys) in config_bindings.iter() {
+			if let Some(argument) = name.strip_prefix(&prefix) {
+				bindings.insert(argument.to_owned(), keys.clone());
+			}
+		}
+	}
+	bindings
+}
+
+/// Writes a key combination of the config file the way it's shown to the user, eg
+/// `CmdCtrl+C` as `Ctrl+C`
+pub fn display_key(key: &str) -> String {
+	let cmd_ctrl = if cfg!(target_os = "macos") { "Cmd" } else { "Ctrl" };
+	key.replace("CmdCtrl", cmd_ctrl)
+}
+
 pub fn char_to_input_key(ch: &str) -> String {
 	let mut input_key = String::with_capacity(8);
 	if ch == " " {
@@ -69,32 +494,40 @@ pub fn char_to_input_key(ch: &str) -> String {
 	input_key
 }
 
-fn substitute_command_parameters(string: &str, var_map: &HashMap<&str, &str>) -> String {
+fn substitute_command_parameters(string: &str, var_map: &HashMap<&str, String>) -> String {
 	let mut result = String::from(string);
-	for (&var_name, &substitute) in var_map.iter() {
+	for (&var_name, substitute) in var_map.iter() {
 		result = result.replace(var_name, substitute);
 	}
 	result
 }
 
-/// Execute all custom commands that were triggered by the input key and modifier set.
+/// Execute all custom commands that were triggered.
+/// `region` is substituted for `${x}`, `${y}`, `${w}` and `${h}`, it's the selection or the
+/// whole image in the pixels of the displayed image.
 /// Note: img_path and folder_path both have to be str instead of Path because we
 /// wouldn't be able to construct a command from them if they cannot be converted to
 /// valid UTF-8.
 pub fn execute_triggered_commands(
 	config: Rc<RefCell<Configuration>>,
-	input_key: &str,
-	modifiers: ModifiersState,
+	trigger: Trigger,
 	img_path: &str,
 	folder_path: &str,
+	region: Option<PixelRect>,
 ) {
 	let config = config.borrow();
 	if let Some(ref commands) = config.commands {
-		let mut var_map = HashMap::with_capacity(2);
-		var_map.insert("${img}", img_path);
-		var_map.insert("${folder}", folder_path);
-		for command in commands.iter() {
-			if keys_triggered(&command.input, input_key, modifiers) {
+		let mut var_map = HashMap::with_capacity(6);
+		var_map.insert("${img}", img_path.to_owned());
+		var_map.insert("${folder}", folder_path.to_owned());
+		if let Some(region) = region {
+			var_map.insert("${x}", region.x.to_string());
+			var_map.insert("${y}", region.y.to_string());
+			var_map.insert("${w}", region.w.to_string());
+			var_map.insert("${h}", region.h.to_string());
+		}
+		for (index, command) in commands.iter().enumerate() {
+			if trigger.command(index, command) {
 				let mut cmd = Command::new(&command.program);
 				if let Some(ref args) = command.args {
 					cmd.args(args.iter().map(|arg| substitute_command_parameters(arg, &var_map)));
@@ -118,41 +551,71 @@ pub fn keys_triggered<S: AsRef<str>>(
 	modifiers: ModifiersState,
 ) -> bool {
 	for key in keys {
-		let complex_key = key.as_ref();
-		let parts = complex_key.split('+').map(|s| s.trim().to_lowercase()).collect::<Vec<_>>();
-		if parts.is_empty() {
-			continue;
-		}
-		let key = parts.last().unwrap();
-		if input_key != *key {
-			continue;
+		let key = ParsedKey::new(key.as_ref());
+		if input_key == key.key
+			&& key.alt == modifiers.alt_key()
+			&& key.ctrl == modifiers.control_key()
+			&& key.logo == modifiers.super_key()
+		{
+			return true;
 		}
-		let mut has_alt = false;
-		let mut has_ctrl = false;
-		let mut has_logo = false;
-		for mod_str in parts.iter().take(parts.len() - 1) {
+	}
+	false
+}
+
+/// A key combination of the config file, eg `Alt+W`
+#[derive(PartialEq, Eq)]
+struct ParsedKey {
+	key: String,
+	alt: bool,
+	ctrl: bool,
+	logo: bool,
+}
+impl ParsedKey {
+	fn new(complex_key: &str) -> ParsedKey {
+		let mut parts = complex_key.split('+').map(|s| s.trim().to_lowercase()).collect::<Vec<_>>();
+		let key = parts.pop().unwrap_or_default();
+		let mut parsed = ParsedKey { key, alt: false, ctrl: false, logo: false };
+		for mod_str in parts.iter() {
 			match mod_str.as_ref() {
-				"alt" => has_alt = true,
-				"ctrl" => has_ctrl = true,
-				"logo" => has_logo = true,
+				"alt" => parsed.alt = true,
+				"ctrl" => parsed.ctrl = true,
+				"logo" => parsed.logo = true,
 				"cmdctrl" => {
 					if cfg!(target_os = "macos") {
-						has_logo = true;
+						parsed.logo = true;
 					} else {
-						has_ctrl = true;
+						parsed.ctrl = true;
 					}
 				}
 				_ => (),
 			}
 		}
-		if has_alt == modifiers.alt_key()
-			&& has_ctrl == modifiers.control_key()
-			&& has_logo == modifiers.super_key()
-		{
-			return true;
-		}
+		parsed
 	}
-	false
+}
+
+/// Whether two key combinations of the config file are pressed the same way
+fn same_keys(a: &str, b: &str) -> bool {
+	ParsedKey::new(a) == ParsedKey::new(b)
+}
+
+/// Returns the arguments of the triggered bindings that have the form `action:argument`.
+///
+/// For example with `"zoom_to:200" = ["2"]` under `[bindings]`, pressing 2 returns `["200"]`
+/// for the `zoom_to` action. A binding in the config file replaces the default binding with
+/// the same argument.
+pub fn triggered_arguments(
+	config: &Rc<RefCell<Configuration>>,
+	action_name: &str,
+	input_key: &str,
+	modifiers: ModifiersState,
+) -> Vec<String> {
+	argument_bindings(&config.borrow(), action_name)
+		.into_iter()
+		.filter(|(_, keys)| keys_triggered(keys, input_key, modifiers))
+		.map(|(argument, _)| argument)
+		.collect()
 }
 
 pub fn action_triggered(
@@ -161,13 +624,6 @@ pub fn action_triggered(
 	input_key: &str,
 	modifiers: ModifiersState,
 ) -> bool {
-	let config = config.borrow();
-	let bindings = config.bindings.as_ref();
-	if let Some(Some(keys)) = bindings.map(|b| b.get(action_name)) {
-		keys_triggered(keys.as_slice(), input_key, modifiers)
-	} else {
-		let empty = Vec::new();
-		let keys = DEFAULT_BINDINGS.get(action_name).unwrap_or(&empty);
-		keys_triggered(keys.as_slice(), input_key, modifiers)
-	}
+	let keys = action_keys(&config.borrow(), action_name);
+	keys_triggered(&keys, input_key, modifiers)
 }
@@ -21,31 +21,43 @@ use gelatin::winit::{
 use gelatin::{
 	application::*,
 	button::*,
+	context_menu::{ContextMenu, MenuItem},
 	image,
 	label::*,
 	line_layout_container::*,
 	misc::*,
 	picture::*,
+	text_input::TextInput,
 	window::{Window, WindowDescriptorBuilder},
 	NextUpdate, Widget,
 };
 
 use crate::configuration::Theme;
 use crate::configuration::{Cache, ConfigWindowSection, Configuration};
+use crate::image_cache::thumbnails::ThumbnailCache;
+use crate::input_handling::*;
 use crate::version::Version;
 use crate::widgets::{
-	bottom_bar::BottomBar, copy_notification::CopyNotifications, help_screen::*, picture_widget::*,
+	bottom_bar::BottomBar, command_palette::CommandPalette, copy_notification::CopyNotifications,
+	filmstrip::Filmstrip, gallery::Gallery, help_screen::HelpScreen, hint::Hint, picture_widget::*,
 };
 
 mod clipboard_handler;
 mod cmd_line;
 mod configuration;
+mod destinations;
+mod export;
+mod file_transform;
 mod handle_panic;
+mod histogram;
 mod image_cache;
+mod image_diff;
 mod input_handling;
+mod metadata;
 mod parallel_action;
 mod playback_manager;
 mod shaders;
+mod template;
 mod utils;
 mod version;
 mod widgets;
@@ -59,7 +71,6 @@ lazy_static! {
 static NEW_VERSION: &[u8] = include_bytes!("../resource/new-version-available.png");
 static NEW_VERSION_LIGHT: &[u8] = include_bytes!("../resource/new-version-available-light.png");
 static VISIT_SITE: &[u8] = include_bytes!("../resource/visit-site.png");
-static USAGE: &[u8] = include_bytes!("../resource/usage.png");
 static LEFT_TO_PAN: &[u8] = include_bytes!("../resource/use-left-to-pan.png");
 
 #[derive(Debug)]
@@ -91,6 +102,7 @@ fn main() {
 	let first_launch = cache.is_err();
 	let cache = Arc::new(Mutex::new(cache.unwrap_or_default()));
 	let config = Rc::new(RefCell::new(config.unwrap_or_default()));
+	input_handling::warn_destination_clashes(&config.borrow());
 
 	if args.displayed_folders.is_some() {
 		config.borrow_mut().title.get_or_insert_with(Default::default).displayed_folders =
@@ -161,37 +173,104 @@ fn main() {
 
 	let update_notification = make_update_notification(update_label.clone());
 
-	let usage_img = Picture::from_encoded_bytes(USAGE);
-	let help_screen = Rc::new(HelpScreen::new(usage_img));
+	let help_screen = Rc::new(HelpScreen::new());
+	help_screen.set_entries(input_handling::help_entries(&config.borrow()));
 	let left_to_pan_img = Picture::from_encoded_bytes(LEFT_TO_PAN);
-	let left_to_pan_hint = Rc::new(HelpScreen::new(left_to_pan_img));
+	let left_to_pan_hint = Rc::new(Hint::new(left_to_pan_img));
 
 	let copy_notifications_widget = Rc::new(Label::new());
 	let copy_notifications = CopyNotifications::new(&copy_notifications_widget);
 
+	let rename_input = make_rename_input();
+
 	let bottom_bar = Rc::new(BottomBar::new(&config.borrow()));
 	let picture_widget = make_picture_widget(
 		&window,
 		bottom_bar.clone(),
 		left_to_pan_hint.clone(),
 		copy_notifications,
+		rename_input.clone(),
 		config.clone(),
 		cache.clone(),
 	);
+	{
+		let picture_widget = picture_widget.clone();
+		rename_input.set_on_submit(move |new_name| {
+			picture_widget.rename_current_file(&new_name);
+		});
+	}
+	{
+		let picture_widget = picture_widget.clone();
+		rename_input.set_on_cancel(move || {
+			picture_widget.cancel_rename();
+		});
+	}
+
+	let thumbnails = Rc::new(RefCell::new(ThumbnailCache::new()));
+	picture_widget.share_thumbnails(thumbnails.clone());
+	let gallery = Rc::new(Gallery::new(thumbnails.clone()));
+	picture_widget.set_gallery(gallery.clone());
+	picture_widget.set_help_screen(help_screen.clone());
+
+	let command_palette = Rc::new(CommandPalette::new());
+	command_palette.set_items(input_handling::palette_items(&config.borrow()));
+	picture_widget.set_command_palette(command_palette.clone());
+	let context_menu = make_context_menu(&config.borrow(), &picture_widget);
+	picture_widget.set_context_menu(context_menu.clone());
+	{
+		let picture_widget = picture_widget.clone();
+		command_palette.set_on_run(move |action| {
+			picture_widget.run_action(action);
+		});
+	}
+	{
+		let picture_widget = picture_widget.clone();
+		gallery.set_on_open(move |index| {
+			picture_widget.open_from_gallery(index);
+		});
+	}
+	{
+		let picture_widget = picture_widget.clone();
+		gallery.set_on_close(move || {
+			picture_widget.set_visible(true);
+		});
+	}
+
+	let show_filmstrip = matches!(
+		config.borrow().window,
+		Some(ConfigWindowSection { show_filmstrip: Some(true), .. })
+	);
+	let filmstrip = Rc::new(Filmstrip::new(thumbnails, show_filmstrip));
+	picture_widget.set_filmstrip(filmstrip.clone());
+	{
+		let picture_widget = picture_widget.clone();
+		filmstrip.set_on_click(move |index| {
+			picture_widget.jump_to_index(index as u32);
+		});
+	}
 
 	if let Some(file_path) = args.file_path {
 		picture_widget.jump_to_path(file_path);
 	}
+	if let Some(compare_path) = args.compare_path {
+		picture_widget.compare_with_path(compare_path);
+	}
 
 	let picture_area_container = make_picture_area_container();
 	picture_area_container.add_child(picture_widget.clone());
+	// Added after the picture so that it gets the events after it. This way the key that
+	// closes the gallery and shows the picture isn't also handled by the picture.
+	picture_area_container.add_child(gallery);
 	picture_area_container.add_child(copy_notifications_widget);
 	picture_area_container.add_child(left_to_pan_hint);
 	picture_area_container.add_child(help_screen.clone());
+	picture_area_container.add_child(command_palette);
+	picture_area_container.add_child(context_menu);
 	picture_area_container.add_child(update_notification.clone());
 
 	let root_container = make_root_container();
 	root_container.add_child(picture_area_container);
+	root_container.add_child(filmstrip);
 	root_container.add_child(bottom_bar.widget.clone());
 
 	let update_available = Arc::new(AtomicBool::new(false));
@@ -228,6 +307,7 @@ fn main() {
 					update_label.set_icon(Some(update_label_image.clone()));
 				}
 			}
+			window.set_text_color(theme.get().text_color());
 			bottom_bar.set_theme(theme.get(), update_available.load(Ordering::SeqCst));
 		})
 	};
@@ -266,23 +346,23 @@ fn main() {
 			picture_widget.set_img_size_to_fit(true);
 		});
 	}
-	let help_visible = Cell::new(first_launch);
-	help_screen.set_visible(help_visible.get());
-	update_notification.set_visible(help_visible.get() && update_available.load(Ordering::SeqCst));
 	{
 		let update_available = update_available.clone();
-		let help_screen = help_screen.clone();
 		let update_notification = update_notification.clone();
-		let bottom_bar_clone = bottom_bar.clone();
-
-		bottom_bar.help_button.set_on_click(move || {
-			help_visible.set(!help_visible.get());
-			help_screen.set_visible(help_visible.get());
-			bottom_bar_clone.set_help_visible(help_visible.get());
-			update_notification
-				.set_visible(help_visible.get() && update_available.load(Ordering::SeqCst));
+		let bottom_bar = bottom_bar.clone();
+		help_screen.set_on_visibility_change(move |visible| {
+			bottom_bar.set_help_visible(visible);
+			update_notification.set_visible(visible && update_available.load(Ordering::SeqCst));
 		});
 	}
+	update_notification.set_visible(false);
+	if first_launch {
+		help_screen.toggle();
+	}
+	{
+		let help_screen = help_screen.clone();
+		bottom_bar.help_button.set_on_click(move || help_screen.toggle());
+	}
 
 	window.set_root(root_container);
 
@@ -408,11 +488,77 @@ fn make_update_notification(update_label: Rc<Label>) -> Rc<HorizontalLayoutConta
 	container
 }
 
+fn make_rename_input() -> Rc<TextInput> {
+	let rename_input = Rc::new(TextInput::new());
+	rename_input.set_fixed_size(LogicalVector::new(480.0, 64.0));
+	rename_input.set_horizontal_align(Alignment::Center);
+	rename_input.set_vertical_align(Alignment::Center);
+	rename_input.set_visible(false);
+	rename_input
+}
+
+/// The menu of the right mouse button, the actions show the first key they are bound to
+fn make_context_menu(
+	config: &Configuration,
+	picture_widget: &Rc<PictureWidget>,
+) -> Rc<ContextMenu> {
+	let item = |label: &str, action: PaletteAction, keys: &[String]| {
+		let picture_widget = picture_widget.clone();
+		let shortcut = keys.first().map(|key| display_key(key));
+		MenuItem::action(label, move || picture_widget.run_action(&action)).with_shortcut(shortcut)
+	};
+	let action = |name: &str| {
+		item(
+			action_description(name),
+			PaletteAction::Action(name.into()),
+			&action_keys(config, name),
+		)
+	};
+	let mut rotate_items = vec![
+		action(VIEW_ROTATE_CW_NAME),
+		action(VIEW_ROTATE_CCW_NAME),
+		action(VIEW_FLIP_H_NAME),
+		action(VIEW_FLIP_V_NAME),
+	];
+	// Without `save_rotation` these would only repeat the view actions
+	if config.image.as_ref().and_then(|i| i.save_rotation).unwrap_or(false) {
+		rotate_items.push(MenuItem::Separator);
+		rotate_items.push(action(ROTATE_CW_NAME));
+		rotate_items.push(action(ROTATE_CCW_NAME));
+		rotate_items.push(action(FLIP_H_NAME));
+	}
+	let mut items = vec![
+		action(IMG_COPY_NAME),
+		action(RENAME_NAME),
+		action(EXPORT_NAME),
+		action(IMG_DEL_NAME),
+		MenuItem::Separator,
+		MenuItem::submenu("Rotate", rotate_items),
+		action(OPEN_FOLDER_NAME),
+	];
+	let commands = palette_items(config)
+		.into_iter()
+		.filter(|i| matches!(i.action, PaletteAction::Command(_)))
+		.map(|i| item(&i.description, i.action, &i.keys))
+		.collect::<Vec<_>>();
+	if !commands.is_empty() {
+		items.push(MenuItem::submenu("Commands", commands));
+	}
+	items.push(MenuItem::Separator);
+	items.push(action(COMMAND_PALETTE_NAME));
+	items.push(action(HELP_NAME));
+
+	let context_menu = Rc::new(ContextMenu::new());
+	context_menu.set_items(items);
+	context_menu
+}
+
 fn make_picture_widget(
 	window: &Rc<Window>,
 	bottom_bar: Rc<BottomBar>,
-	left_to_pan_hint: Rc<HelpScreen>,
+	left_to_pan_hint: Rc<Hint>,
 	copy_notifications: CopyNotifications,
+	rename_input: Rc<TextInput>,
 	config: Rc<RefCell<Configuration>>,
 	cache: Arc<Mutex<Cache>>,
 ) -> Rc<PictureWidget> {
@@ -422,6 +568,7 @@ fn make_picture_widget(
 		bottom_bar,
 		left_to_pan_hint,
 		copy_notifications,
+		rename_input,
 		config,
 		cache,
 	));
@@ -0,0 +1,353 @@
+//! File information and the Exif, XMP and PNG text metadata of images.
+
+use std::{
+	collections::BTreeMap,
+	fs::{self, File},
+	io::{BufReader, Read},
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
+
+use chrono::{DateTime, Local};
+use gelatin::image::ImageReader;
+
+/// Only this many bytes from the start of the file are searched for an XMP packet
+const XMP_SEARCH_LIMIT: u64 = 1 << 20;
+
+/// Values longer than this are cut off in `Metadata::lines`
+const MAX_VALUE_CHARS: usize = 60;
+
+/// At most this many XMP properties and PNG text chunks are listed by `Metadata::lines`
+const MAX_EXTRA_LINES: usize = 12;
+
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+	pub file_size: u64,
+	pub modified: Option<SystemTime>,
+	pub format: Option<String>,
+	pub dimensions: Option<(u32, u32)>,
+	/// The fields of the primary image by their tag name, eg `DateTimeOriginal`
+	pub exif: BTreeMap<String, String>,
+	/// The simple properties of the XMP packet by their qualified name, eg `xmp:Rating`
+	pub xmp: Vec<(String, String)>,
+	/// The keywords and the text of the tEXt, zTXt and iTXt chunks of PNG files
+	pub text_chunks: Vec<(String, String)>,
+}
+
+impl Metadata {
+	/// Reads the metadata of the file. Only failing to access the file is an error, missing or
+	/// broken metadata is left empty.
+	pub fn read(path: &Path) -> std::io::Result<Metadata> {
+		let file_metadata = fs::metadata(path)?;
+		let mut metadata = Metadata {
+			file_size: file_metadata.len(),
+			modified: file_metadata.modified().ok(),
+			..Default::default()
+		};
+		if let Ok(reader) = ImageReader::open(path).and_then(|r| r.with_guessed_format()) {
+			metadata.format = reader.format().map(|f| format!("{:?}", f).to_uppercase());
+			metadata.dimensions = reader.into_dimensions().ok();
+		}
+		metadata.exif = read_exif(path);
+		metadata.text_chunks = read_png_text(path);
+		let xmp_chunk = metadata.text_chunks.iter().position(|(k, _)| k == "XML:com.adobe.xmp");
+		let xmp = match xmp_chunk {
+			Some(index) => Some(metadata.text_chunks.remove(index).1),
+			None => find_xmp_packet(path),
+		};
+		metadata.xmp = xmp.map(|xml| xmp_properties(&xml)).unwrap_or_default();
+		Ok(metadata)
+	}
+
+	/// Returns the value of an Exif field by its tag name, eg `DateTimeOriginal`
+	pub fn exif_value(&self, tag: &str) -> Option<&str> {
+		self.exif.get(tag).map(|value| value.as_str())
+	}
+
+	/// The camera model, including the make unless it's already part of the model name
+	pub fn camera(&self) -> Option<String> {
+		let model = self.exif_value("Model");
+		match (self.exif_value("Make"), model) {
+			(Some(make), Some(model)) if !model.starts_with(make) => {
+				Some(format!("{} {}", make, model))
+			}
+			(_, Some(model)) => Some(model.to_owned()),
+			(Some(make), None) => Some(make.to_owned()),
+			(None, None) => None,
+		}
+	}
+
+	/// The exposure time, the aperture and the ISO speed, as far as they are known
+	pub fn exposure(&self) -> Option<String> {
+		let parts = [
+			self.exif_value("ExposureTime").map(|t| format!("{} s", t.trim_end_matches(" s"))),
+			self.exif_value("FNumber").map(|f| f.to_owned()),
+			self.exif_value("PhotographicSensitivity").map(|iso| format!("ISO {}", iso)),
+		];
+		let parts = parts.iter().flatten().cloned().collect::<Vec<_>>();
+		(!parts.is_empty()).then(|| parts.join("   "))
+	}
+
+	/// The position as signed decimal degrees, eg `47.49801, 19.03991`
+	pub fn gps(&self) -> Option<String> {
+		let coordinate = |value: &str, reference: &str, negative: &str| {
+			let degrees = self.exif_value(value)?.parse::<f64>().ok()?;
+			let sign = match self.exif_value(reference) {
+				Some(r) if r.eq_ignore_ascii_case(negative) => -1.0,
+				_ => 1.0,
+			};
+			Some(sign * degrees)
+		};
+		let latitude = coordinate("GPSLatitude", "GPSLatitudeRef", "S")?;
+		let longitude = coordinate("GPSLongitude", "GPSLongitudeRef", "W")?;
+		Some(format!("{:.5}, {:.5}", latitude, longitude))
+	}
+
+	/// The lines of the info panel, grouped into file information, photo information, XMP
+	/// properties and PNG text chunks
+	pub fn lines(&self) -> Vec<String> {
+		let mut lines = Vec::new();
+		lines.push(format!("Size   {}", format_file_size(self.file_size)));
+		if let Some((w, h)) = self.dimensions {
+			lines.push(format!("Dimensions   {} × {}", w, h));
+		}
+		if let Some(format) = &self.format {
+			lines.push(format!("Format   {}", format));
+		}
+		if let Some(modified) = self.modified {
+			let modified: DateTime<Local> = modified.into();
+			lines.push(format!("Modified   {}", modified.format("%Y-%m-%d %H:%M:%S")));
+		}
+		let photo = [
+			("Camera", self.camera()),
+			("Lens", self.exif_value("LensModel").map(|l| l.to_owned())),
+			("Exposure", self.exposure()),
+			("Focal length", self.exif_value("FocalLength").map(|f| f.to_owned())),
+			("Taken", self.exif_value("DateTimeOriginal").map(|d| d.to_owned())),
+			("GPS", self.gps()),
+		];
+		let mut first = true;
+		for (name, value) in photo.iter() {
+			if let Some(value) = value {
+				if first {
+					lines.push(String::new());
+					first = false;
+				}
+				lines.push(format!("{}   {}", name, shorten(value)));
+			}
+		}
+		for (title, entries) in [("XMP", &self.xmp), ("PNG text", &self.text_chunks)] {
+			if entries.is_empty() {
+				continue;
+			}
+			lines.push(String::new());
+			lines.push(title.to_owned());
+			for (key, value) in entries.iter().take(MAX_EXTRA_LINES) {
+				lines.push(format!("{}   {}", key, shorten(value)));
+			}
+			if entries.len() > MAX_EXTRA_LINES {
+				lines.push(format!("… and {} more", entries.len() - MAX_EXTRA_LINES));
+			}
+		}
+		lines
+	}
+}
+
+/// The output of the worker thread: the image file and its metadata
+pub type MetadataOutput = (PathBuf, Result<Metadata, String>);
+
+/// Reads the metadata of the file, meant to be run by a `ParallelAction`
+pub fn metadata_worker(path: PathBuf) -> MetadataOutput {
+	let result = Metadata::read(&path).map_err(|e| e.to_string());
+	(path, result)
+}
+
+/// Formats the size with a binary unit, eg `2.4 MiB`
+pub fn format_file_size(bytes: u64) -> String {
+	const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+	if bytes < 1024 {
+		return format!("{} B", bytes);
+	}
+	let mut size = bytes as f64 / 1024.0;
+	let mut unit = 0;
+	while size >= 1024.0 && unit < UNITS.len() - 1 {
+		size /= 1024.0;
+		unit += 1;
+	}
+	format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Cuts off the end of long values and puts everything on a single line
+fn shorten(value: &str) -> String {
+	let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+	if value.chars().count() <= MAX_VALUE_CHARS {
+		return value;
+	}
+	let mut shortened = value.chars().take(MAX_VALUE_CHARS - 1).collect::<String>();
+	shortened.push('…');
+	shortened
+}
+
+fn read_exif(path: &Path) -> BTreeMap<String, String> {
+	let mut fields = BTreeMap::new();
+	let file = match File::open(path) {
+		Ok(file) => file,
+		Err(_) => return fields,
+	};
+	let exif = match exif::Reader::new().read_from_container(&mut BufReader::new(file)) {
+		Ok(exif) => exif,
+		Err(_) => return fields,
+	};
+	for field in exif.fields().filter(|f| f.ifd_num == exif::In::PRIMARY) {
+		let value = match &field.value {
+			exif::Value::Ascii(strings) => strings
+				.iter()
+				.map(|s| String::from_utf8_lossy(s).trim().to_owned())
+				.collect::<Vec<_>>()
+				.join(", "),
+			// Degrees, minutes and seconds
+			exif::Value::Rational(parts)
+				if matches!(field.tag, exif::Tag::GPSLatitude | exif::Tag::GPSLongitude) =>
+			{
+				let degrees =
+					parts.iter().zip([1.0, 60.0, 3600.0]).map(|(p, d)| p.to_f64() / d).sum::<f64>();
+				degrees.to_string()
+			}
+			_ => field.display_value().with_unit(&exif).to_string(),
+		};
+		fields.entry(field.tag.to_string()).or_insert(value);
+	}
+	fields
+}
+
+fn read_png_text(path: &Path) -> Vec<(String, String)> {
+	let file = match File::open(path) {
+		Ok(file) => file,
+		Err(_) => return Vec::new(),
+	};
+	let reader = match png::Decoder::new(BufReader::new(file)).read_info() {
+		Ok(reader) => reader,
+		Err(_) => return Vec::new(),
+	};
+	let info = reader.info();
+	let mut chunks = Vec::new();
+	for chunk in info.uncompressed_latin1_text.iter() {
+		chunks.push((chunk.keyword.clone(), chunk.text.clone()));
+	}
+	for chunk in info.compressed_latin1_text.iter() {
+		if let Ok(text) = chunk.get_text() {
+			chunks.push((chunk.keyword.clone(), text));
+		}
+	}
+	for chunk in info.utf8_text.iter() {
+		if let Ok(text) = chunk.get_text() {
+			chunks.push((chunk.keyword.clone(), text));
+		}
+	}
+	chunks
+}
+
+/// Looks for an XMP packet near the start of the file. This finds the packets of JPEG, TIFF
+/// and WebP files.
+fn find_xmp_packet(path: &Path) -> Option<String> {
+	const START: &[u8] = b"<x:xmpmeta";
+	const END: &[u8] = b"</x:xmpmeta>";
+	let mut bytes = Vec::new();
+	File::open(path).ok()?.take(XMP_SEARCH_LIMIT).read_to_end(&mut bytes).ok()?;
+	let start = bytes.windows(START.len()).position(|w| w == START)?;
+	let length = bytes[start..].windows(END.len()).position(|w| w == END)?;
+	Some(String::from_utf8_lossy(&bytes[start..start + length + END.len()]).into_owned())
+}
+
+/// Collects the properties of an XMP packet that have a textual value, both the ones written
+/// as attributes of `rdf:Description` and the ones written as elements. The items of lists
+/// are joined with commas.
+fn xmp_properties(xml: &str) -> Vec<(String, String)> {
+	let mut properties: Vec<(String, String)> = Vec::new();
+	let mut add = |name: &str, value: &str| {
+		let value = unescape_xml(value.trim());
+		if value.is_empty() {
+			return;
+		}
+		match properties.iter_mut().find(|(n, _)| n == name) {
+			Some((_, existing)) => {
+				existing.push_str(", ");
+				existing.push_str(&value);
+			}
+			None => properties.push((name.to_owned(), value)),
+		}
+	};
+	// The names of the open elements
+	let mut open = Vec::<&str>::new();
+	let mut rest = xml;
+	while let Some(tag_start) = rest.find('<') {
+		let text = &rest[..tag_start];
+		if let Some(property) = open.iter().rev().find(|name| !is_xmp_structure(name)) {
+			add(property, text);
+		}
+		let tag_end = match rest[tag_start..].find('>') {
+			Some(end) => tag_start + end,
+			None => break,
+		};
+		let tag = &rest[tag_start + 1..tag_end];
+		rest = &rest[tag_end + 1..];
+		if tag.starts_with('?') || tag.starts_with('!') {
+			continue;
+		}
+		if let Some(name) = tag.strip_prefix('/') {
+			if let Some(index) = open.iter().rposition(|open| *open == name.trim()) {
+				open.truncate(index);
+			}
+			continue;
+		}
+		let self_closing = tag.ends_with('/');
+		let tag = tag.trim_end_matches('/');
+		let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+		let name = &tag[..name_end];
+		if name == "rdf:Description" {
+			for (attr_name, attr_value) in xml_attributes(&tag[name_end..]) {
+				let prefix = attr_name.split(':').next().unwrap_or_default();
+				if !matches!(prefix, "xmlns" | "rdf" | "xml" | "x") && attr_name.contains(':') {
+					add(attr_name, attr_value);
+				}
+			}
+		}
+		if !self_closing {
+			open.push(name);
+		}
+	}
+	properties
+}
+
+/// Elements that only give structure to the packet and are not properties themselves
+fn is_xmp_structure(name: &str) -> bool {
+	name.starts_with("rdf:") || name.starts_with("x:")
+}
+
+/// Splits `name="value"` pairs
+fn xml_attributes(mut attributes: &str) -> Vec<(&str, &str)> {
+	let mut pairs = Vec::new();
+	while let Some(eq) = attributes.find('=') {
+		let name = attributes[..eq].trim();
+		let after = attributes[eq + 1..].trim_start();
+		let quote = match after.chars().next() {
+			Some(quote @ ('"' | '\'')) => quote,
+			_ => break,
+		};
+		let value_end = match after[1..].find(quote) {
+			Some(end) => end + 1,
+			None => break,
+		};
+		pairs.push((name, &after[1..value_end]));
+		attributes = &after[value_end + 1..];
+	}
+	pairs
+}
+
+fn unescape_xml(text: &str) -> String {
+	text.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&quot;", "\"")
+		.replace("&apos;", "'")
+		.replace("&amp;", "&")
+}
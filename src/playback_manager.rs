@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::io::Write;
 use std::marker::PhantomData;
 use std::mem;
@@ -5,7 +6,6 @@ use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::time::{Duration, Instant};
 
-use gelatin::NextUpdate;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
@@ -14,6 +14,8 @@ use log::{debug, trace};
 use gelatin::window::Window;
 use gelatin::Display;
 
+use crate::histogram::Histogram;
+use crate::image_cache::thumbnails::ThumbnailCache;
 use crate::image_cache::{
 	self, AnimationFrameTexture, ImageCache, PathResolutionError, PathedTextureResult,
 	TextureResult,
@@ -155,21 +157,32 @@ pub struct PlaybackManager {
 
 impl PlaybackManager {
 	pub fn new() -> Self {
+		Self::with_memory_share(8, 4)
+	}
+
+	/// Creates a manager for an image shown next to the main one, like the second pane of
+	/// compare mode. It gets a smaller cache and fewer loader threads than the main one.
+	pub fn new_secondary() -> Self {
+		Self::with_memory_share(32, 2)
+	}
+
+	/// The cache may use `1 / memory_divisor` of the system memory
+	fn with_memory_share(memory_divisor: u64, max_threads: u32) -> Self {
 		let cache_capacity = match sys_info::mem_info() {
 			Ok(value) => {
 				// value originally reported in KiB
-				((value.total / 8) * 1024) as isize
+				((value.total / memory_divisor) * 1024) as isize
 			}
 			_ => {
 				eprintln!("Could not get system memory size, using default value");
-				// bytes
-				500_000_000
+				// bytes, 500 MB for the main manager
+				(4_000_000_000 / memory_divisor) as isize
 			}
 		};
 
 		let thread_count = match sys_info::cpu_num() {
-			Ok(value) => value.clamp(2, 4),
-			_ => 4,
+			Ok(value) => value.clamp(2, max_threads),
+			_ => max_threads,
 		};
 
 		PlaybackManager {
@@ -222,6 +235,44 @@ impl PlaybackManager {
 		self.image_cache.current_dir_len()
 	}
 
+	/// The paths of the images in the current folder. Returns None when the folder hasn't
+	/// finished filtering
+	pub fn image_paths(&mut self) -> Option<Vec<PathBuf>> {
+		self.image_cache.image_paths()
+	}
+
+	/// The file at the current index of the folder, which may still be loading
+	pub fn current_file_path(&self) -> Option<PathBuf> {
+		self.image_cache.current_file_path()
+	}
+
+	/// The index of the shown frame of the current image and the number of its frames that
+	/// are loaded
+	pub fn current_frame(&self) -> Option<(usize, usize)> {
+		self.image_cache.current_frame()
+	}
+
+	/// Makes the thumbnails of the loaded images available in `thumbnails`
+	pub fn set_thumbnails(&mut self, thumbnails: Rc<RefCell<ThumbnailCache>>) {
+		self.image_cache.set_thumbnails(thumbnails);
+	}
+
+	/// Makes the loader compute the histograms of the images it decodes, they are available
+	/// from `current_histogram` afterwards
+	pub fn set_histograms(&self, enabled: bool) {
+		self.image_cache.set_histograms(enabled);
+	}
+
+	/// The current file and its histogram, if it was computed while the image was loaded
+	pub fn current_histogram(&self) -> Option<(&Path, &Histogram)> {
+		self.image_cache.current_histogram()
+	}
+
+	/// Returns true while the current image is being decoded
+	pub fn current_loading(&self) -> bool {
+		self.image_cache.current_loading()
+	}
+
 	pub fn update_directory(&mut self) -> directory::Result<()> {
 		debug!("In `update_directory`");
 		if let LoadRequest::None = self.folder_player.load_request {
@@ -240,6 +291,12 @@ impl PlaybackManager {
 		Ok(())
 	}
 
+	/// Loads the current image again, for example after it was modified on disk.
+	pub fn reload_current(&mut self) -> directory::Result<()> {
+		self.image_cache.invalidate_current();
+		self.update_directory()
+	}
+
 	pub fn request_load(&mut self, request: LoadRequest) {
 		self.folder_player.request_load(request);
 		self.image_player.request_load(LoadRequest::Jump(0));
@@ -279,7 +336,7 @@ impl PlaybackManager {
 	}
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LoadedImgPath {
 	NotYetLoaded,
 	ErrLoading(PathBuf),
@@ -388,14 +445,9 @@ impl<P: Playback> ImgSequencePlayer<P> {
 		// function early. And at the same time I want to use it's value as it is at this line.
 		let mut load_request = LoadRequest::None;
 		mem::swap(&mut self.load_request, &mut load_request);
-		let frame_delta_time_nanos;
-		match self.playback_state {
-			PlaybackState::Present | PlaybackState::RandomPresent => {
-				frame_delta_time_nanos = (NANOS_PER_SEC * 6) as i64;
-			}
-			_ => {
-				frame_delta_time_nanos = P::delay_nanos(self) as i64;
-			}
+		let frame_delta_time_nanos = match self.playback_state {
+			PlaybackState::Present | PlaybackState::RandomPresent => (NANOS_PER_SEC * 6) as i64,
+			_ => P::delay_nanos(self) as i64,
 		};
 		if self.playback_state == PlaybackState::Paused {
 			if let Err(e) = image_cache.process_prefetched(display) {
@@ -429,12 +481,8 @@ impl<P: Playback> ImgSequencePlayer<P> {
 			// This assumes that the following frames have the same delay but that's okay considering that
 			// if frame step is greater than 1 it almost certainly means that we couldn't load the
 			// next frame quiclky enough so there's not much else to do here.
-			let frame_step;
-			if frame_delta_time_nanos > 0 {
-				frame_step = elapsed_nanos / frame_delta_time_nanos;
-			} else {
-				frame_step = 0;
-			}
+			let frame_step =
+				if frame_delta_time_nanos > 0 { elapsed_nanos / frame_delta_time_nanos } else { 0 };
 			if frame_step > 0 {
 				load_request = match self.playback_state {
 					PlaybackState::Forward | PlaybackState::Present => {
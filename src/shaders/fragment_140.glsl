@@ -1,19 +1,85 @@
 #version 140
 uniform sampler2D tex;
-uniform float bright_shade;
+// The same cell of the reference image
+uniform sampler2D ref_tex;
+// Draw the difference to the reference image as a heatmap instead of the image
+uniform bool show_difference;
+// The colors and the size in physical pixels of the squares behind transparent parts
+uniform vec4 bg_color_a;
+uniform vec4 bg_color_b;
+uniform float bg_square_size;
 uniform float lod_level;
+// The opacity of the lines between texels, zero if the pixel grid is off
+uniform float pixel_grid_alpha;
+// 0: RGB, 1: red, 2: green, 3: blue, 4: alpha, 5: luminance
+uniform int channel;
+// The color channels of the image are multiplied by the alpha
+uniform bool premultiplied;
 in vec2 v_tex_coords;
 out vec4 f_color;
+
+// Black for no difference through red and yellow to white for the largest difference
+vec3 heatmap(float t) {
+    return clamp(vec3(3.0 * t, 3.0 * t - 1.0, 3.0 * t - 2.0), 0.0, 1.0);
+}
+
+vec3 srgb_to_linear(vec3 c) {
+    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+
+vec3 linear_to_srgb(vec3 c) {
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
 void main() {
     vec4 color = textureLod(tex, v_tex_coords, lod_level);
-    const float grid_size = 12.0;
+    if (premultiplied && color.a > 0.0) {
+        // The premultiplication was done on the stored, sRGB encoded values
+        color.rgb = srgb_to_linear(min(linear_to_srgb(color.rgb) / color.a, 1.0));
+    }
+    if (channel != 0) {
+        float value;
+        if (channel == 1) {
+            value = color.r;
+        } else if (channel == 2) {
+            value = color.g;
+        } else if (channel == 3) {
+            value = color.b;
+        } else if (channel == 4) {
+            // Alpha is not sRGB encoded, so the shade of gray matches the stored value
+            value = srgb_to_linear(vec3(color.a)).r;
+        } else {
+            value = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+        }
+        color = vec4(vec3(value), 1.0);
+    }
     vec4 grid_color;
-    if ((mod(gl_FragCoord.x, grid_size * 2.0) < grid_size)
-        ^^ (mod(gl_FragCoord.y, grid_size * 2.0) < grid_size)
+    if ((mod(gl_FragCoord.x, bg_square_size * 2.0) < bg_square_size)
+        ^^ (mod(gl_FragCoord.y, bg_square_size * 2.0) < bg_square_size)
     ) {
-        grid_color = vec4(bright_shade);
+        grid_color = bg_color_a;
     } else {
-        grid_color = vec4(bright_shade * 0.55);
+        grid_color = bg_color_b;
     }
     f_color = mix(grid_color, color, color.a);
+    if (show_difference) {
+        vec4 stored = textureLod(tex, v_tex_coords, lod_level);
+        vec4 diff = abs(stored - textureLod(ref_tex, v_tex_coords, lod_level));
+        float max_diff = max(max(diff.r, diff.g), max(diff.b, diff.a));
+        // The square root makes small differences stand out
+        f_color = vec4(heatmap(sqrt(max_diff)), 1.0);
+    }
+    if (pixel_grid_alpha > 0.0) {
+        // Texel edges are at integer texel coordinates. Each cell draws the half of the line
+        // that's inside of it, so lines are continuous across cells.
+        vec2 texel_coords = v_tex_coords * vec2(textureSize(tex, 0));
+        vec2 edge_dist = min(fract(texel_coords), 1.0 - fract(texel_coords));
+        vec2 edge_dist_px = edge_dist / fwidth(texel_coords);
+        if (min(edge_dist_px.x, edge_dist_px.y) < 0.5) {
+            // Dark lines over bright pixels and bright lines over dark ones
+            float luma = dot(f_color.rgb, vec3(0.2126, 0.7152, 0.0722));
+            vec3 line_color = vec3(luma > 0.5 ? 0.0 : 1.0);
+            f_color.rgb = mix(f_color.rgb, line_color, 0.4 * pixel_grid_alpha);
+        }
+    }
 }
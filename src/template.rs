@@ -0,0 +1,163 @@
+//! Text with `${placeholder}` parts that are replaced by information about the shown image,
+//! used for the window title.
+
+use std::path::Path;
+
+use crate::metadata::{self, Metadata};
+
+/// Everything the placeholders can be replaced with. Unknown values are substituted with an
+/// empty string.
+#[derive(Default)]
+pub struct TemplateValues<'a> {
+	/// The file name, or the path with as many folders as configured for the title. It's
+	/// marked if nothing is loaded or the file failed to load.
+	pub name: String,
+	/// The playback mode, eg " : Playing", empty while paused
+	pub playback: &'static str,
+	pub path: Option<&'a Path>,
+	/// The zero based index of the image in its folder
+	pub index: Option<usize>,
+	pub total: Option<usize>,
+	/// The size of the image in pixels, with the view rotation applied
+	pub dimensions: Option<(u32, u32)>,
+	/// Physical pixels per image pixel
+	pub zoom: Option<f32>,
+	/// The zero based index of the shown frame and the number of frames
+	pub frame: Option<(usize, usize)>,
+	pub metadata: Option<&'a Metadata>,
+}
+
+impl TemplateValues<'_> {
+	/// Returns `None` for unknown placeholders
+	fn get(&self, placeholder: &str) -> Option<String> {
+		let text = |value: Option<String>| Some(value.unwrap_or_default());
+		match placeholder {
+			"name" => Some(self.name.clone()),
+			"playback" => Some(self.playback.to_owned()),
+			"filename" => text(
+				self.path.and_then(|p| p.file_name()).map(|n| n.to_string_lossy().into_owned()),
+			),
+			"path" => text(self.path.map(|p| p.to_string_lossy().into_owned())),
+			"folder" => {
+				text(self.path.and_then(|p| p.parent()).map(|p| p.to_string_lossy().into_owned()))
+			}
+			"index" => text(self.index.map(|i| (i + 1).to_string())),
+			"total" => text(self.total.map(|t| t.to_string())),
+			"width" => text(self.dimensions.map(|(w, _)| w.to_string())),
+			"height" => text(self.dimensions.map(|(_, h)| h.to_string())),
+			"zoom" => text(self.zoom.map(format_zoom)),
+			"frame" => text(self.frame.map(|(frame, _)| (frame + 1).to_string())),
+			"frames" => text(self.frame.map(|(_, frames)| frames.to_string())),
+			"size" => text(self.metadata.map(|m| metadata::format_file_size(m.file_size))),
+			_ => {
+				let tag = placeholder.strip_prefix("exif:")?;
+				text(self.metadata.and_then(|m| m.exif_value(tag)).map(|v| v.to_owned()))
+			}
+		}
+	}
+}
+
+/// Replaces the placeholders of the template. Unknown placeholders are left as they are, so
+/// that mistakes are visible.
+pub fn render(template: &str, values: &TemplateValues) -> String {
+	let mut result = String::with_capacity(template.len());
+	let mut rest = template;
+	while let Some(start) = rest.find("${") {
+		result.push_str(&rest[..start]);
+		let after = &rest[start + 2..];
+		let end = match after.find('}') {
+			Some(end) => end,
+			None => {
+				result.push_str(&rest[start..]);
+				return result;
+			}
+		};
+		match values.get(&after[..end]) {
+			Some(value) => result.push_str(&value),
+			None => result.push_str(&rest[start..start + end + 3]),
+		}
+		rest = &after[end + 1..];
+	}
+	result.push_str(rest);
+	result
+}
+
+/// Returns true if the template shows values that have to be read from the file
+pub fn needs_metadata(template: &str) -> bool {
+	template.contains("${size}") || template.contains("${exif:")
+}
+
+/// Formats the zoom level as a percentage, eg `33%`
+pub fn format_zoom(texel_size: f32) -> String {
+	let percentage = texel_size * 100.0;
+	if percentage < 10.0 {
+		format!("{:.1}%", percentage)
+	} else {
+		format!("{:.0}%", percentage)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn values() -> TemplateValues<'static> {
+		TemplateValues {
+			name: "cat.jpg".to_owned(),
+			index: Some(2),
+			total: Some(10),
+			zoom: Some(0.5),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn empty_template() {
+		assert_eq!(render("", &values()), "");
+	}
+
+	#[test]
+	fn text_without_placeholders() {
+		assert_eq!(render("emulsion $ {name} }", &values()), "emulsion $ {name} }");
+	}
+
+	#[test]
+	fn placeholders_are_replaced() {
+		let rendered = render("${name} [${index}/${total}] ${zoom}${name}", &values());
+		assert_eq!(rendered, "cat.jpg [3/10] 50%cat.jpg");
+	}
+
+	#[test]
+	fn missing_values_are_empty() {
+		assert_eq!(render("<${width}x${height}> ${exif:Model}", &values()), "<x> ");
+	}
+
+	#[test]
+	fn unknown_placeholders_are_kept() {
+		assert_eq!(render("${nme} - ${name} ${}", &values()), "${nme} - cat.jpg ${}");
+	}
+
+	#[test]
+	fn unterminated_placeholder_is_kept() {
+		assert_eq!(render("${name} ${index", &values()), "cat.jpg ${index");
+		assert_eq!(render("${", &values()), "${");
+		assert_eq!(render("$", &values()), "$");
+	}
+
+	#[test]
+	fn exif_placeholders() {
+		let mut metadata = Metadata::default();
+		metadata.exif.insert("Model".to_owned(), "X100V".to_owned());
+		metadata.exif.insert("FNumber".to_owned(), "f/2".to_owned());
+		let values = TemplateValues { metadata: Some(&metadata), ..values() };
+		assert_eq!(render("${exif:Model} ${exif:FNumber}", &values), "X100V f/2");
+		assert_eq!(render("[${exif:ISO}]", &values), "[]");
+	}
+
+	#[test]
+	fn metadata_is_needed_for_exif_and_size() {
+		assert!(needs_metadata("${name} ${exif:Model}"));
+		assert!(needs_metadata("${size}"));
+		assert!(!needs_metadata("${name} ${zoom} exif:"));
+	}
+}
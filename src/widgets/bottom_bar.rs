@@ -1,13 +1,15 @@
 use super::picture_widget::ScalingMode;
-use crate::{ConfigWindowSection, Configuration, Theme};
+use crate::{template::format_zoom, ConfigWindowSection, Configuration, Theme};
 
 use gelatin::{
 	button::Button,
+	label::Label,
 	line_layout_container::HorizontalLayoutContainer,
 	misc::{Alignment, Length},
 	picture::Picture,
 	slider::Slider,
 };
+use std::cell::Cell;
 use std::f32;
 use std::rc::Rc;
 
@@ -30,12 +32,23 @@ const ACTIVE_BG_COLOR: [f32; 4] = [0.3, 0.3, 0.3, 0.5];
 const SMALL_BUTTON_GAP: f32 = 4.0;
 const BIG_BUTTON_GAP: f32 = 32.0;
 const BUTTON_SIZE: f32 = 24.0;
+const ZOOM_LABEL_WIDTH: f32 = 52.0;
+const LOCK_LABEL_WIDTH: f32 = 52.0;
+/// The status label is as wide as its text but not narrower than this
+const STATUS_LABEL_MIN_WIDTH: f32 = 52.0;
 
 pub struct BottomBar {
 	pub widget: Rc<HorizontalLayoutContainer>,
 	pub orig_scale_button: Rc<Button>,
 	pub fit_stretch_button: Rc<Button>,
 	pub fit_best_button: Rc<Button>,
+	pub zoom_label: Rc<Label>,
+	/// Shows whether the view is locked
+	pub lock_label: Rc<Label>,
+	/// The configurable text, see `Configuration::status_format`
+	pub status_label: Rc<Label>,
+	/// The width that the status label was last given
+	status_width: Cell<f32>,
 	pub slider: Rc<Slider>,
 	pub theme_button: Rc<Button>,
 	pub help_button: Rc<Button>,
@@ -82,6 +95,9 @@ impl BottomBar {
 		let orig_scale_button = make_icon_button(Alignment::Start);
 		let fit_best_button = make_icon_button(Alignment::Start);
 		let fit_stretch_button = make_icon_button(Alignment::Start);
+		let zoom_label = make_text_label(ZOOM_LABEL_WIDTH);
+		let lock_label = make_text_label(LOCK_LABEL_WIDTH);
+		let status_label = make_text_label(STATUS_LABEL_MIN_WIDTH);
 		let slider = make_slider();
 		let theme_button = make_icon_button(Alignment::End);
 		let help_button = make_icon_button(Alignment::End);
@@ -95,6 +111,9 @@ impl BottomBar {
 		widget.add_child(orig_scale_button.clone());
 		widget.add_child(fit_best_button.clone());
 		widget.add_child(fit_stretch_button.clone());
+		widget.add_child(zoom_label.clone());
+		widget.add_child(lock_label.clone());
+		widget.add_child(status_label.clone());
 		widget.add_child(slider.clone());
 		widget.add_child(theme_button.clone());
 		widget.add_child(help_button.clone());
@@ -112,6 +131,10 @@ impl BottomBar {
 			orig_scale_button,
 			fit_stretch_button,
 			fit_best_button,
+			zoom_label,
+			lock_label,
+			status_label,
+			status_width: Cell::new(STATUS_LABEL_MIN_WIDTH),
 			slider,
 			theme_button,
 			help_button,
@@ -175,6 +198,26 @@ impl BottomBar {
 		self.help_button.set_bg_color(if visible { ACTIVE_BG_COLOR } else { NO_BG_COLOR })
 	}
 
+	/// Shows the zoom level as a percentage, where 100% means that an image pixel covers a
+	/// single display pixel. Nothing is shown if `img_texel_size` is `None`.
+	pub fn set_zoom(&self, img_texel_size: Option<f32>) {
+		self.zoom_label.set_text(img_texel_size.map(format_zoom).unwrap_or_default());
+	}
+
+	pub fn set_view_locked(&self, locked: bool) {
+		self.lock_label.set_text(if locked { "Locked" } else { "" });
+	}
+
+	/// Shows the rendered status format. The label grows with the text and the slider
+	/// takes the remaining space. `dpi_scale` is the DPI scaling of the window.
+	pub fn set_status<S: AsRef<str>>(&self, text: S, dpi_scale: f32) {
+		self.status_label.set_text(text);
+		let width = self.status_label.text_width(dpi_scale).ceil().max(STATUS_LABEL_MIN_WIDTH);
+		if self.status_width.replace(width) != width {
+			self.status_label.set_width(Length::Fixed(width));
+		}
+	}
+
 	pub fn update_scaling_buttons(&self, scaling: ScalingMode, img_texel_size: f32) {
 		match scaling {
 			#[allow(clippy::float_cmp)]
@@ -197,6 +240,11 @@ impl BottomBar {
 				self.fit_best_button.set_bg_color(NO_BG_COLOR);
 				self.fit_stretch_button.set_bg_color(ACTIVE_BG_COLOR);
 			}
+			ScalingMode::FitWidth | ScalingMode::FitHeight => {
+				self.orig_scale_button.set_bg_color(NO_BG_COLOR);
+				self.fit_best_button.set_bg_color(NO_BG_COLOR);
+				self.fit_stretch_button.set_bg_color(NO_BG_COLOR);
+			}
 		}
 	}
 }
@@ -210,13 +258,24 @@ fn make_icon_button(alignment: Alignment) -> Rc<Button> {
 	button
 }
 
+fn make_text_label(width: f32) -> Rc<Label> {
+	let label = Rc::new(Label::new());
+	label.set_margin_top(SMALL_BUTTON_GAP);
+	label.set_margin_left(SMALL_BUTTON_GAP * 2.0);
+	label.set_height(Length::Fixed(BUTTON_SIZE));
+	label.set_width(Length::Fixed(width));
+	label.set_horizontal_align(Alignment::Start);
+	label.set_font_size(13.0);
+	label
+}
+
 fn make_slider() -> Rc<Slider> {
 	let slider = Rc::new(Slider::new());
 	slider.set_margin_top(SMALL_BUTTON_GAP);
 	slider.set_margin_left(BIG_BUTTON_GAP);
 	slider.set_margin_right(BIG_BUTTON_GAP);
 	slider.set_height(Length::Fixed(BUTTON_SIZE));
-	slider.set_width(Length::Stretch { min: 0.0, max: std::f32::INFINITY });
+	slider.set_width(Length::Stretch { min: 0.0, max: f32::INFINITY });
 	slider.set_horizontal_align(Alignment::Center);
 	slider.set_steps(6, 1);
 	slider
@@ -0,0 +1,450 @@
+use std::{cell::RefCell, rc::Rc};
+
+use gelatin::{
+	add_common_widget_functions,
+	glium::Frame,
+	misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement},
+	window::RenderValidity,
+	winit::{
+		event::{ElementState, MouseButton},
+		keyboard::{Key, NamedKey},
+	},
+	DrawContext, Event, EventKind, NextUpdate, Widget, WidgetData, WidgetError,
+};
+
+use crate::input_handling::{display_key, PaletteAction, PaletteItem};
+
+const FONT_SIZE: f32 = 14.0;
+const QUERY_FONT_SIZE: f32 = 16.0;
+/// The panel is centered horizontally and never wider than this
+const PANEL_MAX_WIDTH: f32 = 560.0;
+/// The distance between the top of the window and the panel
+const TOP_MARGIN: f32 = 64.0;
+const MARGIN: f32 = 16.0;
+const PADDING: f32 = 8.0;
+const QUERY_HEIGHT: f32 = 36.0;
+const ROW_HEIGHT: f32 = 28.0;
+/// At most this many items are shown, the list scrolls to keep the selected one visible
+const MAX_ROWS: usize = 12;
+
+const TEXT_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const DIM_TEXT_COLOR: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+const SELECTED_COLOR: [f32; 4] = [0.25, 0.45, 0.75, 0.6];
+
+static PLACEHOLDER: &str = "Type to search the commands";
+
+/// Scores how well `query` matches `text` when the characters of the query appear in `text`
+/// in the same order, but not necessarily next to each other. Consecutive characters and
+/// characters at the start of words score higher. `None` if there's no match.
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+	let text = text.chars().flat_map(char::to_lowercase).collect::<Vec<_>>();
+	let mut score = 0;
+	let mut start = 0;
+	let mut prev_index = None;
+	for ch in query.chars().flat_map(char::to_lowercase).filter(|ch| !ch.is_whitespace()) {
+		let index = start + text[start..].iter().position(|&t| t == ch)?;
+		score += 1;
+		if index > 0 && prev_index == Some(index - 1) {
+			score += 5;
+		}
+		if index == 0 || !text[index - 1].is_alphanumeric() {
+			score += 3;
+		}
+		prev_index = Some(index);
+		start = index + 1;
+	}
+	Some(score)
+}
+
+type RunCallback = Rc<dyn Fn(&PaletteAction)>;
+
+struct CommandPaletteData {
+	placement: WidgetPlacement,
+	drawn_bounds: LogicalRect,
+	visible: bool,
+	render_validity: RenderValidity,
+
+	items: Vec<PaletteItem>,
+	query: String,
+	/// The indices of the items that match the query, the best match first
+	matches: Vec<usize>,
+	/// An index into `matches`
+	selected: usize,
+	/// The index into `matches` of the topmost row
+	first_row: usize,
+
+	on_run: Option<RunCallback>,
+}
+
+impl WidgetData for CommandPaletteData {
+	fn placement(&mut self) -> &mut WidgetPlacement {
+		&mut self.placement
+	}
+	fn drawn_bounds(&mut self) -> &mut LogicalRect {
+		&mut self.drawn_bounds
+	}
+	fn visible(&mut self) -> &mut bool {
+		&mut self.visible
+	}
+}
+
+impl CommandPaletteData {
+	fn set_query(&mut self, query: String) {
+		self.query = query;
+		let mut scored = self
+			.items
+			.iter()
+			.enumerate()
+			.filter_map(|(index, item)| {
+				let name_score = match &item.action {
+					PaletteAction::Action(name) => fuzzy_score(&self.query, name),
+					PaletteAction::Command(_) => None,
+				};
+				let score = fuzzy_score(&self.query, &item.description).max(name_score)?;
+				Some((score, index))
+			})
+			.collect::<Vec<_>>();
+		// The sort is stable so equally good matches keep the order of the items
+		scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+		self.matches = scored.into_iter().map(|(_, index)| index).collect();
+		self.selected = 0;
+		self.first_row = 0;
+		self.render_validity.invalidate();
+	}
+
+	fn select(&mut self, selected: usize) {
+		if self.matches.is_empty() {
+			return;
+		}
+		self.selected = selected.min(self.matches.len() - 1);
+		if self.selected < self.first_row {
+			self.first_row = self.selected;
+		} else if self.selected >= self.first_row + MAX_ROWS {
+			self.first_row = self.selected + 1 - MAX_ROWS;
+		}
+		self.render_validity.invalidate();
+	}
+
+	fn row_count(&self) -> usize {
+		self.matches.len().clamp(1, MAX_ROWS)
+	}
+
+	fn panel_rect(&self) -> LogicalRect {
+		let bounds = self.drawn_bounds;
+		let width = (bounds.size.vec.x - 2.0 * MARGIN).clamp(0.0, PANEL_MAX_WIDTH);
+		let height = QUERY_HEIGHT + self.row_count() as f32 * ROW_HEIGHT + 2.0 * PADDING;
+		let top = bounds.top() + TOP_MARGIN.min((bounds.size.vec.y - height).max(0.0) * 0.5);
+		LogicalRect {
+			pos: LogicalVector::new(bounds.left() + (bounds.size.vec.x - width) * 0.5, top),
+			size: LogicalVector::new(width, height),
+		}
+	}
+
+	fn row_rect(&self, row: usize) -> LogicalRect {
+		let panel = self.panel_rect();
+		LogicalRect {
+			pos: panel.pos
+				+ LogicalVector::new(PADDING, PADDING + QUERY_HEIGHT + row as f32 * ROW_HEIGHT),
+			size: LogicalVector::new((panel.size.vec.x - 2.0 * PADDING).max(0.0), ROW_HEIGHT),
+		}
+	}
+
+	/// The index into `matches` of the row under `pos`
+	fn match_at(&self, pos: LogicalVector) -> Option<usize> {
+		let visible_rows = self.matches.len().saturating_sub(self.first_row).min(MAX_ROWS);
+		(0..visible_rows)
+			.find(|&row| self.row_rect(row).contains(pos))
+			.map(|row| self.first_row + row)
+	}
+}
+
+/// Lists the actions and the custom commands with their key bindings. Typing filters the list
+/// with a fuzzy search and Enter runs the selected item.
+pub struct CommandPalette {
+	data: RefCell<CommandPaletteData>,
+}
+
+impl CommandPalette {
+	pub fn new() -> CommandPalette {
+		let placement = WidgetPlacement {
+			width: Length::Stretch { min: 0.0, max: f32::INFINITY },
+			height: Length::Stretch { min: 0.0, max: f32::INFINITY },
+			horizontal_align: Alignment::Center,
+			vertical_align: Alignment::Center,
+			ignore_layout: true,
+			..Default::default()
+		};
+		CommandPalette {
+			data: RefCell::new(CommandPaletteData {
+				placement,
+				drawn_bounds: Default::default(),
+				visible: false,
+				render_validity: Default::default(),
+				items: Vec::new(),
+				query: String::new(),
+				matches: Vec::new(),
+				selected: 0,
+				first_row: 0,
+				on_run: None,
+			}),
+		}
+	}
+
+	add_common_widget_functions!(data);
+
+	pub fn set_items(&self, items: Vec<PaletteItem>) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.items = items;
+		let query = std::mem::take(&mut borrowed.query);
+		borrowed.set_query(query);
+	}
+
+	/// Called with the action of the item that was picked, after the palette is closed
+	pub fn set_on_run<F: Fn(&PaletteAction) + 'static>(&self, callback: F) {
+		self.data.borrow_mut().on_run = Some(Rc::new(callback));
+	}
+
+	/// Shows the palette with an empty query
+	pub fn open(&self) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.visible = true;
+		borrowed.set_query(String::new());
+	}
+
+	fn run(&self, match_index: usize) {
+		let (action, callback) = {
+			let mut borrowed = self.data.borrow_mut();
+			let item = match borrowed.matches.get(match_index) {
+				Some(&item) => item,
+				None => return,
+			};
+			borrowed.visible = false;
+			borrowed.render_validity.invalidate();
+			(borrowed.items[item].action.clone(), borrowed.on_run.clone())
+		};
+		if let Some(callback) = callback {
+			callback(&action);
+		}
+	}
+
+	fn handle_key_input(&self, event: &Event) {
+		let input = match &event.kind {
+			EventKind::KeyInput { input } if input.state == ElementState::Pressed => input,
+			_ => return,
+		};
+		let mut borrowed = self.data.borrow_mut();
+		let selected = borrowed.selected;
+		match &input.logical_key {
+			Key::Named(NamedKey::Escape) => {
+				borrowed.visible = false;
+				borrowed.render_validity.invalidate();
+			}
+			Key::Named(NamedKey::Enter) => {
+				drop(borrowed);
+				self.run(selected);
+			}
+			Key::Named(NamedKey::Backspace) => {
+				let mut query = borrowed.query.clone();
+				query.pop();
+				borrowed.set_query(query);
+			}
+			Key::Named(NamedKey::ArrowUp) => borrowed.select(selected.saturating_sub(1)),
+			Key::Named(NamedKey::ArrowDown) => borrowed.select(selected + 1),
+			Key::Named(NamedKey::PageUp) => borrowed.select(selected.saturating_sub(MAX_ROWS)),
+			Key::Named(NamedKey::PageDown) => borrowed.select(selected + MAX_ROWS),
+			_ => {
+				let shortcut = event.modifiers.control_key() || event.modifiers.super_key();
+				if let Some(typed) = input.text.as_ref().filter(|_| !shortcut) {
+					let typed = typed.chars().filter(|ch| !ch.is_control());
+					let query = borrowed.query.chars().chain(typed).collect();
+					borrowed.set_query(query);
+				}
+			}
+		}
+	}
+
+	fn draw_query(&self, target: &mut Frame, context: &DrawContext, data: &CommandPaletteData) {
+		let panel = data.panel_rect();
+		let text_h = context.line_height(QUERY_FONT_SIZE);
+		let pos =
+			panel.pos + LogicalVector::new(PADDING * 2.0, PADDING + (QUERY_HEIGHT - text_h) * 0.5);
+		let cursor_x = if data.query.is_empty() {
+			let color = DIM_TEXT_COLOR;
+			context.draw_text(target, PLACEHOLDER, QUERY_FONT_SIZE, pos, color, Some(panel));
+			pos.vec.x
+		} else {
+			let query = &data.query;
+			context.draw_text(target, query, QUERY_FONT_SIZE, pos, TEXT_COLOR, Some(panel));
+			pos.vec.x + context.text_width(query, QUERY_FONT_SIZE) + 1.0
+		};
+		let cursor = LogicalRect {
+			pos: LogicalVector::new(cursor_x, pos.vec.y),
+			size: LogicalVector::new(1.5, text_h),
+		};
+		context.clear_color(target, TEXT_COLOR, Some(cursor));
+		let separator = LogicalRect {
+			pos: LogicalVector::new(panel.left() + PADDING, panel.top() + PADDING + QUERY_HEIGHT),
+			size: LogicalVector::new((panel.size.vec.x - 2.0 * PADDING).max(0.0), 1.0),
+		};
+		context.clear_color(target, [1.0, 1.0, 1.0, 0.2], Some(separator));
+	}
+
+	fn draw_rows(&self, target: &mut Frame, context: &DrawContext, data: &CommandPaletteData) {
+		let text_h = context.line_height(FONT_SIZE);
+		if data.matches.is_empty() {
+			let rect = data.row_rect(0);
+			let pos = rect.pos + LogicalVector::new(PADDING, (ROW_HEIGHT - text_h) * 0.5);
+			let text = "No matching commands";
+			context.draw_text(target, text, FONT_SIZE, pos, DIM_TEXT_COLOR, Some(rect));
+			return;
+		}
+		let rows = data.matches.iter().enumerate().skip(data.first_row).take(MAX_ROWS);
+		for (row, (match_index, &item_index)) in rows.enumerate() {
+			let item = &data.items[item_index];
+			let rect = data.row_rect(row);
+			if match_index == data.selected {
+				context.clear_color(target, SELECTED_COLOR, Some(rect));
+			}
+			let y = rect.top() + (ROW_HEIGHT - text_h) * 0.5;
+			// The keys are right aligned and the description is cut off before them
+			let keys = item.keys.iter().map(|k| display_key(k)).collect::<Vec<_>>().join("  ");
+			let keys_w = context.text_width(&keys, FONT_SIZE);
+			let keys_x = rect.right() - PADDING - keys_w;
+			let pos = LogicalVector::new(keys_x, y);
+			context.draw_text(target, &keys, FONT_SIZE, pos, DIM_TEXT_COLOR, Some(rect));
+			let description_clip = LogicalRect {
+				pos: rect.pos,
+				size: LogicalVector::new((keys_x - rect.left() - PADDING).max(0.0), ROW_HEIGHT),
+			};
+			let pos = LogicalVector::new(rect.left() + PADDING, y);
+			let description = &item.description;
+			let clip = Some(description_clip);
+			context.draw_text(target, description, FONT_SIZE, pos, TEXT_COLOR, clip);
+		}
+	}
+}
+
+impl Default for CommandPalette {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Widget for CommandPalette {
+	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
+		let borrowed = self.data.borrow();
+		if !borrowed.visible {
+			return Ok(NextUpdate::Latest);
+		}
+		context.clear_color(target, [0.0, 0.0, 0.0, 0.3], Some(borrowed.drawn_bounds));
+		context.clear_color(target, [0.08, 0.08, 0.08, 0.96], Some(borrowed.panel_rect()));
+		self.draw_query(target, context, &borrowed);
+		self.draw_rows(target, context, &borrowed);
+		Ok(NextUpdate::Latest)
+	}
+
+	fn layout(&self, available_space: LogicalRect) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.default_layout(available_space);
+	}
+
+	fn handle_event(&self, event: &Event) {
+		if !self.data.borrow().visible {
+			return;
+		}
+		match event.kind {
+			EventKind::MouseMove => {
+				let mut borrowed = self.data.borrow_mut();
+				if let Some(hovered) = borrowed.match_at(event.cursor_pos) {
+					if hovered != borrowed.selected {
+						borrowed.select(hovered);
+					}
+				}
+			}
+			EventKind::MouseButton { state: ElementState::Pressed, button: MouseButton::Left } => {
+				let mut borrowed = self.data.borrow_mut();
+				if let Some(clicked) = borrowed.match_at(event.cursor_pos) {
+					drop(borrowed);
+					self.run(clicked);
+				} else if !borrowed.panel_rect().contains(event.cursor_pos) {
+					borrowed.visible = false;
+					borrowed.render_validity.invalidate();
+				}
+			}
+			EventKind::MouseScroll { delta } => {
+				let mut borrowed = self.data.borrow_mut();
+				let max_first_row = borrowed.matches.len().saturating_sub(MAX_ROWS);
+				let first_row = if delta.vec.y > 0.0 {
+					borrowed.first_row.saturating_sub(1)
+				} else {
+					(borrowed.first_row + 1).min(max_first_row)
+				};
+				borrowed.first_row = first_row;
+				borrowed.selected = borrowed.selected.clamp(first_row, first_row + MAX_ROWS - 1);
+				borrowed.render_validity.invalidate();
+			}
+			EventKind::KeyInput { .. } => self.handle_key_input(event),
+			_ => (),
+		}
+	}
+
+	fn children(&self, _children: &mut Vec<Rc<dyn Widget>>) {}
+
+	fn placement(&self) -> WidgetPlacement {
+		self.data.borrow().placement
+	}
+
+	fn visible(&self) -> bool {
+		self.data.borrow().visible
+	}
+
+	fn set_valid_ref(&self, render_validity: RenderValidity) {
+		self.data.borrow_mut().render_validity = render_validity;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn matching_is_case_insensitive() {
+		assert_eq!(fuzzy_score("ZOOM", "zoom in"), fuzzy_score("zoom", "zoom in"));
+		assert_eq!(fuzzy_score("zoom", "Zoom In"), fuzzy_score("zoom", "zoom in"));
+		assert!(fuzzy_score("ÉT", "édition étendue").is_some());
+	}
+
+	#[test]
+	fn consecutive_characters_score_higher() {
+		let consecutive = fuzzy_score("flip", "flip horizontally").unwrap();
+		let scattered = fuzzy_score("flip", "fill the clipboard").unwrap();
+		assert!(consecutive > scattered, "{} <= {}", consecutive, scattered);
+	}
+
+	#[test]
+	fn word_starts_score_higher() {
+		assert!(fuzzy_score("ni", "next image") > fuzzy_score("ni", "nextimage"));
+		assert!(fuzzy_score("ti", "toggle_inspector") > fuzzy_score("ti", "toggling"));
+	}
+
+	#[test]
+	fn characters_have_to_be_in_order() {
+		assert_eq!(fuzzy_score("piz", "zoom in"), None);
+		assert_eq!(fuzzy_score("mooz", "zoom"), None);
+		assert_eq!(fuzzy_score("zooom", "zoom"), None);
+		assert!(fuzzy_score("zm", "zoom").is_some());
+	}
+
+	#[test]
+	fn empty_query_matches_everything_equally() {
+		assert_eq!(fuzzy_score("", "zoom in"), Some(0));
+		assert_eq!(fuzzy_score("  ", "next image"), Some(0));
+		assert_eq!(fuzzy_score("", ""), Some(0));
+		assert_eq!(fuzzy_score("a", ""), None);
+	}
+
+	#[test]
+	fn whitespace_in_the_query_is_ignored() {
+		assert_eq!(fuzzy_score("zoom in", "zoom_in"), fuzzy_score("zoomin", "zoom_in"));
+	}
+}
@@ -0,0 +1,210 @@
+//! Compare mode: a second image next to the current one or on top of it
+
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use gelatin::glium::Frame;
+use gelatin::misc::{LogicalRect, LogicalVector};
+use gelatin::window::Window;
+use gelatin::{DrawContext, NextUpdate};
+
+use crate::image_cache::{image_loader::Orientation, AnimationFrameTexture};
+use crate::playback_manager::{LoadRequest, LoadedImgPath, PlaybackManager};
+
+use super::overlay::{self, intersect, rect_from_edges};
+
+/// How close to the divider a click has to be to grab it, in logical pixels
+const DIVIDER_GRAB_DISTANCE: f32 = 6.0;
+
+/// How the two images of compare mode are arranged
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CompareLayout {
+	/// Each image gets one half of the widget
+	SideBySide,
+	/// The images are drawn on top of each other. The main image is visible to the left of
+	/// the divider and the other image to the right of it.
+	Swipe,
+}
+
+/// The second image of compare mode. It's navigated independently of the main image but
+/// shares its zoom level, position and view orientation.
+///
+/// The geometry is computed from `bounds`, the area of the whole picture widget.
+pub struct ComparePane {
+	playback_manager: PlaybackManager,
+	layout: CompareLayout,
+	/// The position of the divider in the swipe layout, relative to the width of the widget
+	divider: f32,
+	dragging_divider: bool,
+}
+
+impl ComparePane {
+	/// Starts loading the image at `path`
+	pub fn new(path: PathBuf) -> Self {
+		let mut playback_manager = PlaybackManager::new_secondary();
+		playback_manager.request_load(LoadRequest::FilePath(path));
+		ComparePane {
+			playback_manager,
+			layout: CompareLayout::SideBySide,
+			divider: 0.5,
+			dragging_divider: false,
+		}
+	}
+
+	pub fn request_load(&mut self, request: LoadRequest) {
+		self.playback_manager.request_load(request);
+	}
+
+	pub fn shown_file_path(&self) -> &LoadedImgPath {
+		self.playback_manager.shown_file_path()
+	}
+
+	/// Returns the current texture with the view orientation applied
+	pub fn texture(&self, view_orientation: Orientation) -> Option<AnimationFrameTexture> {
+		let mut texture = self.playback_manager.image_texture()?;
+		texture.orientation = texture.orientation.followed_by(view_orientation);
+		Some(texture)
+	}
+
+	/// Loads the requested images. Returns true if the shown texture changed.
+	pub fn update(&mut self, window: &Window) -> (bool, NextUpdate) {
+		let prev_texture = self.playback_manager.image_texture();
+		let next_update = self.playback_manager.update_image(window);
+		let new_texture = self.playback_manager.image_texture();
+		let changed = match (prev_texture, new_texture) {
+			(Some(prev), Some(new)) => !Rc::ptr_eq(&prev.tex_grid, &new.tex_grid),
+			(prev, new) => prev.is_none() != new.is_none(),
+		};
+		(changed, next_update)
+	}
+
+	pub fn cycle_layout(&mut self) {
+		self.layout = match self.layout {
+			CompareLayout::SideBySide => CompareLayout::Swipe,
+			CompareLayout::Swipe => CompareLayout::SideBySide,
+		};
+	}
+
+	/// The area that the position and the size of the main image are relative to. This is
+	/// the left half of the widget in the side by side layout, otherwise the whole widget.
+	pub fn main_view_bounds(&self, bounds: LogicalRect) -> LogicalRect {
+		match self.layout {
+			CompareLayout::SideBySide => {
+				let size = LogicalVector::new(bounds.size.vec.x * 0.5, bounds.size.vec.y);
+				LogicalRect { pos: bounds.pos, size }
+			}
+			CompareLayout::Swipe => bounds,
+		}
+	}
+
+	/// The area that the compare image is positioned in, the same way as the main image is
+	/// positioned in `main_view_bounds`
+	pub fn view_bounds(&self, bounds: LogicalRect) -> LogicalRect {
+		let main_bounds = self.main_view_bounds(bounds);
+		match self.layout {
+			CompareLayout::SideBySide => LogicalRect {
+				pos: main_bounds.pos + LogicalVector::new(main_bounds.size.vec.x, 0.0),
+				size: main_bounds.size,
+			},
+			CompareLayout::Swipe => main_bounds,
+		}
+	}
+
+	/// The horizontal position of the line between the two images
+	pub fn divider_x(&self, bounds: LogicalRect) -> f32 {
+		match self.layout {
+			CompareLayout::SideBySide => bounds.left() + bounds.size.vec.x * 0.5,
+			CompareLayout::Swipe => bounds.left() + bounds.size.vec.x * self.divider,
+		}
+	}
+
+	/// The part of the widget where the main image is visible
+	pub fn main_pane(&self, bounds: LogicalRect) -> LogicalRect {
+		rect_from_edges(bounds.left(), bounds.top(), self.divider_x(bounds), bounds.bottom())
+	}
+
+	/// The part of the widget where the compare image is visible
+	pub fn pane(&self, bounds: LogicalRect) -> LogicalRect {
+		rect_from_edges(self.divider_x(bounds), bounds.top(), bounds.right(), bounds.bottom())
+	}
+
+	/// Maps a position over the compare image to the position over the main image that shows
+	/// the same point of the image. Other positions are returned unchanged.
+	pub fn to_main_pane(&self, bounds: LogicalRect, pos: LogicalVector) -> LogicalVector {
+		let view_bounds = self.view_bounds(bounds);
+		if self.layout == CompareLayout::SideBySide && view_bounds.contains(pos) {
+			pos - (view_bounds.pos - self.main_view_bounds(bounds).pos)
+		} else {
+			pos
+		}
+	}
+
+	/// Grabs the divider of the swipe layout if `pressed` is true and the cursor is on it,
+	/// otherwise lets go of it. Returns true if the divider was or is being dragged, in which
+	/// case the click shouldn't be handled otherwise.
+	pub fn grab_divider(
+		&mut self,
+		bounds: LogicalRect,
+		cursor: LogicalVector,
+		pressed: bool,
+	) -> bool {
+		let on_divider = self.layout == CompareLayout::Swipe
+			&& (cursor.vec.x - self.divider_x(bounds)).abs() <= DIVIDER_GRAB_DISTANCE;
+		let was_dragging = self.dragging_divider;
+		self.dragging_divider = pressed && on_divider;
+		was_dragging || self.dragging_divider
+	}
+
+	/// Moves the divider to the cursor while it's grabbed. Returns false if it isn't grabbed.
+	pub fn drag_divider(&mut self, bounds: LogicalRect, cursor: LogicalVector) -> bool {
+		if !self.dragging_divider {
+			return false;
+		}
+		let divider = (cursor.vec.x - bounds.left()) / bounds.size.vec.x;
+		self.divider = divider.clamp(0.0, 1.0);
+		true
+	}
+
+	/// Draws the divider between the images and the name of each image
+	pub fn draw(
+		&self,
+		target: &mut Frame,
+		context: &DrawContext,
+		bounds: LogicalRect,
+		main_path: &LoadedImgPath,
+	) {
+		const WIDTH: f32 = 2.0;
+		let divider_x = self.divider_x(bounds);
+		let line = rect_from_edges(
+			divider_x - WIDTH * 0.5,
+			bounds.top(),
+			divider_x + WIDTH * 0.5,
+			bounds.bottom(),
+		);
+		let outline = LogicalRect {
+			pos: line.pos - LogicalVector::new(1.0, 0.0),
+			size: line.size + LogicalVector::new(2.0, 0.0),
+		};
+		if let Some(outline) = intersect(outline, bounds) {
+			context.clear_color(target, [0.0, 0.0, 0.0, 0.8], Some(outline));
+		}
+		if let Some(line) = intersect(line, bounds) {
+			context.clear_color(target, [1.0, 1.0, 1.0, 0.9], Some(line));
+		}
+
+		let file_name = |path: &LoadedImgPath| match path {
+			LoadedImgPath::Loaded(path) | LoadedImgPath::ErrLoading(path) => {
+				path.file_name().map(|name| name.to_string_lossy().into_owned())
+			}
+			LoadedImgPath::NotYetLoaded => None,
+		};
+		let names = [file_name(main_path), file_name(self.shown_file_path())];
+		let panes = [self.main_pane(bounds), self.pane(bounds)];
+		for (name, pane) in names.iter().zip(panes.iter()) {
+			if let Some(name) = name {
+				// The label is cut off if the pane is too narrow for it
+				overlay::draw_label(target, context, name, pane.pos, *pane, overlay::LABEL_COLOR);
+			}
+		}
+	}
+}
@@ -0,0 +1,73 @@
+use std::path::{Path, PathBuf};
+
+use crate::parallel_action::ParallelAction;
+
+/// Information about the shown image that's computed on a worker thread, like its histogram
+pub struct FilePanel<T> {
+	action: ParallelAction<PathBuf, (PathBuf, Result<T, String>)>,
+	/// The image that is being processed
+	pending: Option<PathBuf>,
+	result: Option<(PathBuf, Result<T, String>)>,
+}
+
+impl<T: Send + 'static> FilePanel<T> {
+	pub fn new(worker: fn(PathBuf) -> (PathBuf, Result<T, String>)) -> Self {
+		FilePanel { action: ParallelAction::new(worker), pending: None, result: None }
+	}
+
+	/// Collects the output of the worker thread and starts processing `path` if there's no
+	/// result for it yet. Returns true if an output arrived.
+	pub fn update(&mut self, path: Option<PathBuf>) -> bool {
+		let mut received = false;
+		if let Some(output) = self.action.try_get_output() {
+			// The outputs of the images that are no longer shown are dropped
+			if self.pending.as_ref() == Some(&output.0) {
+				self.pending = None;
+				self.result = Some(output);
+				received = true;
+			}
+		}
+		if let Some(path) = path {
+			let result_path = self.result.as_ref().map(|(path, _)| path);
+			if Some(&path) != result_path && Some(&path) != self.pending.as_ref() {
+				self.result = None;
+				self.pending = Some(path.clone());
+				self.action.give_input(path);
+			}
+		}
+		received
+	}
+}
+
+impl<T> FilePanel<T> {
+	/// Returns true while an image is being processed
+	pub fn pending(&self) -> bool {
+		self.pending.is_some()
+	}
+
+	/// The path of the last processed image and the result
+	pub fn result(&self) -> Option<(&Path, &Result<T, String>)> {
+		self.result.as_ref().map(|(path, result)| (path.as_path(), result))
+	}
+
+	/// The value computed for the image at `path` if it has been computed successfully
+	pub fn value_for(&self, path: &Path) -> Option<&T> {
+		match self.result() {
+			Some((result_path, Ok(value))) if result_path == path => Some(value),
+			_ => None,
+		}
+	}
+}
+
+impl<T: Clone> FilePanel<T> {
+	/// Uses a value computed elsewhere as the result for `path`. Returns true if the result
+	/// changed.
+	pub fn set_result(&mut self, path: &Path, value: &T) -> bool {
+		if self.result.as_ref().is_some_and(|(result_path, _)| result_path == path) {
+			return false;
+		}
+		self.pending = None;
+		self.result = Some((path.to_owned(), Ok(value.clone())));
+		true
+	}
+}
@@ -0,0 +1,238 @@
+use std::{
+	cell::RefCell,
+	path::{Path, PathBuf},
+	rc::Rc,
+	time::{Duration, Instant},
+};
+
+use gelatin::{
+	add_common_widget_functions,
+	glium::Frame,
+	misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement},
+	window::{RenderValidity, Window},
+	winit::event::{ElementState, MouseButton},
+	DrawContext, Event, EventKind, NextUpdate, Widget, WidgetData, WidgetError,
+};
+
+use crate::image_cache::thumbnails::ThumbnailCache;
+
+/// The height of the strip in logical pixels. The cells are squares of this size.
+const FILMSTRIP_HEIGHT: f32 = 72.0;
+const CELL_PADDING: f32 = 4.0;
+
+struct FilmstripData {
+	placement: WidgetPlacement,
+	drawn_bounds: LogicalRect,
+	visible: bool,
+	render_validity: RenderValidity,
+
+	thumbnails: Rc<RefCell<ThumbnailCache>>,
+	/// The value of `ThumbnailCache::generation` at the last draw
+	drawn_generation: u64,
+	paths: Vec<PathBuf>,
+	current: usize,
+	hovered: Option<usize>,
+
+	on_click: Option<Rc<dyn Fn(usize)>>,
+}
+
+impl WidgetData for FilmstripData {
+	fn placement(&mut self) -> &mut WidgetPlacement {
+		&mut self.placement
+	}
+	fn drawn_bounds(&mut self) -> &mut LogicalRect {
+		&mut self.drawn_bounds
+	}
+	fn visible(&mut self) -> &mut bool {
+		&mut self.visible
+	}
+}
+
+impl FilmstripData {
+	/// The number of cells on each side of the current one
+	fn cells_per_side(&self) -> usize {
+		let cells = (self.drawn_bounds.size.vec.x / FILMSTRIP_HEIGHT) as usize;
+		cells.saturating_sub(1) / 2
+	}
+
+	/// The current image is always in the middle
+	fn cell_rect(&self, index: usize) -> LogicalRect {
+		let bounds = self.drawn_bounds;
+		let center = bounds.left() + bounds.size.vec.x * 0.5;
+		let offset = index as f32 - self.current as f32;
+		LogicalRect {
+			pos: LogicalVector::new(
+				center + (offset - 0.5) * FILMSTRIP_HEIGHT,
+				bounds.top() + (bounds.size.vec.y - FILMSTRIP_HEIGHT) * 0.5,
+			),
+			size: LogicalVector::new(FILMSTRIP_HEIGHT, FILMSTRIP_HEIGHT),
+		}
+	}
+
+	/// The indices of the images that are on the strip
+	fn shown_range(&self) -> std::ops::Range<usize> {
+		let side = self.cells_per_side();
+		let first = self.current.saturating_sub(side);
+		let last = (self.current + side + 1).min(self.paths.len());
+		first..last
+	}
+
+	fn index_at(&self, pos: LogicalVector) -> Option<usize> {
+		self.shown_range().find(|&index| self.cell_rect(index).contains(pos))
+	}
+}
+
+/// A row of the thumbnails of the images around the current one
+pub struct Filmstrip {
+	data: RefCell<FilmstripData>,
+}
+
+impl Filmstrip {
+	pub fn new(thumbnails: Rc<RefCell<ThumbnailCache>>, visible: bool) -> Filmstrip {
+		let placement = WidgetPlacement {
+			width: Length::Stretch { min: 0.0, max: f32::INFINITY },
+			height: Length::Fixed(FILMSTRIP_HEIGHT),
+			horizontal_align: Alignment::Start,
+			vertical_align: Alignment::Start,
+			..Default::default()
+		};
+		Filmstrip {
+			data: RefCell::new(FilmstripData {
+				placement,
+				drawn_bounds: Default::default(),
+				visible,
+				render_validity: Default::default(),
+				thumbnails,
+				drawn_generation: 0,
+				paths: Vec::new(),
+				current: 0,
+				hovered: None,
+				on_click: None,
+			}),
+		}
+	}
+
+	add_common_widget_functions!(data);
+
+	/// Returns true if the strip is showing `path` as the image at `index`
+	pub fn shows(&self, index: usize, path: &Path) -> bool {
+		let borrowed = self.data.borrow();
+		borrowed.current == index && borrowed.paths.get(index).is_some_and(|p| p == path)
+	}
+
+	/// Shows the thumbnails of `paths` around the image at `current`
+	pub fn set_images(&self, paths: Vec<PathBuf>, current: usize) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.current = current.min(paths.len().saturating_sub(1));
+		borrowed.paths = paths;
+		borrowed.hovered = None;
+		borrowed.render_validity.invalidate();
+	}
+
+	/// Called with the index of the image when a thumbnail is clicked
+	pub fn set_on_click<F: Fn(usize) + 'static>(&self, callback: F) {
+		self.data.borrow_mut().on_click = Some(Rc::new(callback));
+	}
+}
+
+impl Widget for Filmstrip {
+	fn before_draw(&self, _window: &Window) -> NextUpdate {
+		let borrowed = self.data.borrow();
+		if !borrowed.visible {
+			return NextUpdate::Latest;
+		}
+		let mut thumbnails = borrowed.thumbnails.borrow_mut();
+		thumbnails.update();
+		if thumbnails.generation() != borrowed.drawn_generation {
+			borrowed.render_validity.invalidate();
+		}
+		if thumbnails.is_loading() {
+			return NextUpdate::WaitUntil(Instant::now() + Duration::from_millis(50));
+		}
+		NextUpdate::Latest
+	}
+
+	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
+		let mut borrowed = self.data.borrow_mut();
+		if !borrowed.visible {
+			return Ok(NextUpdate::Latest);
+		}
+		let bounds = borrowed.drawn_bounds;
+		context.clear_color(target, [0.1, 0.1, 0.1, 1.0], Some(bounds));
+		let data = &*borrowed;
+		let mut thumbnails = data.thumbnails.borrow_mut();
+		for index in data.shown_range() {
+			let cell = data.cell_rect(index);
+			let inner = LogicalRect {
+				pos: cell.pos + LogicalVector::new(CELL_PADDING, CELL_PADDING),
+				size: cell.size - LogicalVector::new(2.0 * CELL_PADDING, 2.0 * CELL_PADDING),
+			};
+			if index == data.current {
+				context.clear_color(target, [0.3, 0.55, 0.95, 0.6], Some(inner));
+			} else if data.hovered == Some(index) {
+				context.clear_color(target, [0.5, 0.5, 0.5, 0.3], Some(inner));
+			}
+			let picture = match thumbnails.get(&data.paths[index]) {
+				Some(picture) => picture,
+				None => continue,
+			};
+			let texture = picture.texture(context.display)?;
+			let (w, h) = texture.dimensions();
+			// Fit into the cell, leaving room for the highlight
+			let max_size = inner.size.vec.x - 2.0 * CELL_PADDING;
+			let scale = (max_size / w.max(h).max(1) as f32).min(1.0 / context.dpi_scale_factor);
+			let size = LogicalVector::new(w as f32 * scale, h as f32 * scale);
+			let pos = inner.pos + (inner.size - size) * 0.5;
+			context.draw_texture(target, &texture, LogicalRect { pos, size }, Some(bounds));
+		}
+		let generation = thumbnails.generation();
+		drop(thumbnails);
+		borrowed.drawn_generation = generation;
+		Ok(NextUpdate::Latest)
+	}
+
+	fn layout(&self, available_space: LogicalRect) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.default_layout(available_space);
+	}
+
+	fn handle_event(&self, event: &Event) {
+		if !self.data.borrow().visible {
+			return;
+		}
+		match event.kind {
+			EventKind::MouseMove => {
+				let mut borrowed = self.data.borrow_mut();
+				let hovered = borrowed.index_at(event.cursor_pos);
+				if hovered != borrowed.hovered {
+					borrowed.hovered = hovered;
+					borrowed.render_validity.invalidate();
+				}
+			}
+			EventKind::MouseButton { state: ElementState::Pressed, button: MouseButton::Left } => {
+				let (index, on_click) = {
+					let borrowed = self.data.borrow();
+					(borrowed.index_at(event.cursor_pos), borrowed.on_click.clone())
+				};
+				if let (Some(index), Some(callback)) = (index, on_click) {
+					callback(index);
+				}
+			}
+			_ => (),
+		}
+	}
+
+	fn children(&self, _children: &mut Vec<Rc<dyn Widget>>) {}
+
+	fn placement(&self) -> WidgetPlacement {
+		self.data.borrow().placement
+	}
+
+	fn visible(&self) -> bool {
+		self.data.borrow().visible
+	}
+
+	fn set_valid_ref(&self, render_validity: RenderValidity) {
+		self.data.borrow_mut().render_validity = render_validity;
+	}
+}
@@ -0,0 +1,427 @@
+use std::{
+	cell::RefCell,
+	path::PathBuf,
+	rc::Rc,
+	time::{Duration, Instant},
+};
+
+use gelatin::{
+	add_common_widget_functions,
+	glium::Frame,
+	misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement},
+	window::{RenderValidity, Window},
+	winit::{
+		event::{ElementState, MouseButton},
+		platform::modifier_supplement::KeyEventExtModifierSupplement,
+	},
+	DrawContext, Event, EventKind, NextUpdate, Widget, WidgetData, WidgetError,
+};
+
+use crate::{
+	image_cache::thumbnails::ThumbnailCache, input_handling::char_to_input_key,
+	utils::virtual_keycode_to_string,
+};
+
+/// The size of a cell of the grid in logical pixels, including the space around the thumbnail
+const CELL_SIZE: f32 = 176.0;
+const CELL_PADDING: f32 = 8.0;
+/// The height of the bar at the bottom that shows the name of the selected file
+const STATUS_HEIGHT: f32 = 24.0;
+/// How far one step of the mouse wheel scrolls in logical pixels
+const SCROLL_STEP: f32 = 60.0;
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+
+struct GalleryData {
+	placement: WidgetPlacement,
+	drawn_bounds: LogicalRect,
+	visible: bool,
+	render_validity: RenderValidity,
+
+	thumbnails: Rc<RefCell<ThumbnailCache>>,
+	/// The value of `ThumbnailCache::generation` at the last draw
+	drawn_generation: u64,
+	paths: Vec<PathBuf>,
+	selected: usize,
+	hovered: Option<usize>,
+	/// The distance between the top of the grid and the top of the widget in logical pixels
+	scroll: f32,
+	/// Scroll to the selected thumbnail on the next draw
+	reveal_selected: bool,
+	/// The time and the index of the last click, used for detecting double clicks
+	last_click: Option<(Instant, usize)>,
+	last_mouse_pos: LogicalVector,
+
+	on_open: Option<Rc<dyn Fn(usize)>>,
+	on_close: Option<Rc<dyn Fn()>>,
+}
+
+impl WidgetData for GalleryData {
+	fn placement(&mut self) -> &mut WidgetPlacement {
+		&mut self.placement
+	}
+	fn drawn_bounds(&mut self) -> &mut LogicalRect {
+		&mut self.drawn_bounds
+	}
+	fn visible(&mut self) -> &mut bool {
+		&mut self.visible
+	}
+}
+
+impl GalleryData {
+	/// The area of the thumbnails, above the status bar
+	fn grid_bounds(&self) -> LogicalRect {
+		let mut bounds = self.drawn_bounds;
+		bounds.size.vec.y = (bounds.size.vec.y - STATUS_HEIGHT).max(0.0);
+		bounds
+	}
+
+	fn columns(&self) -> usize {
+		((self.drawn_bounds.size.vec.x / CELL_SIZE) as usize).max(1)
+	}
+
+	/// The number of rows that fit on the screen
+	fn visible_rows(&self) -> usize {
+		((self.grid_bounds().size.vec.y / CELL_SIZE) as usize).max(1)
+	}
+
+	fn max_scroll(&self) -> f32 {
+		let rows = self.paths.len().div_ceil(self.columns());
+		(rows as f32 * CELL_SIZE - self.grid_bounds().size.vec.y).max(0.0)
+	}
+
+	/// The grid is centered horizontally
+	fn grid_left(&self) -> f32 {
+		let grid_width = self.columns() as f32 * CELL_SIZE;
+		self.drawn_bounds.left() + ((self.drawn_bounds.size.vec.x - grid_width) * 0.5).max(0.0)
+	}
+
+	fn cell_rect(&self, index: usize) -> LogicalRect {
+		let columns = self.columns();
+		let (row, col) = (index / columns, index % columns);
+		LogicalRect {
+			pos: LogicalVector::new(
+				self.grid_left() + col as f32 * CELL_SIZE,
+				self.drawn_bounds.top() + row as f32 * CELL_SIZE - self.scroll,
+			),
+			size: LogicalVector::new(CELL_SIZE, CELL_SIZE),
+		}
+	}
+
+	fn index_at(&self, pos: LogicalVector) -> Option<usize> {
+		if !self.grid_bounds().contains(pos) {
+			return None;
+		}
+		let x = pos.vec.x - self.grid_left();
+		let y = pos.vec.y - self.drawn_bounds.top() + self.scroll;
+		let col = (x / CELL_SIZE).floor();
+		if x < 0.0 || col as usize >= self.columns() {
+			return None;
+		}
+		let index = (y / CELL_SIZE).floor() as usize * self.columns() + col as usize;
+		(index < self.paths.len()).then_some(index)
+	}
+
+	fn set_scroll(&mut self, scroll: f32) {
+		self.scroll = scroll.min(self.max_scroll()).max(0.0);
+		self.render_validity.invalidate();
+	}
+
+	/// Scrolls the least amount that makes the selected thumbnail fully visible
+	fn scroll_to_selected(&mut self) {
+		let row = self.selected / self.columns();
+		let top = row as f32 * CELL_SIZE;
+		let bottom = top + CELL_SIZE;
+		let height = self.grid_bounds().size.vec.y;
+		if top < self.scroll {
+			self.set_scroll(top);
+		} else if bottom > self.scroll + height {
+			self.set_scroll(bottom - height);
+		}
+	}
+
+	fn select(&mut self, index: usize) {
+		if self.paths.is_empty() {
+			return;
+		}
+		self.selected = index.min(self.paths.len() - 1);
+		self.scroll_to_selected();
+		self.render_validity.invalidate();
+	}
+
+	/// Moves the selection by `delta` thumbnails, stopping at the first and the last one
+	fn move_selection(&mut self, delta: isize) {
+		let index = (self.selected as isize + delta).max(0) as usize;
+		self.select(index);
+	}
+}
+
+/// A scrollable grid of the thumbnails of the images in a folder
+pub struct Gallery {
+	data: RefCell<GalleryData>,
+}
+
+impl Gallery {
+	pub fn new(thumbnails: Rc<RefCell<ThumbnailCache>>) -> Gallery {
+		let placement = WidgetPlacement {
+			width: Length::Stretch { min: 0.0, max: f32::INFINITY },
+			height: Length::Stretch { min: 0.0, max: f32::INFINITY },
+			horizontal_align: Alignment::Start,
+			vertical_align: Alignment::Start,
+			..Default::default()
+		};
+		Gallery {
+			data: RefCell::new(GalleryData {
+				placement,
+				drawn_bounds: Default::default(),
+				visible: false,
+				render_validity: Default::default(),
+				thumbnails,
+				drawn_generation: 0,
+				paths: Vec::new(),
+				selected: 0,
+				hovered: None,
+				scroll: 0.0,
+				reveal_selected: false,
+				last_click: None,
+				last_mouse_pos: Default::default(),
+				on_open: None,
+				on_close: None,
+			}),
+		}
+	}
+
+	add_common_widget_functions!(data);
+
+	/// Shows the thumbnails of `paths` with the image at `selected` selected
+	pub fn show(&self, paths: Vec<PathBuf>, selected: usize) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.selected = selected.min(paths.len().saturating_sub(1));
+		borrowed.paths = paths;
+		borrowed.hovered = None;
+		borrowed.last_click = None;
+		borrowed.reveal_selected = true;
+		borrowed.visible = true;
+		borrowed.render_validity.invalidate();
+	}
+
+	/// Called with the index of the image when an image is opened. The gallery hides itself
+	/// before calling it.
+	pub fn set_on_open<F: Fn(usize) + 'static>(&self, callback: F) {
+		self.data.borrow_mut().on_open = Some(Rc::new(callback));
+	}
+
+	/// Called when the gallery is closed without opening an image
+	pub fn set_on_close<F: Fn() + 'static>(&self, callback: F) {
+		self.data.borrow_mut().on_close = Some(Rc::new(callback));
+	}
+
+	fn open(&self, index: usize) {
+		let on_open = {
+			let mut borrowed = self.data.borrow_mut();
+			borrowed.visible = false;
+			borrowed.render_validity.invalidate();
+			borrowed.on_open.clone()
+		};
+		if let Some(callback) = on_open {
+			callback(index);
+		}
+	}
+
+	fn close(&self) {
+		let on_close = {
+			let mut borrowed = self.data.borrow_mut();
+			borrowed.visible = false;
+			borrowed.render_validity.invalidate();
+			borrowed.on_close.clone()
+		};
+		if let Some(callback) = on_close {
+			callback();
+		}
+	}
+
+	fn handle_key_input(&self, input_key: &str) {
+		let mut borrowed = self.data.borrow_mut();
+		let columns = borrowed.columns() as isize;
+		let page = columns * borrowed.visible_rows() as isize;
+		match input_key {
+			"left" => borrowed.move_selection(-1),
+			"right" => borrowed.move_selection(1),
+			"up" => borrowed.move_selection(-columns),
+			"down" => borrowed.move_selection(columns),
+			"pageup" => borrowed.move_selection(-page),
+			"pagedown" => borrowed.move_selection(page),
+			"home" => borrowed.select(0),
+			"end" => {
+				let last = borrowed.paths.len().saturating_sub(1);
+				borrowed.select(last);
+			}
+			"return" if !borrowed.paths.is_empty() => {
+				let selected = borrowed.selected;
+				drop(borrowed);
+				self.open(selected);
+			}
+			"escape" => {
+				drop(borrowed);
+				self.close();
+			}
+			_ => (),
+		}
+	}
+
+	fn draw_status(&self, target: &mut Frame, context: &DrawContext, data: &GalleryData) {
+		const LABEL_FONT_SIZE: f32 = 13.0;
+		const PADDING: f32 = 4.0;
+		let path = match data.paths.get(data.selected) {
+			Some(path) => path,
+			None => return,
+		};
+		let bounds = data.drawn_bounds;
+		let status = LogicalRect {
+			pos: LogicalVector::new(bounds.left(), bounds.bottom() - STATUS_HEIGHT),
+			size: LogicalVector::new(bounds.size.vec.x, STATUS_HEIGHT),
+		};
+		context.clear_color(target, [0.0, 0.0, 0.0, 0.6], Some(status));
+		let name = path.file_name().unwrap_or_default().to_string_lossy();
+		let text = format!("{}   ·   {} / {}", name, data.selected + 1, data.paths.len());
+		let text_h = context.line_height(LABEL_FONT_SIZE);
+		let pos = status.pos + LogicalVector::new(PADDING, (STATUS_HEIGHT - text_h) * 0.5);
+		context.draw_text(target, &text, LABEL_FONT_SIZE, pos, [1.0, 1.0, 1.0, 0.9], Some(status));
+	}
+}
+
+impl Widget for Gallery {
+	fn before_draw(&self, _window: &Window) -> NextUpdate {
+		let borrowed = self.data.borrow();
+		if !borrowed.visible {
+			return NextUpdate::Latest;
+		}
+		let mut thumbnails = borrowed.thumbnails.borrow_mut();
+		thumbnails.update();
+		// Thumbnails also arrive from the image cache of the picture
+		if thumbnails.generation() != borrowed.drawn_generation {
+			borrowed.render_validity.invalidate();
+		}
+		if thumbnails.is_loading() {
+			return NextUpdate::WaitUntil(Instant::now() + Duration::from_millis(50));
+		}
+		NextUpdate::Latest
+	}
+
+	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
+		let mut borrowed = self.data.borrow_mut();
+		if !borrowed.visible {
+			return Ok(NextUpdate::Latest);
+		}
+		if borrowed.reveal_selected {
+			borrowed.reveal_selected = false;
+			borrowed.scroll_to_selected();
+		}
+		// The size of the widget may have changed since the last scroll
+		let scroll = borrowed.scroll;
+		borrowed.set_scroll(scroll);
+		let data = &*borrowed;
+		let grid = data.grid_bounds();
+		let columns = data.columns();
+		let first = (data.scroll / CELL_SIZE) as usize * columns;
+		let last = ((data.scroll + grid.size.vec.y) / CELL_SIZE).ceil() as usize * columns;
+		let mut thumbnails = data.thumbnails.borrow_mut();
+		for index in first..last.min(data.paths.len()) {
+			let cell = data.cell_rect(index);
+			let inner = LogicalRect {
+				pos: cell.pos + LogicalVector::new(CELL_PADDING, CELL_PADDING),
+				size: cell.size - LogicalVector::new(2.0 * CELL_PADDING, 2.0 * CELL_PADDING),
+			};
+			if index == data.selected {
+				context.clear_color(target, [0.3, 0.55, 0.95, 0.6], Some(inner));
+			} else if data.hovered == Some(index) {
+				context.clear_color(target, [0.5, 0.5, 0.5, 0.3], Some(inner));
+			}
+			let picture = match thumbnails.get(&data.paths[index]) {
+				Some(picture) => picture,
+				None => continue,
+			};
+			let texture = picture.texture(context.display)?;
+			let (w, h) = texture.dimensions();
+			// Fit into the cell, leaving room for the selection frame
+			let max_size = inner.size.vec.x - 2.0 * CELL_PADDING;
+			let scale = (max_size / w.max(h).max(1) as f32).min(1.0 / context.dpi_scale_factor);
+			let size = LogicalVector::new(w as f32 * scale, h as f32 * scale);
+			let pos = inner.pos + (inner.size - size) * 0.5;
+			context.draw_texture(target, &texture, LogicalRect { pos, size }, Some(grid));
+		}
+		let generation = thumbnails.generation();
+		drop(thumbnails);
+		self.draw_status(target, context, data);
+		borrowed.drawn_generation = generation;
+		Ok(NextUpdate::Latest)
+	}
+
+	fn layout(&self, available_space: LogicalRect) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.default_layout(available_space);
+	}
+
+	fn handle_event(&self, event: &Event) {
+		if !self.data.borrow().visible {
+			return;
+		}
+		match event.kind {
+			EventKind::MouseMove => {
+				let mut borrowed = self.data.borrow_mut();
+				borrowed.last_mouse_pos = event.cursor_pos;
+				let hovered = borrowed.index_at(event.cursor_pos);
+				if hovered != borrowed.hovered {
+					borrowed.hovered = hovered;
+					borrowed.render_validity.invalidate();
+				}
+			}
+			EventKind::MouseButton { state: ElementState::Pressed, button: MouseButton::Left } => {
+				let mut borrowed = self.data.borrow_mut();
+				let index = match borrowed.index_at(event.cursor_pos) {
+					Some(index) => index,
+					None => return,
+				};
+				let now = Instant::now();
+				let double_click = borrowed.last_click.is_some_and(|(time, prev_index)| {
+					prev_index == index && now.duration_since(time) < DOUBLE_CLICK_TIME
+				});
+				borrowed.last_click = Some((now, index));
+				borrowed.select(index);
+				if double_click {
+					drop(borrowed);
+					self.open(index);
+				}
+			}
+			EventKind::MouseScroll { delta } => {
+				let mut borrowed = self.data.borrow_mut();
+				let scroll = borrowed.scroll - delta.vec.y * SCROLL_STEP;
+				borrowed.set_scroll(scroll);
+				let hovered = borrowed.index_at(borrowed.last_mouse_pos);
+				borrowed.hovered = hovered;
+			}
+			EventKind::KeyInput { ref input } => {
+				if input.state != ElementState::Pressed {
+					return;
+				}
+				let key = input.key_without_modifiers();
+				let input_key_str = virtual_keycode_to_string(&key).to_lowercase();
+				self.handle_key_input(&char_to_input_key(&input_key_str));
+			}
+			_ => (),
+		}
+	}
+
+	fn children(&self, _children: &mut Vec<Rc<dyn Widget>>) {}
+
+	fn placement(&self) -> WidgetPlacement {
+		self.data.borrow().placement
+	}
+
+	fn visible(&self) -> bool {
+		self.data.borrow().visible
+	}
+
+	fn set_valid_ref(&self, render_validity: RenderValidity) {
+		self.data.borrow_mut().render_validity = render_validity;
+	}
+}
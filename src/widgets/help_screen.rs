@@ -1,193 +1,381 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-
-use gelatin::cgmath::{Matrix4, Vector3};
-
-use gelatin::glium::{uniform, Frame, Surface};
-
-use gelatin::add_common_widget_functions;
-use gelatin::misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement};
-use gelatin::picture::Picture;
-use gelatin::window::RenderValidity;
-use gelatin::NextUpdate;
-use gelatin::{DrawContext, Event, Widget, WidgetData, WidgetError};
-
-struct HelpScreenData {
-	placement: WidgetPlacement,
-	drawn_bounds: LogicalRect,
-	visible: bool,
-	render_validity: RenderValidity,
-	initiaizlied: bool,
-	parent_space: LogicalRect,
-	usage_image: Picture,
-}
-
-impl WidgetData for HelpScreenData {
-	fn placement(&mut self) -> &mut WidgetPlacement {
-		&mut self.placement
-	}
-	fn drawn_bounds(&mut self) -> &mut LogicalRect {
-		&mut self.drawn_bounds
-	}
-	fn visible(&mut self) -> &mut bool {
-		&mut self.visible
-	}
-}
-
-pub struct HelpScreen {
-	data: RefCell<HelpScreenData>,
-}
-
-impl HelpScreen {
-	pub fn new(usage_img: Picture) -> HelpScreen {
-		let placement = WidgetPlacement {
-			width: Length::Fixed(0.0),
-			height: Length::Fixed(0.0),
-			horizontal_align: Alignment::Center,
-			vertical_align: Alignment::Center,
-			ignore_layout: true,
-			..Default::default()
-		};
-		HelpScreen {
-			data: RefCell::new(HelpScreenData {
-				placement,
-				drawn_bounds: Default::default(),
-				visible: false,
-				render_validity: Default::default(),
-				initiaizlied: false,
-				parent_space: LogicalRect::default(),
-				usage_image: usage_img,
-			}),
-		}
-	}
-
-	add_common_widget_functions!(data);
-}
-
-impl Widget for HelpScreen {
-	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
-		use gelatin::glium::{Blend, BlendingFunction, LinearBlendingFactor};
-		{
-			let borrowed = self.data.borrow();
-			if !borrowed.visible {
-				return Ok(NextUpdate::Latest);
-			}
-
-			let w = borrowed.parent_space.size.vec.x;
-			let h = borrowed.parent_space.size.vec.y;
-			let pos = borrowed.parent_space.pos.vec;
-			// Model tranform
-			let transform = Matrix4::from_nonuniform_scale(w, h, 1.0);
-			let transform = Matrix4::from_translation(pos.extend(0.0)) * transform;
-			// Projection
-			let transform = context.projection_transform * transform;
-			let image_draw_params = gelatin::glium::DrawParameters {
-				viewport: Some(*context.viewport),
-				blend: Blend {
-					color: BlendingFunction::Addition {
-						source: LinearBlendingFactor::SourceAlpha,
-						destination: LinearBlendingFactor::OneMinusSourceAlpha,
-					},
-					..Default::default()
-				},
-				..Default::default()
-			};
-			let uniforms = uniform! {
-				matrix: Into::<[[f32; 4]; 4]>::into(transform),
-				color: [0.0f32, 0.0, 0.0, 0.5],
-			};
-
-			target
-				.draw(
-					context.unit_quad_vertices,
-					context.unit_quad_indices,
-					context.colored_program,
-					&uniforms,
-					&image_draw_params,
-				)
-				.unwrap();
-
-			///////////////////////////////////////////////////////////////////////////
-			// Draw Help Image
-			//////////////////////////////////////////////////////////////////////////
-			let aligned_bounds = borrowed.drawn_bounds.align_to_pixels(context.dpi_scale_factor);
-			let img_w = aligned_bounds.size.vec.x;
-			let img_h = aligned_bounds.size.vec.y;
-			let pos = aligned_bounds.pos.vec;
-			// Model tranform
-			let transform = Matrix4::from_nonuniform_scale(img_w, img_h, 1.0);
-			let transform = Matrix4::from_translation(pos.extend(0.0)) * transform;
-			// Projection
-			let transform = context.projection_transform * transform;
-
-			let texture_size = [img_w, img_h];
-			let texture = borrowed.usage_image.texture(context.display)?;
-			let sampler = texture
-				.sampled()
-				.wrap_function(gelatin::glium::uniforms::SamplerWrapFunction::Clamp)
-				.minify_filter(gelatin::glium::uniforms::MinifySamplerFilter::Linear)
-				.magnify_filter(gelatin::glium::uniforms::MagnifySamplerFilter::Linear);
-			let uniforms = uniform! {
-				matrix: Into::<[[f32; 4]; 4]>::into(transform),
-				tex: sampler,
-				color: [1.0f32, 0.1, 0.5, 0.5],
-				texture_size: texture_size,
-				//brighten: if self.hover { 0.15f32 } else { 0.0f32 },
-				brighten: 0.0f32,
-				shadow_color: Into::<[f32; 3]>::into(Vector3::<f32>::new(0.0, 0.0, 0.0)),
-				shadow_offset: 1.0f32
-			};
-			target
-				.draw(
-					context.unit_quad_vertices,
-					context.unit_quad_indices,
-					context.textured_program,
-					&uniforms,
-					&image_draw_params,
-				)
-				.unwrap();
-			//////////////////////////////////////////////////////////////////////////
-
-			//let uniforms
-		}
-		Ok(NextUpdate::Latest)
-	}
-
-	fn layout(&self, available_space: LogicalRect) {
-		let mut borrowed = self.data.borrow_mut();
-		if !borrowed.visible {
-			return;
-		}
-		if !borrowed.initiaizlied {
-			borrowed.initiaizlied = true;
-			let img_data = borrowed.usage_image.get_metadata().unwrap();
-			borrowed.placement = WidgetPlacement {
-				width: Length::Fixed(img_data.width as f32 * 0.5),
-				height: Length::Fixed(img_data.height as f32 * 0.5),
-				horizontal_align: Alignment::Center,
-				vertical_align: Alignment::Center,
-				ignore_layout: true,
-				..Default::default()
-			};
-		}
-		borrowed.default_layout(available_space);
-		borrowed.parent_space = available_space;
-	}
-
-	fn handle_event(&self, _event: &Event) {}
-
-	// No children for a button
-	fn children(&self, _children: &mut Vec<Rc<dyn Widget>>) {}
-
-	fn placement(&self) -> WidgetPlacement {
-		self.data.borrow().placement
-	}
-
-	fn visible(&self) -> bool {
-		self.data.borrow().visible
-	}
-
-	fn set_valid_ref(&self, render_validity: RenderValidity) {
-		self.data.borrow_mut().render_validity = render_validity;
-	}
-}
+use std::{cell::RefCell, rc::Rc};
+
+use gelatin::{
+	add_common_widget_functions,
+	glium::Frame,
+	misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement},
+	window::RenderValidity,
+	winit::{
+		event::ElementState,
+		keyboard::{Key, NamedKey},
+	},
+	DrawContext, Event, EventKind, NextUpdate, Widget, WidgetData, WidgetError,
+};
+
+use crate::input_handling::{display_key, HelpEntry};
+
+const FONT_SIZE: f32 = 14.0;
+const HEADING_FONT_SIZE: f32 = 16.0;
+/// The panel is centered and never wider than this
+const PANEL_MAX_WIDTH: f32 = 720.0;
+const MARGIN: f32 = 24.0;
+const PADDING: f32 = 16.0;
+const ROW_HEIGHT: f32 = 22.0;
+const HEADING_HEIGHT: f32 = 36.0;
+/// The tips and the filter above the list
+const HEADER_HEIGHT: f32 = 2.0 * ROW_HEIGHT + PADDING;
+const SCROLL_STEP: f32 = 60.0;
+const SCROLLBAR_WIDTH: f32 = 4.0;
+
+const TEXT_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const DIM_TEXT_COLOR: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+const HEADING_COLOR: [f32; 4] = [0.55, 0.75, 1.0, 1.0];
+
+static TOGGLE_TIP: &str = "Click the question mark at the bottom to toggle this screen.";
+static FILTER_TIP: &str =
+	"Type to filter the list. Escape clears the filter, then closes this screen.";
+
+enum Row<'a> {
+	Heading(&'a str),
+	Entry(&'a HelpEntry),
+}
+
+impl Row<'_> {
+	fn height(&self) -> f32 {
+		match self {
+			Row::Heading(_) => HEADING_HEIGHT,
+			Row::Entry(_) => ROW_HEIGHT,
+		}
+	}
+}
+
+struct HelpScreenData {
+	placement: WidgetPlacement,
+	drawn_bounds: LogicalRect,
+	visible: bool,
+	render_validity: RenderValidity,
+
+	entries: Vec<HelpEntry>,
+	filter: String,
+	/// How far the list is scrolled down in logical pixels
+	scroll: f32,
+
+	on_visibility_change: Option<Rc<dyn Fn(bool)>>,
+}
+
+impl WidgetData for HelpScreenData {
+	fn placement(&mut self) -> &mut WidgetPlacement {
+		&mut self.placement
+	}
+	fn drawn_bounds(&mut self) -> &mut LogicalRect {
+		&mut self.drawn_bounds
+	}
+	fn visible(&mut self) -> &mut bool {
+		&mut self.visible
+	}
+}
+
+impl HelpScreenData {
+	/// The entries that match every word of the filter, each category under a heading
+	fn rows(&self) -> Vec<Row<'_>> {
+		let words = self.filter.to_lowercase();
+		let words = words.split_whitespace().collect::<Vec<_>>();
+		let mut rows = Vec::new();
+		let mut category = None;
+		for entry in &self.entries {
+			let text = format!("{} {} {}", entry.category, entry.description, entry.keys.join(" "))
+				.to_lowercase();
+			if !words.iter().all(|word| text.contains(word)) {
+				continue;
+			}
+			if category != Some(entry.category.as_str()) {
+				category = Some(entry.category.as_str());
+				rows.push(Row::Heading(&entry.category));
+			}
+			rows.push(Row::Entry(entry));
+		}
+		rows
+	}
+
+	fn panel_rect(&self) -> LogicalRect {
+		let bounds = self.drawn_bounds;
+		let width = (bounds.size.vec.x - 2.0 * MARGIN).clamp(0.0, PANEL_MAX_WIDTH);
+		let height = (bounds.size.vec.y - 2.0 * MARGIN).max(0.0);
+		LogicalRect {
+			pos: LogicalVector::new(
+				bounds.left() + (bounds.size.vec.x - width) * 0.5,
+				bounds.top() + MARGIN,
+			),
+			size: LogicalVector::new(width, height),
+		}
+	}
+
+	/// The part of the panel where the rows are drawn
+	fn list_rect(&self) -> LogicalRect {
+		let panel = self.panel_rect();
+		LogicalRect {
+			pos: panel.pos + LogicalVector::new(PADDING, PADDING + HEADER_HEIGHT),
+			size: LogicalVector::new(
+				(panel.size.vec.x - 2.0 * PADDING).max(0.0),
+				(panel.size.vec.y - 2.0 * PADDING - HEADER_HEIGHT).max(0.0),
+			),
+		}
+	}
+
+	fn max_scroll(&self) -> f32 {
+		let content_height: f32 = self.rows().iter().map(Row::height).sum();
+		(content_height - self.list_rect().size.vec.y).max(0.0)
+	}
+
+	fn set_scroll(&mut self, scroll: f32) {
+		self.scroll = scroll.min(self.max_scroll()).max(0.0);
+		self.render_validity.invalidate();
+	}
+
+	fn set_filter(&mut self, filter: String) {
+		self.filter = filter;
+		self.scroll = 0.0;
+		self.render_validity.invalidate();
+	}
+}
+
+/// Lists the key bindings, the custom commands and the mouse gestures by category. The list
+/// can be scrolled and filtered by typing while it's visible.
+pub struct HelpScreen {
+	data: RefCell<HelpScreenData>,
+}
+
+impl HelpScreen {
+	pub fn new() -> HelpScreen {
+		let placement = WidgetPlacement {
+			width: Length::Stretch { min: 0.0, max: f32::INFINITY },
+			height: Length::Stretch { min: 0.0, max: f32::INFINITY },
+			horizontal_align: Alignment::Center,
+			vertical_align: Alignment::Center,
+			ignore_layout: true,
+			..Default::default()
+		};
+		HelpScreen {
+			data: RefCell::new(HelpScreenData {
+				placement,
+				drawn_bounds: Default::default(),
+				visible: false,
+				render_validity: Default::default(),
+				entries: Vec::new(),
+				filter: String::new(),
+				scroll: 0.0,
+				on_visibility_change: None,
+			}),
+		}
+	}
+
+	add_common_widget_functions!(data);
+
+	pub fn set_entries(&self, entries: Vec<HelpEntry>) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.entries = entries;
+		let scroll = borrowed.scroll;
+		borrowed.set_scroll(scroll);
+	}
+
+	/// Called with the new visibility when the screen is opened or closed by `toggle` or by
+	/// pressing Escape
+	pub fn set_on_visibility_change<F: Fn(bool) + 'static>(&self, callback: F) {
+		self.data.borrow_mut().on_visibility_change = Some(Rc::new(callback));
+	}
+
+	/// Shows the screen with an empty filter or hides it
+	pub fn toggle(&self) {
+		let callback = {
+			let mut borrowed = self.data.borrow_mut();
+			borrowed.visible = !borrowed.visible;
+			borrowed.set_filter(String::new());
+			borrowed.on_visibility_change.clone().map(|callback| (callback, borrowed.visible))
+		};
+		if let Some((callback, visible)) = callback {
+			callback(visible);
+		}
+	}
+
+	fn handle_key_input(&self, event: &Event) {
+		let input = match &event.kind {
+			EventKind::KeyInput { input } if input.state == ElementState::Pressed => input,
+			_ => return,
+		};
+		let mut borrowed = self.data.borrow_mut();
+		let page = borrowed.list_rect().size.vec.y - ROW_HEIGHT;
+		let scroll = borrowed.scroll;
+		match &input.logical_key {
+			Key::Named(NamedKey::Escape) if borrowed.filter.is_empty() => {
+				drop(borrowed);
+				self.toggle();
+			}
+			Key::Named(NamedKey::Escape) => borrowed.set_filter(String::new()),
+			Key::Named(NamedKey::Backspace) => {
+				let mut filter = borrowed.filter.clone();
+				filter.pop();
+				borrowed.set_filter(filter);
+			}
+			Key::Named(NamedKey::ArrowUp) => borrowed.set_scroll(scroll - ROW_HEIGHT),
+			Key::Named(NamedKey::ArrowDown) => borrowed.set_scroll(scroll + ROW_HEIGHT),
+			Key::Named(NamedKey::PageUp) => borrowed.set_scroll(scroll - page),
+			Key::Named(NamedKey::PageDown) => borrowed.set_scroll(scroll + page),
+			Key::Named(NamedKey::Home) => borrowed.set_scroll(0.0),
+			Key::Named(NamedKey::End) => borrowed.set_scroll(f32::INFINITY),
+			_ => {
+				let shortcut = event.modifiers.control_key() || event.modifiers.super_key();
+				if let Some(typed) = input.text.as_ref().filter(|_| !shortcut) {
+					let typed = typed.chars().filter(|ch| !ch.is_control());
+					let filter = borrowed.filter.chars().chain(typed).collect();
+					borrowed.set_filter(filter);
+				}
+			}
+		}
+	}
+
+	fn draw_header(&self, target: &mut Frame, context: &DrawContext, data: &HelpScreenData) {
+		let panel = data.panel_rect();
+		let text_h = context.line_height(FONT_SIZE);
+		let mut pos = panel.pos + LogicalVector::new(PADDING, PADDING);
+		context.draw_text(target, TOGGLE_TIP, FONT_SIZE, pos, DIM_TEXT_COLOR, Some(panel));
+		pos.vec.y += ROW_HEIGHT;
+		if data.filter.is_empty() {
+			context.draw_text(target, FILTER_TIP, FONT_SIZE, pos, DIM_TEXT_COLOR, Some(panel));
+		} else {
+			let text = format!("Filter: {}", data.filter);
+			context.draw_text(target, &text, FONT_SIZE, pos, TEXT_COLOR, Some(panel));
+			let cursor = LogicalRect {
+				pos: pos + LogicalVector::new(context.text_width(&text, FONT_SIZE) + 1.0, 0.0),
+				size: LogicalVector::new(1.5, text_h),
+			};
+			context.clear_color(target, TEXT_COLOR, Some(cursor));
+		}
+		let separator = LogicalRect {
+			pos: LogicalVector::new(panel.left() + PADDING, pos.vec.y + ROW_HEIGHT + PADDING * 0.5),
+			size: LogicalVector::new((panel.size.vec.x - 2.0 * PADDING).max(0.0), 1.0),
+		};
+		context.clear_color(target, [1.0, 1.0, 1.0, 0.2], Some(separator));
+	}
+
+	fn draw_rows(&self, target: &mut Frame, context: &DrawContext, data: &HelpScreenData) {
+		let list = data.list_rect();
+		let rows = data.rows();
+		if rows.is_empty() {
+			let text = "Nothing matches the filter";
+			context.draw_text(target, text, FONT_SIZE, list.pos, DIM_TEXT_COLOR, Some(list));
+			return;
+		}
+		let text_h = context.line_height(FONT_SIZE);
+		let heading_h = context.line_height(HEADING_FONT_SIZE);
+		// The keys are aligned in a column
+		let keys_x = list.left() + (list.size.vec.x * 0.55).round();
+		let mut top = list.top() - data.scroll;
+		for row in &rows {
+			let height = row.height();
+			if top + height < list.top() {
+				top += height;
+				continue;
+			}
+			if top > list.bottom() {
+				break;
+			}
+			match row {
+				Row::Heading(category) => {
+					let pos = LogicalVector::new(list.left(), top + height - heading_h - 4.0);
+					let color = HEADING_COLOR;
+					context.draw_text(target, category, HEADING_FONT_SIZE, pos, color, Some(list));
+				}
+				Row::Entry(entry) => {
+					let y = top + (height - text_h) * 0.5;
+					let description_clip = LogicalRect {
+						pos: list.pos,
+						size: LogicalVector::new(keys_x - list.left() - PADDING, list.size.vec.y),
+					};
+					let pos = LogicalVector::new(list.left(), y);
+					let description = &entry.description;
+					let clip = Some(description_clip);
+					context.draw_text(target, description, FONT_SIZE, pos, TEXT_COLOR, clip);
+					let pos = LogicalVector::new(keys_x, y);
+					if entry.keys.is_empty() {
+						let text = "not bound";
+						context.draw_text(target, text, FONT_SIZE, pos, DIM_TEXT_COLOR, Some(list));
+					} else {
+						let keys = entry.keys.iter().map(|k| display_key(k)).collect::<Vec<_>>();
+						let text = keys.join("  or  ");
+						context.draw_text(target, &text, FONT_SIZE, pos, TEXT_COLOR, Some(list));
+					}
+				}
+			}
+			top += height;
+		}
+		let content_height: f32 = rows.iter().map(Row::height).sum();
+		if content_height > list.size.vec.y {
+			let track_h = list.size.vec.y;
+			let thumb_h = (track_h * track_h / content_height).max(ROW_HEIGHT);
+			let max_scroll = content_height - track_h;
+			let thumb_y = list.top() + data.scroll / max_scroll * (track_h - thumb_h);
+			let thumb = LogicalRect {
+				pos: LogicalVector::new(list.right() + (PADDING - SCROLLBAR_WIDTH) * 0.5, thumb_y),
+				size: LogicalVector::new(SCROLLBAR_WIDTH, thumb_h),
+			};
+			context.clear_color(target, [1.0, 1.0, 1.0, 0.3], Some(thumb));
+		}
+	}
+}
+
+impl Default for HelpScreen {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Widget for HelpScreen {
+	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
+		let borrowed = self.data.borrow();
+		if !borrowed.visible {
+			return Ok(NextUpdate::Latest);
+		}
+		context.clear_color(target, [0.0, 0.0, 0.0, 0.5], Some(borrowed.drawn_bounds));
+		context.clear_color(target, [0.08, 0.08, 0.08, 0.94], Some(borrowed.panel_rect()));
+		self.draw_header(target, context, &borrowed);
+		self.draw_rows(target, context, &borrowed);
+		Ok(NextUpdate::Latest)
+	}
+
+	fn layout(&self, available_space: LogicalRect) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.default_layout(available_space);
+		// The list may have become taller than the content
+		let scroll = borrowed.scroll;
+		borrowed.scroll = scroll.min(borrowed.max_scroll());
+	}
+
+	fn handle_event(&self, event: &Event) {
+		if !self.data.borrow().visible {
+			return;
+		}
+		match event.kind {
+			EventKind::MouseScroll { delta } => {
+				let mut borrowed = self.data.borrow_mut();
+				let scroll = borrowed.scroll - delta.vec.y * SCROLL_STEP;
+				borrowed.set_scroll(scroll);
+			}
+			EventKind::KeyInput { .. } => self.handle_key_input(event),
+			_ => (),
+		}
+	}
+
+	fn children(&self, _children: &mut Vec<Rc<dyn Widget>>) {}
+
+	fn placement(&self) -> WidgetPlacement {
+		self.data.borrow().placement
+	}
+
+	fn visible(&self) -> bool {
+		self.data.borrow().visible
+	}
+
+	fn set_valid_ref(&self, render_validity: RenderValidity) {
+		self.data.borrow_mut().render_validity = render_validity;
+	}
+}
@@ -0,0 +1,131 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use gelatin::glium::{texture::Texture2d, Frame};
+use gelatin::misc::{LogicalRect, LogicalVector};
+use gelatin::text::mask_texture;
+use gelatin::DrawContext;
+
+use crate::histogram::{self, Histogram};
+
+use super::file_panel::FilePanel;
+use super::overlay::{self, intersect};
+
+/// The size of the histogram graph in logical pixels, one column per channel value
+const HISTOGRAM_SIZE: (f32, f32) = (256.0, 100.0);
+const CHANNEL_COLORS: [[f32; 4]; 4] =
+	[[1.0, 0.2, 0.2, 0.6], [0.2, 1.0, 0.2, 0.6], [0.3, 0.4, 1.0, 0.6], [1.0, 1.0, 1.0, 0.5]];
+
+/// The histogram of the shown image and the statistics of its channels
+pub struct HistogramPanel {
+	panel: FilePanel<Histogram>,
+	/// The graphs of the channels of the histogram, the image they belong to and their
+	/// height in physical pixels
+	masks: RefCell<Option<(PathBuf, u32, Vec<Texture2d>)>>,
+}
+
+impl Default for HistogramPanel {
+	fn default() -> Self {
+		HistogramPanel {
+			panel: FilePanel::new(histogram::histogram_worker),
+			masks: Default::default(),
+		}
+	}
+}
+
+impl HistogramPanel {
+	/// Shows the histogram of the image at `path`. `loaded` is the histogram that the loader
+	/// computed for the image it decoded last, it's used if it belongs to `path`. `loading` is
+	/// true while the loader is still decoding the image. Returns true if the histogram
+	/// changed.
+	pub fn update(
+		&mut self,
+		path: Option<PathBuf>,
+		loaded: Option<(&Path, &Histogram)>,
+		loading: bool,
+	) -> bool {
+		// The loader computes the histograms of the images it decodes while the panel is open,
+		// only the images that were loaded before that are decoded again
+		match loaded {
+			Some((loaded_path, histogram)) if path.as_deref() == Some(loaded_path) => {
+				self.panel.set_result(loaded_path, histogram)
+			}
+			_ if loading => false,
+			_ => self.panel.update(path),
+		}
+	}
+
+	pub fn pending(&self) -> bool {
+		self.panel.pending()
+	}
+
+	/// Draws the graph and the statistics in the bottom right corner of `pane`
+	pub fn draw(&self, target: &mut Frame, context: &DrawContext, pane: LogicalRect) {
+		const PADDING: f32 = overlay::PADDING;
+		let result = self.panel.result();
+		let lines = match result {
+			Some((_, Ok(histogram))) => histogram_lines(histogram),
+			Some((_, Err(e))) => vec![format!("can not compute the histogram: {}", e)],
+			None if self.panel.pending() => vec!["computing the histogram…".to_owned()],
+			None => return,
+		};
+		let histogram = match result {
+			Some((path, Ok(histogram))) => Some((path, histogram)),
+			_ => None,
+		};
+		let mask_height = (HISTOGRAM_SIZE.1 * context.dpi_scale_factor).round().max(1.0) as u32;
+		let mut masks = self.masks.borrow_mut();
+		if let Some((path, histogram)) = histogram {
+			let outdated = masks.as_ref().is_none_or(|(p, h, _)| p != path || *h != mask_height);
+			if outdated {
+				let textures = (0..CHANNEL_COLORS.len())
+					.map(|i| mask_texture(context.display, histogram.channel_mask(i, mask_height)))
+					.collect();
+				*masks = Some((path.to_owned(), mask_height, textures));
+			}
+		}
+		let graph_height = if histogram.is_some() { HISTOGRAM_SIZE.1 + PADDING } else { 0.0 };
+		let text_size = overlay::lines_size(context, &lines);
+		let panel_size = LogicalVector::new(
+			text_size.vec.x.max(HISTOGRAM_SIZE.0) + 2.0 * PADDING,
+			graph_height + text_size.vec.y + 2.0 * PADDING,
+		);
+		let pos = LogicalVector::new(
+			(pane.right() - panel_size.vec.x).max(pane.left()),
+			(pane.bottom() - panel_size.vec.y).max(pane.top()),
+		);
+		let clip = match intersect(LogicalRect { pos, size: panel_size }, pane) {
+			Some(clip) => clip,
+			None => return,
+		};
+		context.clear_color(target, overlay::PANEL_COLOR, Some(clip));
+		let mut text_pos = pos + LogicalVector::new(PADDING, PADDING);
+		if let (Some(_), Some((_, _, textures))) = (histogram, masks.as_ref()) {
+			let graph = LogicalRect {
+				pos: text_pos,
+				size: LogicalVector::new(HISTOGRAM_SIZE.0, HISTOGRAM_SIZE.1),
+			};
+			for (texture, color) in textures.iter().zip(CHANNEL_COLORS) {
+				context.draw_mask(target, texture, graph, color, Some(clip));
+			}
+			text_pos.vec.y += graph_height;
+		}
+		overlay::draw_lines(target, context, &lines, text_pos, clip);
+	}
+}
+
+/// The statistics of the histogram as lines of text
+fn histogram_lines(histogram: &Histogram) -> Vec<String> {
+	let mut lines: Vec<String> = histogram::CHANNEL_NAMES
+		.iter()
+		.zip(histogram.stats.iter())
+		.map(|(name, stats)| {
+			format!("{}   min {}   max {}   mean {:.1}", name, stats.min, stats.max, stats.mean)
+		})
+		.collect();
+	lines.push(format!(
+		"clipped   shadows {:.2}%   highlights {:.2}%",
+		histogram.clipped_shadows, histogram.clipped_highlights
+	));
+	lines
+}
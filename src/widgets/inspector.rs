@@ -0,0 +1,135 @@
+//! The coordinates and the color of the pixel under the cursor
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use gelatin::glium::Frame;
+use gelatin::misc::{LogicalRect, LogicalVector};
+use gelatin::DrawContext;
+
+use crate::image_cache::{image_loader::Orientation, AnimationFrameTexture, TextureGridItem};
+
+use super::overlay;
+
+/// A pixel of a texture and its color
+struct InspectedPixel {
+	/// Keeps the allocation alive so that it can't be mistaken for the grid of another texture
+	tex_grid: Weak<Vec<TextureGridItem>>,
+	orientation: Orientation,
+	pos: (u32, u32),
+	color: [u8; 4],
+}
+
+impl InspectedPixel {
+	fn matches(&self, texture: &AnimationFrameTexture, pos: (u32, u32)) -> bool {
+		self.pos == pos
+			&& self.orientation == texture.orientation
+			&& Weak::ptr_eq(&self.tex_grid, &Rc::downgrade(&texture.tex_grid))
+	}
+}
+
+#[derive(Default)]
+pub struct Inspector {
+	enabled: bool,
+	/// The last pixel read back from the GPU, so that it's not read again on every redraw
+	last_pixel: RefCell<Option<InspectedPixel>>,
+}
+
+impl Inspector {
+	pub fn enabled(&self) -> bool {
+		self.enabled
+	}
+
+	pub fn toggle(&mut self) {
+		self.enabled = !self.enabled;
+	}
+
+	/// Returns the color of a pixel of the oriented texture
+	pub fn pixel_color(&self, texture: &AnimationFrameTexture, pos: (u32, u32)) -> Option<[u8; 4]> {
+		let mut last_pixel = self.last_pixel.borrow_mut();
+		if let Some(pixel) = last_pixel.as_ref() {
+			if pixel.matches(texture, pos) {
+				return Some(pixel.color);
+			}
+		}
+		let color = texture.read_pixel(pos.0, pos.1)?;
+		*last_pixel = Some(InspectedPixel {
+			tex_grid: Rc::downgrade(&texture.tex_grid),
+			orientation: texture.orientation,
+			pos,
+			color,
+		});
+		Some(color)
+	}
+
+	/// Draws the coordinates and the color of the pixel next to the cursor
+	pub fn draw(
+		&self,
+		target: &mut Frame,
+		context: &DrawContext,
+		bounds: LogicalRect,
+		cursor: LogicalVector,
+		(x, y): (u32, u32),
+		color: [u8; 4],
+	) {
+		const PADDING: f32 = overlay::PADDING;
+		const CURSOR_OFFSET: f32 = 16.0;
+		let [r, g, b, a] = color;
+		let text = format!("{}, {}   {} {} {} {}   {}", x, y, r, g, b, a, hex_color(color));
+		let text_size = context.text_size(&text, overlay::FONT_SIZE);
+		// A swatch of the color is shown before the text
+		let swatch_size = text_size.vec.y;
+		let panel_size = LogicalVector::new(
+			swatch_size + text_size.vec.x + 3.0 * PADDING,
+			text_size.vec.y + 2.0 * PADDING,
+		);
+		// Below and to the right of the cursor, unless that would be outside of the widget
+		let cursor = cursor.vec;
+		let mut pos = cursor + LogicalVector::new(CURSOR_OFFSET, CURSOR_OFFSET).vec;
+		if pos.x + panel_size.vec.x > bounds.right() {
+			pos.x = cursor.x - CURSOR_OFFSET - panel_size.vec.x;
+		}
+		if pos.y + panel_size.vec.y > bounds.bottom() {
+			pos.y = cursor.y - CURSOR_OFFSET - panel_size.vec.y;
+		}
+		pos.x = pos.x.clamp(bounds.left(), (bounds.right() - panel_size.vec.x).max(bounds.left()));
+		pos.y = pos.y.clamp(bounds.top(), (bounds.bottom() - panel_size.vec.y).max(bounds.top()));
+		let panel = LogicalRect { pos: LogicalVector::new(pos.x, pos.y), size: panel_size };
+		context.clear_color(target, [0.0, 0.0, 0.0, 0.8], Some(panel));
+		let swatch = LogicalRect {
+			pos: panel.pos + LogicalVector::new(PADDING, PADDING),
+			size: LogicalVector::new(swatch_size, swatch_size),
+		};
+		let to_float = |c: u8| c as f32 / 255.0;
+		context.clear_color(target, [to_float(r), to_float(g), to_float(b), 1.0], Some(swatch));
+		let text_pos = panel.pos + LogicalVector::new(swatch_size + 2.0 * PADDING, PADDING);
+		context.draw_text(
+			target,
+			&text,
+			overlay::FONT_SIZE,
+			text_pos,
+			overlay::TEXT_COLOR,
+			Some(panel),
+		);
+	}
+}
+
+/// Formats the color as `#RRGGBB`, or as `#RRGGBBAA` if it's not fully opaque
+pub fn hex_color([r, g, b, a]: [u8; 4]) -> String {
+	if a == u8::MAX {
+		format!("#{:02X}{:02X}{:02X}", r, g, b)
+	} else {
+		format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn hex_color_includes_alpha_only_if_transparent() {
+		assert_eq!(hex_color([255, 128, 0, 255]), "#FF8000");
+		assert_eq!(hex_color([1, 2, 171, 0]), "#0102AB00");
+	}
+}
@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+
+use gelatin::glium::Frame;
+use gelatin::misc::{LogicalRect, LogicalVector};
+use gelatin::{DrawContext, NextUpdate};
+
+const DISPLAY_TIME: Duration = Duration::from_secs(4);
+const FONT_SIZE: f32 = 13.0;
+const PADDING: f32 = 6.0;
+const MARGIN: f32 = 8.0;
+
+/// A line of text at the bottom of the picture area that tells the result of an operation,
+/// eg that a file could not be moved. It disappears after a few seconds.
+#[derive(Default)]
+pub struct Message {
+	shown: Option<(String, Instant)>,
+}
+
+impl Message {
+	pub fn show<S: Into<String>>(&mut self, text: S) {
+		self.shown = Some((text.into(), Instant::now()));
+	}
+
+	/// Hides the message once it has been shown for long enough. Returns true if the message
+	/// was hidden, in which case the widget has to be drawn again.
+	pub fn update(&mut self, now: Instant) -> (bool, NextUpdate) {
+		match &self.shown {
+			Some((_, shown_time)) if now.duration_since(*shown_time) >= DISPLAY_TIME => {
+				self.shown = None;
+				(true, NextUpdate::Latest)
+			}
+			Some((_, shown_time)) => (false, NextUpdate::WaitUntil(*shown_time + DISPLAY_TIME)),
+			None => (false, NextUpdate::Latest),
+		}
+	}
+
+	/// Draws the message centered at the bottom of `area`
+	pub fn draw(&self, target: &mut Frame, context: &DrawContext, area: LogicalRect) {
+		let text = match &self.shown {
+			Some((text, _)) => text,
+			None => return,
+		};
+		let text_size = context.text_size(text, FONT_SIZE);
+		let size = text_size + LogicalVector::new(2.0 * PADDING, 2.0 * PADDING);
+		let pos = LogicalVector::new(
+			(area.left() + (area.size.vec.x - size.vec.x) * 0.5).max(area.left()),
+			(area.bottom() - size.vec.y - MARGIN).max(area.top()),
+		);
+		let rect = LogicalRect {
+			pos,
+			size: LogicalVector::new(size.vec.x.min(area.size.vec.x), size.vec.y),
+		};
+		context.clear_color(target, [0.0, 0.0, 0.0, 0.7], Some(rect));
+		let text_pos = pos + LogicalVector::new(PADDING, PADDING);
+		context.draw_text(target, text, FONT_SIZE, text_pos, [1.0, 1.0, 1.0, 0.9], Some(rect));
+	}
+}
@@ -0,0 +1,73 @@
+use std::path::{Path, PathBuf};
+
+use gelatin::glium::Frame;
+use gelatin::misc::{LogicalRect, LogicalVector};
+use gelatin::DrawContext;
+
+use crate::metadata::{self, Metadata};
+
+use super::file_panel::FilePanel;
+use super::overlay::{self, intersect};
+
+/// The file information and the metadata of the shown image
+#[derive(Default)]
+pub struct MetadataPanel {
+	/// Reads the metadata while the panel is shown or the window title needs it
+	reader: Option<FilePanel<Metadata>>,
+	/// Toggled with the `metadata` action
+	shown: bool,
+}
+
+impl MetadataPanel {
+	pub fn toggle(&mut self) {
+		self.shown = !self.shown;
+	}
+
+	/// Reads the metadata of the image at `path` if the panel is shown or `needed` is true.
+	/// Returns true if the metadata changed.
+	pub fn update(&mut self, path: Option<PathBuf>, needed: bool) -> bool {
+		let needed = needed || self.shown;
+		if needed != self.reader.is_some() {
+			self.reader = needed.then(|| FilePanel::new(metadata::metadata_worker));
+		}
+		match &mut self.reader {
+			Some(reader) => reader.update(path),
+			None => false,
+		}
+	}
+
+	pub fn pending(&self) -> bool {
+		self.reader.as_ref().is_some_and(|reader| reader.pending())
+	}
+
+	/// The metadata of the image at `path` if it has been read
+	pub fn metadata(&self, path: &Path) -> Option<&Metadata> {
+		self.reader.as_ref()?.value_for(path)
+	}
+
+	/// Draws the panel in the top right corner of `area` if it's shown
+	pub fn draw(&self, target: &mut Frame, context: &DrawContext, area: LogicalRect) {
+		const PADDING: f32 = overlay::PADDING;
+		let reader = match &self.reader {
+			Some(reader) if self.shown => reader,
+			_ => return,
+		};
+		let lines = match reader.result() {
+			Some((_, Ok(metadata))) => metadata.lines(),
+			Some((_, Err(e))) => vec![format!("can not read the file: {}", e)],
+			None if reader.pending() => vec!["reading the metadata…".to_owned()],
+			None => return,
+		};
+		let panel_size =
+			overlay::lines_size(context, &lines) + LogicalVector::new(2.0 * PADDING, 2.0 * PADDING);
+		let pos =
+			LogicalVector::new((area.right() - panel_size.vec.x).max(area.left()), area.top());
+		let clip = match intersect(LogicalRect { pos, size: panel_size }, area) {
+			Some(clip) => clip,
+			None => return,
+		};
+		context.clear_color(target, overlay::PANEL_COLOR, Some(clip));
+		let text_pos = pos + LogicalVector::new(PADDING, PADDING);
+		overlay::draw_lines(target, context, &lines, text_pos, clip);
+	}
+}
@@ -0,0 +1,143 @@
+//! The navigator: a downscaled copy of the image with the visible part outlined, shown when
+//! the image doesn't fit in its pane. Clicking or dragging on it moves the view.
+
+use gelatin::cgmath::Vector2;
+use gelatin::glium::Frame;
+use gelatin::misc::{LogicalRect, LogicalVector};
+use gelatin::DrawContext;
+
+use super::overlay::{self, rect_from_edges};
+
+/// The length of the longer side of the minimap in logical pixels
+const MINIMAP_SIZE: f32 = 160.0;
+const MARGIN: f32 = 8.0;
+/// Leaves room for the file name of compare mode
+const COMPARE_LABEL_HEIGHT: f32 = 24.0;
+const BORDER: f32 = 1.0;
+
+#[derive(Default)]
+pub struct Minimap {
+	/// The distance between the cursor and the center of the viewport rectangle while the
+	/// minimap is being dragged
+	grab: Option<LogicalVector>,
+}
+
+impl Minimap {
+	/// Starts dragging the minimap at `cursor`. Dragging the viewport keeps the cursor at the
+	/// same spot of it, clicking elsewhere centers the view at the cursor. Returns the point
+	/// of the minimap to center the view at.
+	pub fn press(&mut self, cursor: LogicalVector, viewport: LogicalRect) -> LogicalVector {
+		let grab =
+			if viewport.contains(cursor) { cursor - viewport.center() } else { Default::default() };
+		self.grab = Some(grab);
+		cursor - grab
+	}
+
+	/// Stops dragging. Returns true if the minimap was being dragged.
+	pub fn release(&mut self) -> bool {
+		self.grab.take().is_some()
+	}
+
+	/// The point of the minimap to center the view at while it's being dragged
+	pub fn drag_to(&self, cursor: LogicalVector) -> Option<LogicalVector> {
+		self.grab.map(|grab| cursor - grab)
+	}
+}
+
+/// The area of the minimap in `pane` and the number of its logical pixels per image pixel.
+/// `img_scale` is the number of logical pixels per image pixel of the image in the pane.
+/// Returns `None` when the whole image is visible or the pane is too small for the minimap.
+pub fn placement(
+	pane: LogicalRect,
+	(img_w, img_h): (u32, u32),
+	img_scale: f32,
+	comparing: bool,
+) -> Option<(LogicalRect, f32)> {
+	let displayed = LogicalVector::new(img_w as f32, img_h as f32) * img_scale;
+	// Half a pixel of tolerance for the rounding of fitted images
+	let exceeds =
+		displayed.vec.x > pane.size.vec.x + 0.5 || displayed.vec.y > pane.size.vec.y + 0.5;
+	if !exceeds {
+		return None;
+	}
+	let scale = MINIMAP_SIZE / img_w.max(img_h).max(1) as f32;
+	let size = LogicalVector::new(img_w as f32 * scale, img_h as f32 * scale);
+	let top = if comparing { MARGIN + COMPARE_LABEL_HEIGHT } else { MARGIN };
+	let pos = pane.pos + LogicalVector::new(MARGIN, top);
+	let fits = pos.vec.x + size.vec.x + MARGIN <= pane.right()
+		&& pos.vec.y + size.vec.y + MARGIN <= pane.bottom();
+	fits.then_some((LogicalRect { pos, size }, scale))
+}
+
+/// The part of the minimap that shows the visible part of the image, given as the image
+/// positions of the corners of the pane
+pub fn viewport(
+	minimap: LogicalRect,
+	scale: f32,
+	(img_w, img_h): (u32, u32),
+	top_left: Vector2<f32>,
+	bottom_right: Vector2<f32>,
+) -> LogicalRect {
+	let left = minimap.left() + top_left.x.clamp(0.0, img_w as f32) * scale;
+	let top = minimap.top() + top_left.y.clamp(0.0, img_h as f32) * scale;
+	let right = minimap.left() + bottom_right.x.clamp(0.0, img_w as f32) * scale;
+	let bottom = minimap.top() + bottom_right.y.clamp(0.0, img_h as f32) * scale;
+	rect_from_edges(left, top, right, bottom)
+}
+
+/// Draws the frame of the minimap, the image with `draw_image` and the outline of the
+/// viewport. Nothing is drawn outside of `pane`.
+pub fn draw(
+	target: &mut Frame,
+	context: &DrawContext,
+	minimap: LogicalRect,
+	viewport: LogicalRect,
+	pane: LogicalRect,
+	draw_image: impl FnOnce(&mut Frame),
+) {
+	let frame = LogicalRect {
+		pos: minimap.pos - LogicalVector::new(2.0 * BORDER, 2.0 * BORDER),
+		size: minimap.size + LogicalVector::new(4.0 * BORDER, 4.0 * BORDER),
+	};
+	context.clear_color(target, overlay::LABEL_COLOR, Some(frame));
+	draw_image(target);
+	overlay::draw_outline(target, context, viewport, BORDER, pane);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rect(x: f32, y: f32, w: f32, h: f32) -> LogicalRect {
+		LogicalRect { pos: LogicalVector::new(x, y), size: LogicalVector::new(w, h) }
+	}
+
+	#[test]
+	fn hidden_when_the_image_fits() {
+		assert!(placement(rect(0.0, 0.0, 800.0, 600.0), (400, 300), 2.0, false).is_none());
+		assert!(placement(rect(0.0, 0.0, 800.0, 600.0), (400, 300), 2.1, false).is_some());
+	}
+
+	#[test]
+	fn longer_side_has_the_minimap_size() {
+		let (minimap, scale) =
+			placement(rect(10.0, 20.0, 800.0, 600.0), (1600, 400), 1.0, false).unwrap();
+		assert_eq!(scale, MINIMAP_SIZE / 1600.0);
+		assert_eq!(minimap.pos.vec, Vector2::new(10.0 + MARGIN, 20.0 + MARGIN));
+		assert_eq!(minimap.size.vec, Vector2::new(MINIMAP_SIZE, MINIMAP_SIZE / 4.0));
+	}
+
+	#[test]
+	fn viewport_is_clamped_to_the_image() {
+		let minimap = rect(0.0, 0.0, 100.0, 50.0);
+		let viewport = viewport(
+			minimap,
+			0.5,
+			(200, 100),
+			Vector2::new(-20.0, 10.0),
+			Vector2::new(50.0, 300.0),
+		);
+		assert_eq!(viewport.pos.vec, Vector2::new(0.0, 5.0));
+		assert_eq!(viewport.size.vec, Vector2::new(25.0, 45.0));
+	}
+}
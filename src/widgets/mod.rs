@@ -1,4 +1,18 @@
 pub mod bottom_bar;
+pub mod command_palette;
+pub mod compare;
 pub mod copy_notification;
+pub mod file_panel;
+pub mod filmstrip;
+pub mod gallery;
 pub mod help_screen;
+pub mod hint;
+pub mod histogram_panel;
+pub mod inspector;
+pub mod message;
+pub mod metadata_panel;
+pub mod minimap;
+pub mod overlay;
 pub mod picture_widget;
+pub mod reference;
+pub mod selection;
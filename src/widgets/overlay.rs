@@ -0,0 +1,109 @@
+//! Helpers for the labels and the panels that are drawn on top of the image
+
+use gelatin::glium::Frame;
+use gelatin::misc::{LogicalRect, LogicalVector};
+use gelatin::DrawContext;
+
+pub const FONT_SIZE: f32 = 13.0;
+pub const PADDING: f32 = 4.0;
+pub const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+/// The background of the labels
+pub const LABEL_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+/// The background of the panels with several lines of text
+pub const PANEL_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.7];
+
+/// The size of a label of a single line with padding around the text
+pub fn label_size(context: &DrawContext, text: &str) -> LogicalVector {
+	context.text_size(text, FONT_SIZE) + LogicalVector::new(2.0 * PADDING, 2.0 * PADDING)
+}
+
+/// Draws a single line of text with its background at `pos`. The label is cut off at the
+/// edges of `area`.
+pub fn draw_label(
+	target: &mut Frame,
+	context: &DrawContext,
+	text: &str,
+	pos: LogicalVector,
+	area: LogicalRect,
+	background: [f32; 4],
+) {
+	let rect = LogicalRect { pos, size: label_size(context, text) };
+	let clip = match intersect(rect, area) {
+		Some(clip) => clip,
+		None => return,
+	};
+	context.clear_color(target, background, Some(clip));
+	let text_pos = pos + LogicalVector::new(PADDING, PADDING);
+	context.draw_text(target, text, FONT_SIZE, text_pos, TEXT_COLOR, Some(clip));
+}
+
+/// The size of the lines of text drawn by `draw_lines`, without padding
+pub fn lines_size(context: &DrawContext, lines: &[String]) -> LogicalVector {
+	let line_height = context.line_height(FONT_SIZE);
+	let width = lines.iter().map(|line| context.text_width(line, FONT_SIZE)).fold(0.0, f32::max);
+	LogicalVector::new(width, line_height * lines.len() as f32)
+}
+
+/// Draws the lines of text below each other starting at `pos`
+pub fn draw_lines(
+	target: &mut Frame,
+	context: &DrawContext,
+	lines: &[String],
+	mut pos: LogicalVector,
+	clip: LogicalRect,
+) {
+	let line_height = context.line_height(FONT_SIZE);
+	for line in lines {
+		context.draw_text(target, line, FONT_SIZE, pos, TEXT_COLOR, Some(clip));
+		pos.vec.y += line_height;
+	}
+}
+
+/// Draws a light and a dark border around `rect`, so that it's visible on any image
+pub fn draw_outline(
+	target: &mut Frame,
+	context: &DrawContext,
+	rect: LogicalRect,
+	border: f32,
+	clip: LogicalRect,
+) {
+	let dark = [0.0, 0.0, 0.0, 0.8];
+	let light = [1.0, 1.0, 1.0, 0.9];
+	let (left, top, right, bottom) = (rect.left(), rect.top(), rect.right(), rect.bottom());
+	let edges = [
+		rect_from_edges(left, top - border, right, top),
+		rect_from_edges(left, bottom, right, bottom + border),
+		rect_from_edges(left - border, top - border, left, bottom + border),
+		rect_from_edges(right, top - border, right + border, bottom + border),
+	];
+	for (edge, offset) in edges.iter().zip([(0.0, -1.0), (0.0, 1.0), (-1.0, 0.0), (1.0, 0.0)]) {
+		let offset = LogicalVector::new(offset.0 * border, offset.1 * border);
+		if let Some(outer) =
+			intersect(LogicalRect { pos: edge.pos + offset, size: edge.size }, clip)
+		{
+			context.clear_color(target, dark, Some(outer));
+		}
+		if let Some(inner) = intersect(*edge, clip) {
+			context.clear_color(target, light, Some(inner));
+		}
+	}
+}
+
+pub fn rect_from_edges(left: f32, top: f32, right: f32, bottom: f32) -> LogicalRect {
+	LogicalRect {
+		pos: LogicalVector::new(left, top),
+		size: LogicalVector::new(right - left, bottom - top),
+	}
+}
+
+/// Returns the overlapping part of the rectangles or `None` if they don't overlap
+pub fn intersect(a: LogicalRect, b: LogicalRect) -> Option<LogicalRect> {
+	let left = a.left().max(b.left());
+	let top = a.top().max(b.top());
+	let right = a.right().min(b.right());
+	let bottom = a.bottom().min(b.bottom());
+	if right <= left || bottom <= top {
+		return None;
+	}
+	Some(rect_from_edges(left, top, right, bottom))
+}
@@ -1,14 +1,24 @@
 use std::{
-	cell::{Ref, RefCell},
-	path::PathBuf,
+	cell::RefCell,
+	fs,
+	path::{self, PathBuf},
+	process,
 	rc::{Rc, Weak},
-	sync::{Arc, Mutex},
+	sync::{
+		mpsc::{self, Receiver, Sender},
+		Arc, Mutex,
+	},
 	time::{Duration, Instant},
 };
 
 use gelatin::{
 	cgmath::{Matrix4, Vector2, Vector3},
-	glium::{uniform, uniforms::MagnifySamplerFilter, Frame, Program, Surface},
+	glium::{
+		texture::SrgbTexture2d,
+		uniform,
+		uniforms::{MagnifySamplerFilter, Sampler},
+		Frame, Program, Surface,
+	},
 	shaders::ShaderDescriptor,
 	winit::{
 		event::{ElementState, MouseButton},
@@ -19,33 +29,145 @@ use gelatin::{
 use gelatin::{
 	add_common_widget_functions,
 	application::request_exit,
+	context_menu::ContextMenu,
 	misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement},
+	text_input::TextInput,
 	window::{RenderValidity, Window},
 	winit::keyboard::ModifiersState,
 	Display, DrawContext, Event, EventKind, NextUpdate, Widget, WidgetData, WidgetError,
 };
 
 use crate::{
-	clipboard_handler::ClipboardHandler,
-	configuration::{Antialias, Cache, Configuration},
-	image_cache::{image_loader::Orientation, AnimationFrameTexture},
+	clipboard_handler::{ClipboardHandler, ClipboardRequest},
+	configuration::{
+		self, Antialias, BackgroundMode, BackgroundSection, Cache, Configuration, RightButton,
+		TitleSection,
+	},
+	destinations::{self, MovedFile, TransferOutcome},
+	export::{self, ExportRequest, ExportResult, ExportSettings, PixelRect},
+	file_transform::{self, FileTransform},
+	image_cache::{image_loader::Orientation, thumbnails::ThumbnailCache, AnimationFrameTexture},
 	input_handling::*,
 	playback_manager::*,
 	shaders,
+	template::{self, TemplateValues},
 	utils::virtual_keycode_to_string,
 };
 
-use super::{bottom_bar::BottomBar, copy_notification::CopyNotifications, help_screen::HelpScreen};
+use super::{
+	bottom_bar::BottomBar,
+	command_palette::CommandPalette,
+	compare::ComparePane,
+	copy_notification::CopyNotifications,
+	filmstrip::Filmstrip,
+	gallery::Gallery,
+	help_screen::HelpScreen,
+	hint::Hint,
+	histogram_panel::HistogramPanel,
+	inspector::{self, Inspector},
+	message::Message,
+	metadata_panel::MetadataPanel,
+	minimap::{self, Minimap},
+	overlay::{self, rect_from_edges},
+	reference::{self, Reference},
+	selection::{self, Selection},
+};
 
 const MIN_ZOOM_FACTOR: f32 = 0.0001;
 const MAX_ZOOM_FACTOR: f32 = 10000.0;
 const AA_TEXEL_SIZE_THRESHOLD: f32 = 4f32;
+/// The zoom level above which the pixel grid starts to fade in if it's not configured
+const DEFAULT_PIXEL_GRID_ZOOM: f32 = 8.0;
+/// The size of the squares of the theme's checkerboard in physical pixels
+const THEME_SQUARE_SIZE: f32 = 12.0;
+/// The number of moves to destinations that can be undone
+const MAX_UNDO_MOVES: usize = 100;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ScalingMode {
 	Fixed,
 	FitStretch,
 	FitMin,
+	FitWidth,
+	FitHeight,
+}
+
+/// The colors of the background modes, read from the `[background]` section of the config
+struct BackgroundColors {
+	checkerboard: [[f32; 4]; 2],
+	/// In logical pixels
+	checkerboard_size: f32,
+	solid: [f32; 4],
+}
+impl BackgroundColors {
+	fn from_config(config: Option<&BackgroundSection>) -> BackgroundColors {
+		let color = |value: Option<&String>, default: [f32; 4]| match value {
+			Some(value) => configuration::parse_color(value).unwrap_or_else(|| {
+				eprintln!("Illegal color {:?} in the background section, expected #RRGGBB", value);
+				default
+			}),
+			None => default,
+		};
+		let colors = config.and_then(|c| c.checkerboard_colors.as_ref());
+		BackgroundColors {
+			checkerboard: [
+				color(colors.map(|c| &c[0]), [1.0, 1.0, 1.0, 1.0]),
+				color(colors.map(|c| &c[1]), [0.8, 0.8, 0.8, 1.0]),
+			],
+			checkerboard_size: config.and_then(|c| c.checkerboard_size).unwrap_or(8.0).max(1.0),
+			solid: color(config.and_then(|c| c.color.as_ref()), [0.5, 0.5, 0.5, 1.0]),
+		}
+	}
+}
+
+/// Which channels of the image are drawn
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ChannelView {
+	Rgb,
+	Red,
+	Green,
+	Blue,
+	/// The alpha channel as shades of gray
+	Alpha,
+	/// The relative luminance as shades of gray
+	Luma,
+}
+impl ChannelView {
+	fn next(self) -> Self {
+		match self {
+			ChannelView::Rgb => ChannelView::Red,
+			ChannelView::Red => ChannelView::Green,
+			ChannelView::Green => ChannelView::Blue,
+			ChannelView::Blue => ChannelView::Alpha,
+			ChannelView::Alpha => ChannelView::Luma,
+			ChannelView::Luma => ChannelView::Rgb,
+		}
+	}
+
+	/// The value of the `channel` uniform of the fragment shader
+	fn shader_index(self) -> i32 {
+		self as i32
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			ChannelView::Rgb => "RGB",
+			ChannelView::Red => "Red channel",
+			ChannelView::Green => "Green channel",
+			ChannelView::Blue => "Blue channel",
+			ChannelView::Alpha => "Alpha channel",
+			ChannelView::Luma => "Luminance",
+		}
+	}
+}
+
+/// Where and how large an image is drawn by `draw_tex_grid`
+#[derive(Debug, Copy, Clone)]
+struct ImageView {
+	/// The center of the image relative to the bounds it's drawn in
+	pos: LogicalVector,
+	/// Physical pixels per image pixel
+	texel_size: f32,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy)]
@@ -96,8 +218,8 @@ fn orientation_to_matrix(orientation: Orientation) -> Matrix4<f32> {
 			0.0, 0.0, 0.0, 1.0
 		),
 		Orientation::Deg90VerFlip => Matrix4::new(
-			0.0, -1.0, 0.0, 0.0,
-			-1.0, 0.0, 0.0, 0.0,
+			0.0, 1.0, 0.0, 0.0,
+			1.0, 0.0, 0.0, 0.0,
 			0.0, 0.0, 1.0, 0.0,
 			0.0, 0.0, 0.0, 1.0
 		),
@@ -108,8 +230,8 @@ fn orientation_to_matrix(orientation: Orientation) -> Matrix4<f32> {
 			0.0, 0.0, 0.0, 1.0
 		),
 		Orientation::Deg270VerFlip => Matrix4::new(
-			0.0, 1.0, 0.0, 0.0,
-			1.0, 0.0, 0.0, 0.0,
+			0.0, -1.0, 0.0, 0.0,
+			-1.0, 0.0, 0.0, 0.0,
 			0.0, 0.0, 1.0, 0.0,
 			0.0, 0.0, 0.0, 1.0
 		),
@@ -133,14 +255,35 @@ struct PictureWidgetData {
 	// It's an option to allow manual destruction.
 	clipboard_handler: Option<ClipboardHandler>,
 	clipboard_request_was_pending: bool,
+	/// Files moved with a destination binding, most recent last
+	undo_stack: Vec<MovedFile>,
 
 	program: Program,
 	bright_shade: f32,
+	/// The dpi scale factor of the last draw
+	dpi_scale: f32,
 	/// Size of an image texel in physical display pixels
 	img_texel_size: f32,
 	scaling: ScalingMode,
 	img_pos: LogicalVector,
 	antialiasing: Antialias,
+	background: BackgroundMode,
+	background_colors: BackgroundColors,
+	/// Applied on top of the orientation of the image file. Only affects the display.
+	view_orientation: Orientation,
+	selection: Selection,
+	/// Keep the zoom level and the relative position of the image when moving to another image
+	view_locked: bool,
+	inspector: Inspector,
+	/// Draw lines between the pixels of the image when zoomed in far enough
+	pixel_grid: bool,
+	channel_view: ChannelView,
+	/// Treat the color channels of the image as multiplied by the alpha
+	premultiplied: bool,
+	compare: Option<ComparePane>,
+	reference: Option<Reference>,
+	histogram: Option<HistogramPanel>,
+	metadata: MetadataPanel,
 
 	hor_pan_input: MovementDir,
 	ver_pan_input: MovementDir,
@@ -163,8 +306,18 @@ struct PictureWidgetData {
 	last_cam_move_time: Instant,
 	next_update: NextUpdate,
 	bottom_bar: Rc<BottomBar>,
-	left_to_pan_hint: Rc<HelpScreen>,
+	left_to_pan_hint: Rc<Hint>,
 	copy_notifications: CopyNotifications,
+	message: Message,
+	/// Where the exports running in the background send their results to
+	export_sender: Sender<ExportResult>,
+	export_results: Receiver<ExportResult>,
+	exports_pending: usize,
+	/// Shown while the current file is being renamed. Receives all key input while visible.
+	rename_input: Rc<TextInput>,
+	/// The thumbnails of the images around the current one, kept up to date while visible
+	filmstrip: Option<Rc<Filmstrip>>,
+	minimap: Minimap,
 	window: Weak<Window>,
 }
 impl WidgetData for PictureWidgetData {
@@ -180,7 +333,7 @@ impl WidgetData for PictureWidgetData {
 }
 impl PictureWidgetData {
 	fn fit_image_to_panel(&mut self, dpi_scale: f32, stretch: bool) {
-		let size = self.drawn_bounds.size.vec;
+		let size = self.view_bounds().size.vec;
 		if let Some(texture) = self.get_texture() {
 			let panel_aspect = size.x / size.y;
 			let (img_phys_w, img_pyhs_h) = {
@@ -213,6 +366,39 @@ impl PictureWidgetData {
 		}
 	}
 
+	/// Scales the image so that its width or height equals to the widget's. If the other
+	/// dimension doesn't fit, the image is aligned to the top or to the left.
+	fn fit_image_side_to_panel(&mut self, dpi_scale: f32, fit_width: bool) {
+		let size = self.view_bounds().size.vec;
+		if let Some(texture) = self.get_texture() {
+			let (img_w, img_h) = texture.oriented_dimensions();
+			let (img_w, img_h) = (img_w as f32, img_h as f32);
+			let logical_texel_size = if fit_width { size.x / img_w } else { size.y / img_h };
+			let img_size = Vector2::new(img_w, img_h) * logical_texel_size;
+			let center = |widget_size: f32, img_size: f32| {
+				if img_size > widget_size {
+					img_size * 0.5
+				} else {
+					widget_size * 0.5
+				}
+			};
+			self.img_pos =
+				LogicalVector::new(center(size.x, img_size.x), center(size.y, img_size.y));
+			self.img_texel_size = logical_texel_size * dpi_scale;
+		}
+	}
+
+	/// Sets the zoom level while keeping the center of the widget fixed on the image
+	fn zoom_to(&mut self, img_texel_size: f32) {
+		let img_texel_size = img_texel_size.clamp(MIN_ZOOM_FACTOR, MAX_ZOOM_FACTOR);
+		let anchor = self.view_bounds().size * 0.5f32;
+		self.img_pos = (img_texel_size / self.img_texel_size) * (self.img_pos - anchor) + anchor;
+		self.img_texel_size = img_texel_size;
+		self.scaling = ScalingMode::Fixed;
+		self.update_scaling_buttons();
+		self.render_validity.invalidate();
+	}
+
 	fn zoom_image(&mut self, anchor: LogicalVector, mut delta: f32) {
 		delta = if delta > 0.0 { delta + 1.0 } else { 1.0 / (delta.abs() + 1.0) };
 		let mut image_texel_size = (self.img_texel_size * delta).max(0.0);
@@ -229,9 +415,10 @@ impl PictureWidgetData {
 	}
 
 	fn update_image_transform(&mut self, dpi_scale: f32) {
+		self.dpi_scale = dpi_scale;
 		match self.scaling {
 			ScalingMode::Fixed => {
-				let center_offset = (self.drawn_bounds.size - self.prev_draw_size) * 0.5f32;
+				let center_offset = (self.view_bounds().size - self.prev_draw_size) * 0.5f32;
 				self.img_pos += center_offset;
 				self.apply_img_bounds(dpi_scale);
 			}
@@ -241,8 +428,16 @@ impl PictureWidgetData {
 			ScalingMode::FitMin => {
 				self.fit_image_to_panel(dpi_scale, false);
 			}
+			ScalingMode::FitWidth => {
+				self.fit_image_side_to_panel(dpi_scale, true);
+			}
+			ScalingMode::FitHeight => {
+				self.fit_image_side_to_panel(dpi_scale, false);
+			}
 		}
-		self.prev_draw_size = self.drawn_bounds.size;
+		self.prev_draw_size = self.view_bounds().size;
+		let zoom = self.get_texture().map(|_| self.img_texel_size);
+		self.bottom_bar.set_zoom(zoom);
 	}
 
 	fn apply_camera_movement(&mut self, dpi_scale: f32) {
@@ -288,7 +483,7 @@ impl PictureWidgetData {
 		}
 
 		if self.zoom_input.moving() {
-			let bounds_size = self.drawn_bounds.size.vec;
+			let bounds_size = self.view_bounds().size.vec;
 			let anchor = LogicalVector::new(bounds_size.x * 0.5, bounds_size.y * 0.5);
 			self.zoom_image(anchor, self.zoom_vel * dt_sec);
 		}
@@ -310,13 +505,10 @@ impl PictureWidgetData {
 		}
 	}
 
-	fn set_window_title_filename(
-		&self,
-		window: &Window,
-		playback_state: PlaybackState,
-		file_path: &LoadedImgPath,
-	) {
-		let playback = match playback_state {
+	/// The values of the placeholders of the window title and the status. `index` and `total` are the
+	/// position of the current image in its folder.
+	fn template_values(&self, index: Option<usize>, total: Option<usize>) -> TemplateValues<'_> {
+		let playback = match self.playback_manager.playback_state() {
 			PlaybackState::Forward => " : Playing",
 			PlaybackState::Present => " : Presenting",
 			PlaybackState::RandomPresent => " : Presenting Shuffled",
@@ -326,19 +518,473 @@ impl PictureWidgetData {
 		let config = self.configuration.borrow();
 		let title_config = config.title.clone().unwrap_or_default();
 
-		let name = match file_path {
-			LoadedImgPath::NotYetLoaded => "[ none ]".into(),
+		let file_path = self.playback_manager.shown_file_path();
+		let (name, path) = match file_path {
+			LoadedImgPath::NotYetLoaded => ("[ none ]".to_owned(), None),
 			LoadedImgPath::ErrLoading(path) => {
-				format!("[ FAILED TO OPEN ] {}", title_config.format_file_path(path)).into()
+				let name = format!("[ FAILED TO OPEN ] {}", title_config.format_file_path(path));
+				(name, Some(path.as_path()))
+			}
+			LoadedImgPath::Loaded(path) => {
+				(title_config.format_file_path(path).into_owned(), Some(path.as_path()))
 			}
-			LoadedImgPath::Loaded(path) => title_config.format_file_path(path),
 		};
-		let title = format!("{}{}{}", name, playback, title_config.format_program_name());
-		window.set_title(title);
+		let texture = self.get_texture();
+		let metadata = path.and_then(|path| self.metadata.metadata(path));
+		TemplateValues {
+			name,
+			playback,
+			path,
+			index,
+			total,
+			dimensions: texture.as_ref().map(|t| t.oriented_dimensions()),
+			zoom: texture.as_ref().map(|_| self.img_texel_size),
+			frame: texture.and_then(|_| self.playback_manager.current_frame()),
+			metadata,
+		}
 	}
 
+	/// Sets the window title and the status text of the bottom bar
+	fn update_title_and_status(&self, window: &Window, index: Option<usize>, total: Option<usize>) {
+		let config = self.configuration.borrow();
+		let default_title = TitleSection::default();
+		let title_template = config.title.as_ref().unwrap_or(&default_title).template();
+		let values = self.template_values(index, total);
+		window.set_title(template::render(&title_template, &values));
+		let status = match values.path {
+			Some(_) => template::render(config.status_format(), &values),
+			None => String::new(),
+		};
+		self.bottom_bar.set_status(status, self.dpi_scale);
+	}
+
+	/// Returns true if the metadata of the image has to be read for the window title or the
+	/// status
+	fn templates_need_metadata(&self) -> bool {
+		let config = self.configuration.borrow();
+		let title_format = config.title.as_ref().and_then(|t| t.format.as_deref());
+		title_format.is_some_and(template::needs_metadata)
+			|| template::needs_metadata(config.status_format())
+	}
+
+	/// Returns the current texture with the view orientation applied
 	fn get_texture(&self) -> Option<AnimationFrameTexture> {
-		self.playback_manager.image_texture()
+		let mut texture = self.playback_manager.image_texture()?;
+		texture.orientation = texture.orientation.followed_by(self.view_orientation);
+		Some(texture)
+	}
+
+	/// Returns the current texture of the compare pane with the view orientation applied
+	fn get_compare_texture(&self) -> Option<AnimationFrameTexture> {
+		self.compare.as_ref()?.texture(self.view_orientation)
+	}
+
+	/// The area that the position and the size of the image are relative to
+	fn view_bounds(&self) -> LogicalRect {
+		match &self.compare {
+			Some(compare) => compare.main_view_bounds(self.drawn_bounds),
+			None => self.drawn_bounds,
+		}
+	}
+
+	/// The area that the compare image is positioned in
+	fn compare_bounds(&self) -> Option<LogicalRect> {
+		Some(self.compare.as_ref()?.view_bounds(self.drawn_bounds))
+	}
+
+	/// The part of the widget where the main image is visible
+	fn main_pane_rect(&self) -> LogicalRect {
+		match &self.compare {
+			Some(compare) => compare.main_pane(self.drawn_bounds),
+			None => self.drawn_bounds,
+		}
+	}
+
+	/// The part of the widget where the compare image is visible
+	fn compare_pane_rect(&self) -> Option<LogicalRect> {
+		Some(self.compare.as_ref()?.pane(self.drawn_bounds))
+	}
+
+	/// Maps a position over the compare image to the position over the main image that shows
+	/// the same point of the image
+	fn to_main_pane(&self, pos: LogicalVector) -> LogicalVector {
+		match &self.compare {
+			Some(compare) => compare.to_main_pane(self.drawn_bounds, pos),
+			None => pos,
+		}
+	}
+
+	/// Enters compare mode showing the image at `path` next to the current one. The current
+	/// image is shown on both sides if `path` is `None`.
+	fn start_compare(&mut self, path: Option<PathBuf>) {
+		let path = match path {
+			Some(path) => path,
+			None => match self.playback_manager.shown_file_path() {
+				LoadedImgPath::Loaded(path) => path.clone(),
+				_ => return,
+			},
+		};
+		match &mut self.compare {
+			Some(compare) => compare.request_load(LoadRequest::FilePath(path)),
+			None => self.compare = Some(ComparePane::new(path)),
+		}
+		self.clear_selection();
+		self.render_validity.invalidate();
+	}
+
+	fn toggle_compare(&mut self) {
+		if self.compare.is_some() {
+			self.compare = None;
+			self.render_validity.invalidate();
+		} else {
+			self.start_compare(None);
+		}
+	}
+
+	fn cycle_compare_layout(&mut self) {
+		if let Some(compare) = &mut self.compare {
+			compare.cycle_layout();
+			self.render_validity.invalidate();
+		}
+	}
+
+	/// Pins the current image as the reference, or unpins the reference if there's one
+	fn toggle_reference(&mut self) {
+		if self.reference.take().is_some() {
+			self.render_validity.invalidate();
+			return;
+		}
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path.clone(),
+			_ => return,
+		};
+		if let Some(texture) = self.playback_manager.image_texture() {
+			self.reference = Some(Reference::new(path, texture));
+			self.render_validity.invalidate();
+		}
+	}
+
+	fn cycle_reference_mode(&mut self) {
+		if let Some(reference) = &mut self.reference {
+			reference.cycle_mode();
+			self.render_validity.invalidate();
+		}
+	}
+
+	/// Updates the statistics of the difference to the reference and its flicker
+	fn update_reference(&mut self, now: Instant) -> NextUpdate {
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => Some(path.clone()),
+			_ => None,
+		};
+		let reference = match &mut self.reference {
+			Some(reference) => reference,
+			None => return NextUpdate::Latest,
+		};
+		let (redraw, next_update) = reference.update(path, now);
+		if redraw {
+			self.render_validity.invalidate();
+		}
+		next_update
+	}
+
+	/// Gives the filmstrip the images of the current folder if it doesn't show them yet
+	fn update_filmstrip(&mut self, curr_file_index: usize) {
+		let filmstrip = match &self.filmstrip {
+			Some(filmstrip) if filmstrip.visible() => filmstrip.clone(),
+			_ => return,
+		};
+		let curr_path = match self.playback_manager.current_file_path() {
+			Some(path) => path,
+			None => return,
+		};
+		if filmstrip.shows(curr_file_index, &curr_path) {
+			return;
+		}
+		if let Some(paths) = self.playback_manager.image_paths() {
+			filmstrip.set_images(paths, curr_file_index);
+		}
+	}
+
+	fn toggle_histogram(&mut self) {
+		if self.histogram.take().is_none() {
+			self.histogram = Some(HistogramPanel::default());
+		}
+		self.playback_manager.set_histograms(self.histogram.is_some());
+		self.render_validity.invalidate();
+	}
+
+	fn toggle_metadata(&mut self) {
+		self.metadata.toggle();
+		self.render_validity.invalidate();
+	}
+
+	/// Collects the histogram and the metadata read on the worker threads and starts
+	/// processing the current image if needed.
+	fn update_file_panels(&mut self, now: Instant) -> NextUpdate {
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => Some(path.clone()),
+			_ => None,
+		};
+		let mut pending = false;
+		if let Some(panel) = &mut self.histogram {
+			let loaded = self.playback_manager.current_histogram();
+			let loading = self.playback_manager.current_loading();
+			if panel.update(path.clone(), loaded, loading) {
+				self.render_validity.invalidate();
+			}
+			pending |= loading || panel.pending();
+		}
+		let needs_metadata = self.templates_need_metadata();
+		if self.metadata.update(path, needs_metadata) {
+			self.render_validity.invalidate();
+		}
+		pending |= self.metadata.pending();
+		if pending {
+			return NextUpdate::WaitUntil(now + Duration::from_millis(100));
+		}
+		NextUpdate::Latest
+	}
+
+	/// Returns the texture to draw in the main pane and the reference texture if the
+	/// difference to it should be drawn
+	fn main_pane_textures(&self) -> Option<(AnimationFrameTexture, Option<AnimationFrameTexture>)> {
+		let texture = self.get_texture()?;
+		match &self.reference {
+			Some(reference) => {
+				Some(reference.textures(texture, self.view_orientation, Instant::now()))
+			}
+			None => Some((texture, None)),
+		}
+	}
+
+	fn compare_request_load(&mut self, request: LoadRequest) {
+		if let Some(compare) = &mut self.compare {
+			compare.request_load(request);
+			self.render_validity.invalidate();
+		}
+	}
+
+	/// Logical pixels per image pixel
+	fn img_scale(&self) -> f32 {
+		self.img_texel_size / self.dpi_scale
+	}
+
+	/// Position of the top left corner of the oriented image relative to the widget
+	fn img_corner(&self, texture: &AnimationFrameTexture) -> LogicalVector {
+		let (img_w, img_h) = texture.oriented_dimensions();
+		let img_size = LogicalVector::new(img_w as f32, img_h as f32) * self.img_scale();
+		self.img_pos - img_size * 0.5f32
+	}
+
+	/// Converts a position in the window into the pixel coordinates of the oriented image.
+	///
+	/// This is the inverse of the transform built in `draw_tex_grid`. The result may be outside
+	/// of the image.
+	fn window_to_image(&self, pos: LogicalVector) -> Option<Vector2<f32>> {
+		let texture = self.get_texture()?;
+		let corner = self.img_corner(&texture);
+		Some((pos - self.view_bounds().pos - corner).vec / self.img_scale())
+	}
+
+	/// Returns the rectangle in the window that the given region of the oriented image covers.
+	fn image_rect_to_window(&self, rect: PixelRect) -> Option<LogicalRect> {
+		let texture = self.get_texture()?;
+		let scale = self.img_scale();
+		let corner = self.view_bounds().pos + self.img_corner(&texture);
+		Some(LogicalRect {
+			pos: corner + LogicalVector::new(rect.x as f32, rect.y as f32) * scale,
+			size: LogicalVector::new(rect.w as f32, rect.h as f32) * scale,
+		})
+	}
+
+	/// Returns the point of the oriented image at the center of the widget, relative to the
+	/// size of the image. For example (0.5, 0.5) is the center of the image.
+	fn relative_center(&self) -> Option<Vector2<f32>> {
+		let center = self.window_to_image(self.view_bounds().center())?;
+		let (img_w, img_h) = self.get_texture()?.oriented_dimensions();
+		Some(Vector2::new(center.x / img_w as f32, center.y / img_h as f32))
+	}
+
+	/// Moves the image so that the point given relative to its size is at the center of the
+	/// widget.
+	fn set_relative_center(&mut self, relative_center: Vector2<f32>) {
+		if let Some(texture) = self.get_texture() {
+			let (img_w, img_h) = texture.oriented_dimensions();
+			let offset_from_img_center = Vector2::new(
+				(relative_center.x - 0.5) * img_w as f32,
+				(relative_center.y - 0.5) * img_h as f32,
+			) * self.img_scale();
+			let widget_center = self.view_bounds().size * 0.5f32;
+			self.img_pos = LogicalVector { vec: widget_center.vec - offset_from_img_center };
+			self.render_validity.invalidate();
+		}
+	}
+
+	fn toggle_view_lock(&mut self) {
+		self.view_locked = !self.view_locked;
+		if self.view_locked {
+			// Keep the current zoom level instead of fitting the next image
+			self.scaling = ScalingMode::Fixed;
+			self.update_scaling_buttons();
+		}
+		self.bottom_bar.set_view_locked(self.view_locked);
+		self.render_validity.invalidate();
+	}
+
+	/// Returns the part of the image that's visible within the widget, in the pixels of the
+	/// oriented image.
+	fn visible_image_region(&self) -> Option<PixelRect> {
+		let bounds = self.main_pane_rect();
+		let top_left = self.window_to_image(bounds.pos)?;
+		let bottom_right = self.window_to_image(bounds.pos + bounds.size)?;
+		let image_size = self.get_texture()?.oriented_dimensions();
+		selection::rect_between(top_left, bottom_right, image_size)
+	}
+
+	/// The view of the image as it's drawn in `view_bounds`
+	fn image_view(&self) -> ImageView {
+		ImageView { pos: self.img_pos, texel_size: self.img_texel_size }
+	}
+
+	/// The area of the navigator minimap and the number of its logical pixels per image pixel
+	fn minimap_rect(&self) -> Option<(LogicalRect, f32)> {
+		let image_size = self.get_texture()?.oriented_dimensions();
+		let comparing = self.compare.is_some();
+		minimap::placement(self.main_pane_rect(), image_size, self.img_scale(), comparing)
+	}
+
+	/// The part of the minimap that's visible in the main pane
+	fn minimap_viewport(&self, minimap: LogicalRect, scale: f32) -> Option<LogicalRect> {
+		let pane = self.main_pane_rect();
+		let top_left = self.window_to_image(pane.pos)?;
+		let bottom_right = self.window_to_image(pane.pos + pane.size)?;
+		let image_size = self.get_texture()?.oriented_dimensions();
+		Some(minimap::viewport(minimap, scale, image_size, top_left, bottom_right))
+	}
+
+	/// Moves the image so that the point of the minimap under `pos` is in the center of the
+	/// main pane
+	fn center_minimap_pos(&mut self, pos: LogicalVector) {
+		let (minimap, scale) = match self.minimap_rect() {
+			Some(minimap) => minimap,
+			None => return,
+		};
+		let texture = match self.get_texture() {
+			Some(texture) => texture,
+			None => return,
+		};
+		let (img_w, img_h) = texture.oriented_dimensions();
+		let img_point = ((pos - minimap.pos) / scale).vec;
+		let img_size = LogicalVector::new(img_w as f32, img_h as f32) * self.img_scale();
+		let pane_center = self.main_pane_rect().center() - self.view_bounds().pos;
+		self.img_pos = LogicalVector {
+			vec: pane_center.vec - img_point * self.img_scale() + img_size.vec * 0.5,
+		};
+		self.scaling = ScalingMode::Fixed;
+		self.update_scaling_buttons();
+		self.render_validity.invalidate();
+	}
+
+	/// The selection if there's one, otherwise the whole oriented image
+	fn selection_or_image(&self) -> Option<PixelRect> {
+		self.selection.rect().or_else(|| {
+			let (w, h) = self.get_texture()?.oriented_dimensions();
+			Some(PixelRect { x: 0, y: 0, w, h })
+		})
+	}
+
+	fn set_selection_mode(&mut self, enabled: bool) {
+		self.selection.set_enabled(enabled);
+		self.render_validity.invalidate();
+	}
+
+	/// Returns the coordinates in the oriented image and the color of the pixel under the cursor
+	fn pixel_under_cursor(&self) -> Option<((u32, u32), [u8; 4])> {
+		if !self.main_pane_rect().contains(self.last_mouse_pos) {
+			return None;
+		}
+		let pos = self.window_to_image(self.last_mouse_pos)?;
+		if pos.x < 0.0 || pos.y < 0.0 {
+			return None;
+		}
+		let pos = (pos.x as u32, pos.y as u32);
+		let color = self.inspector.pixel_color(&self.get_texture()?, pos)?;
+		Some((pos, color))
+	}
+
+	fn copy_color_under_cursor(&mut self) {
+		let color = match self.pixel_under_cursor() {
+			Some((_, color)) => color,
+			None => return,
+		};
+		if let Some(clipboard_handler) = &mut self.clipboard_handler {
+			if clipboard_handler.request_copy(ClipboardRequest::Text(inspector::hex_color(color))) {
+				self.copy_notifications.set_started();
+				self.clipboard_request_was_pending = true;
+			}
+		}
+	}
+
+	fn clear_selection(&mut self) {
+		self.selection.clear();
+		self.render_validity.invalidate();
+	}
+
+	fn export_current(&mut self) {
+		let source = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path.clone(),
+			_ => return,
+		};
+		let config = self.configuration.borrow();
+		let settings = match ExportSettings::from_config(config.export.as_ref()) {
+			Ok(settings) => settings,
+			Err(e) => {
+				drop(config);
+				self.message.show(format!("Invalid export configuration: {}", e));
+				self.render_validity.invalidate();
+				return;
+			}
+		};
+		let visible_region = config.export.as_ref().and_then(|e| e.visible_region).unwrap_or(false);
+		let region = if let Some(selection) = self.selection.rect() {
+			Some(selection)
+		} else if visible_region {
+			match self.visible_image_region() {
+				Some(region) => Some(region),
+				None => return,
+			}
+		} else {
+			None
+		};
+		drop(config);
+		let request = ExportRequest { source, view_orientation: self.view_orientation, region };
+		export::export_in_background(request, settings, self.export_sender.clone());
+		self.exports_pending += 1;
+	}
+
+	/// Shows the results of the finished exports
+	fn update_exports(&mut self, now: Instant) -> NextUpdate {
+		while let Ok((source, result)) = self.export_results.try_recv() {
+			self.exports_pending -= 1;
+			let file_name = source.file_name().unwrap_or_default().to_string_lossy();
+			self.message.show(match result {
+				Ok(target) => format!("Exported {} to {}", file_name, target.display()),
+				Err(e) => format!("Could not export {}: {}", file_name, e),
+			});
+			self.render_validity.invalidate();
+		}
+		if self.exports_pending > 0 {
+			NextUpdate::WaitUntil(now + Duration::from_millis(100))
+		} else {
+			NextUpdate::Latest
+		}
+	}
+
+	fn transform_view(&mut self, transform: fn(Orientation) -> Orientation) {
+		self.view_orientation = transform(self.view_orientation);
+		// The selection is in the coordinates of the oriented image
+		self.clear_selection();
 	}
 
 	pub fn set_img_size_to_orig(&mut self) {
@@ -348,6 +994,12 @@ impl PictureWidgetData {
 		self.render_validity.invalidate();
 	}
 
+	pub fn set_img_scaling(&mut self, scaling: ScalingMode) {
+		self.scaling = scaling;
+		self.update_scaling_buttons();
+		self.render_validity.invalidate();
+	}
+
 	pub fn set_img_size_to_fit(&mut self, stretch: bool) {
 		{
 			let mut cache = self.cache.lock().unwrap();
@@ -369,6 +1021,52 @@ impl PictureWidgetData {
 		self.render_validity.invalidate();
 	}
 
+	fn cycle_background(&mut self) {
+		self.background = self.background.next();
+		self.cache.lock().unwrap().image.background = self.background;
+		self.render_validity.invalidate();
+	}
+
+	/// Returns the two colors of the background checkerboard and the size of its squares in
+	/// physical pixels. Both colors are the same for a solid background.
+	fn background_checkerboard(&self, dpi_scale: f32) -> ([f32; 4], [f32; 4], f32) {
+		let colors = &self.background_colors;
+		match self.background {
+			BackgroundMode::Theme => {
+				let shade = self.bright_shade;
+				let dark_shade = shade * 0.55;
+				(
+					[shade, shade, shade, 1.0],
+					[dark_shade, dark_shade, dark_shade, 1.0],
+					THEME_SQUARE_SIZE,
+				)
+			}
+			BackgroundMode::Checkerboard => (
+				colors.checkerboard[0],
+				colors.checkerboard[1],
+				colors.checkerboard_size * dpi_scale,
+			),
+			BackgroundMode::Solid => (colors.solid, colors.solid, THEME_SQUARE_SIZE),
+		}
+	}
+
+	/// Returns the opacity of the pixel grid at the given zoom level. The grid fades in
+	/// between the configured zoom level and one and a half times that.
+	fn pixel_grid_alpha(&self, texel_size: f32) -> f32 {
+		if !self.pixel_grid {
+			return 0.0;
+		}
+		let threshold = self
+			.configuration
+			.borrow()
+			.image
+			.as_ref()
+			.and_then(|i| i.pixel_grid_zoom)
+			.unwrap_or(DEFAULT_PIXEL_GRID_ZOOM)
+			.max(1.0);
+		((texel_size - threshold) / (threshold * 0.5)).clamp(0.0, 1.0)
+	}
+
 	pub fn set_automatic_antialias(&mut self) {
 		self.antialiasing = Antialias::Auto;
 		self.cache.lock().unwrap().image.antialiasing = Antialias::Auto;
@@ -385,7 +1083,7 @@ impl PictureWidgetData {
 			let img_w = img_phys_w / dpi_scale;
 			let img_h = img_phys_h / dpi_scale;
 
-			let widget_size = self.drawn_bounds.size.vec;
+			let widget_size = self.view_bounds().size.vec;
 			let img_pos = self.img_pos.vec;
 
 			if img_pos.x < -img_w / 2.0 {
@@ -404,6 +1102,190 @@ impl PictureWidgetData {
 		}
 	}
 
+	/// Sends the shown image to the destination bound to the key. Returns false if no
+	/// destination is bound to it.
+	fn send_to_destinations(&mut self, input_key: &str, modifiers: ModifiersState) -> bool {
+		let destination = {
+			let config = self.configuration.borrow();
+			let destinations = config.destinations.as_deref().unwrap_or_default();
+			destinations.iter().find(|d| keys_triggered(&d.input, input_key, modifiers)).cloned()
+		};
+		let destination = match destination {
+			Some(destination) => destination,
+			None => return false,
+		};
+		let img_path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path.clone(),
+			_ => return true,
+		};
+		let file_name = img_path.file_name().unwrap_or_default().to_string_lossy();
+		match destinations::transfer(&img_path, &destination) {
+			Ok(TransferOutcome::Moved(moved)) => {
+				self.message.show(format!("Moved {} to {}", file_name, moved.to.display()));
+				if self.undo_stack.len() == MAX_UNDO_MOVES {
+					self.undo_stack.remove(0);
+				}
+				self.undo_stack.push(moved);
+				// The moved file is gone from the folder, so updating the directory
+				// selects the image that took its place.
+				if let Err(e) = self.playback_manager.update_directory() {
+					eprintln!("Error while updating directory {:?}", e);
+				}
+			}
+			Ok(TransferOutcome::Copied) => {
+				// The folder of the image didn't change
+				self.message.show(format!(
+					"Copied {} to {}",
+					file_name,
+					destination.path.display()
+				));
+				self.playback_manager.request_load(LoadRequest::LoadNext);
+			}
+			Ok(TransferOutcome::Skipped(target)) => {
+				self.message.show(format!(
+					"Skipped {}, {} already exists",
+					file_name,
+					target.display()
+				));
+			}
+			Err(e) => {
+				self.message.show(format!(
+					"Could not send {} to {}: {}",
+					file_name,
+					destination.path.display(),
+					e
+				));
+			}
+		}
+		self.render_validity.invalidate();
+		true
+	}
+
+	fn undo_last_move(&mut self) {
+		if let Some(moved) = self.undo_stack.pop() {
+			if let Err(e) = destinations::undo_move(&moved) {
+				self.message.show(format!(
+					"Could not move {} back to {}: {}",
+					moved.to.display(),
+					moved.from.display(),
+					e
+				));
+				self.render_validity.invalidate();
+				return;
+			}
+			if let Err(e) = self.playback_manager.update_directory() {
+				eprintln!("Error while updating directory {:?}", e);
+			}
+			self.playback_manager.request_load(LoadRequest::FilePath(moved.from));
+			self.render_validity.invalidate();
+		}
+	}
+
+	/// Rotates or flips the view, and the file too if `save_rotation` is on
+	fn rotate_or_flip(&mut self, transform: FileTransform) {
+		let config = self.configuration.borrow();
+		let save_rotation = config.image.as_ref().and_then(|i| i.save_rotation).unwrap_or(false);
+		drop(config);
+		if save_rotation {
+			self.transform_current_file(transform);
+		} else {
+			self.transform_view(match transform {
+				FileTransform::RotateCw => Orientation::rotated_cw,
+				FileTransform::RotateCcw => Orientation::rotated_ccw,
+				FileTransform::FlipHorizontal => Orientation::flipped_h,
+			});
+		}
+	}
+
+	fn transform_current_file(&mut self, transform: FileTransform) {
+		if let LoadedImgPath::Loaded(path) = self.playback_manager.shown_file_path() {
+			if let Err(e) = file_transform::transform_file(path, transform) {
+				let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+				self.message.show(format!("Could not save the rotation of {}: {}", file_name, e));
+				self.render_validity.invalidate();
+				return;
+			}
+			if let Err(e) = self.playback_manager.reload_current() {
+				eprintln!("Error while updating directory {:?}", e);
+			}
+			self.render_validity.invalidate();
+		}
+	}
+
+	/// Shows the folder of the current image in the file manager. The image is selected on
+	/// Windows and macOS.
+	fn open_containing_folder(&self) {
+		let img_path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path,
+			_ => return,
+		};
+		let mut command;
+		if cfg!(target_os = "windows") {
+			command = process::Command::new("explorer");
+			command.arg("/select,").arg(img_path);
+		} else if cfg!(target_os = "macos") {
+			command = process::Command::new("open");
+			command.arg("-R").arg(img_path);
+		} else {
+			command = process::Command::new("xdg-open");
+			command.arg(img_path.parent().unwrap_or(img_path));
+		}
+		if let Err(e) = command.spawn() {
+			eprintln!("Could not open the folder of {:?}: {:?}", img_path, e);
+		}
+	}
+
+	fn start_rename(&mut self) {
+		if let LoadedImgPath::Loaded(path) = self.playback_manager.shown_file_path() {
+			let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+			let extension = match path.extension() {
+				Some(extension) => format!(".{}", extension.to_string_lossy()),
+				None => String::new(),
+			};
+			self.rename_input.set_text(stem);
+			self.rename_input.set_suffix(extension);
+			self.rename_input.set_message(None);
+			self.rename_input.set_visible(true);
+			// The siblings of the picture widget must not react to the typed keys either
+			if let Some(window) = self.window.upgrade() {
+				window.set_focus(Some(self.rename_input.clone()));
+			}
+		}
+	}
+
+	fn rename_current_file(&mut self, new_name: &str) {
+		let path = match self.playback_manager.shown_file_path() {
+			LoadedImgPath::Loaded(path) => path.clone(),
+			_ => {
+				self.rename_input.set_visible(false);
+				return;
+			}
+		};
+		let folder = path.parent().unwrap_or_else(|| path::Path::new(""));
+		let new_path = folder.join(new_name);
+		let error = if new_name.trim().is_empty() {
+			Some("The name cannot be empty".to_owned())
+		} else if new_name.contains(|ch| ch == '/' || path::is_separator(ch)) {
+			Some("The name cannot contain a path separator".to_owned())
+		} else if new_path.exists() && !is_same_file(&new_path, &path) {
+			Some(format!("\"{}\" already exists", new_name))
+		} else if new_path == path {
+			None
+		} else {
+			fs::rename(&path, &new_path).err().map(|e| format!("Could not rename: {}", e))
+		};
+		if let Some(error) = error {
+			self.rename_input.set_message(Some(error));
+			return;
+		}
+		self.rename_input.set_visible(false);
+		if let Err(e) = self.playback_manager.update_directory() {
+			eprintln!("Error while updating directory {:?}", e);
+		}
+		self.playback_manager.request_load(LoadRequest::FilePath(new_path));
+		self.render_validity.invalidate();
+	}
+
 	fn update_scaling_buttons(&mut self) {
 		self.bottom_bar.update_scaling_buttons(self.scaling, self.img_texel_size);
 	}
@@ -411,14 +1293,24 @@ impl PictureWidgetData {
 
 pub struct PictureWidget {
 	data: RefCell<PictureWidgetData>,
+	/// Replaces this widget when the `gallery` action is triggered
+	gallery: RefCell<Option<Rc<Gallery>>>,
+	/// Receives the keyboard input instead of this widget while it's visible
+	help_screen: RefCell<Option<Rc<HelpScreen>>>,
+	/// Receives the keyboard input instead of this widget while it's visible
+	command_palette: RefCell<Option<Rc<CommandPalette>>>,
+	/// Receives the keyboard input and the clicks instead of this widget while it's open
+	context_menu: RefCell<Option<Rc<ContextMenu>>>,
 }
 impl PictureWidget {
+	#[allow(clippy::too_many_arguments)]
 	pub fn new(
 		display: &Display,
 		window: &Rc<Window>,
 		bottom_bar: Rc<BottomBar>,
-		left_to_pan_hint: Rc<HelpScreen>,
+		left_to_pan_hint: Rc<Hint>,
 		copy_notifications: CopyNotifications,
+		rename_input: Rc<TextInput>,
 		configuration: Rc<RefCell<Configuration>>,
 		cache: Arc<Mutex<Cache>>,
 	) -> PictureWidget {
@@ -474,6 +1366,11 @@ impl PictureWidget {
 			}
 		};
 
+		let background = cache.lock().unwrap().image.background;
+		let background_colors =
+			BackgroundColors::from_config(configuration.borrow().background.as_ref());
+		let (export_sender, export_results) = mpsc::channel();
+
 		let mut data = PictureWidgetData {
 			placement: Default::default(),
 			drawn_bounds: Default::default(),
@@ -486,14 +1383,29 @@ impl PictureWidget {
 			playback_manager: PlaybackManager::new(),
 			clipboard_handler: Some(ClipboardHandler::new()),
 			clipboard_request_was_pending: false,
+			undo_stack: Vec::new(),
 			render_validity: Default::default(),
 
 			program,
 			bright_shade: 0.95,
+			dpi_scale: 1.0,
 			img_texel_size: 0.0,
 			scaling,
 			img_pos: Default::default(),
 			antialiasing,
+			background,
+			background_colors,
+			view_orientation: Orientation::Deg0,
+			selection: Default::default(),
+			view_locked: false,
+			inspector: Default::default(),
+			pixel_grid: false,
+			channel_view: ChannelView::Rgb,
+			premultiplied: false,
+			compare: None,
+			reference: None,
+			histogram: None,
+			metadata: Default::default(),
 			hor_pan_input: MovementDir::None,
 			ver_pan_input: MovementDir::None,
 			zoom_input: MovementDir::None,
@@ -512,10 +1424,23 @@ impl PictureWidget {
 			bottom_bar,
 			left_to_pan_hint,
 			copy_notifications,
+			message: Default::default(),
+			export_sender,
+			export_results,
+			exports_pending: 0,
+			rename_input,
+			filmstrip: None,
+			minimap: Default::default(),
 			window: Rc::downgrade(window),
 		};
 		data.update_scaling_buttons();
-		PictureWidget { data: RefCell::new(data) }
+		PictureWidget {
+			data: RefCell::new(data),
+			gallery: Default::default(),
+			help_screen: Default::default(),
+			command_palette: Default::default(),
+			context_menu: Default::default(),
+		}
 	}
 
 	add_common_widget_functions!(data);
@@ -536,25 +1461,118 @@ impl PictureWidget {
 		borrowed.set_img_size_to_fit(stretch);
 	}
 
+	pub fn set_gallery(&self, gallery: Rc<Gallery>) {
+		*self.gallery.borrow_mut() = Some(gallery);
+	}
+
+	pub fn set_help_screen(&self, help_screen: Rc<HelpScreen>) {
+		*self.help_screen.borrow_mut() = Some(help_screen);
+	}
+
+	pub fn set_command_palette(&self, command_palette: Rc<CommandPalette>) {
+		*self.command_palette.borrow_mut() = Some(command_palette);
+	}
+
+	pub fn set_context_menu(&self, context_menu: Rc<ContextMenu>) {
+		*self.context_menu.borrow_mut() = Some(context_menu);
+	}
+
+	pub fn set_filmstrip(&self, filmstrip: Rc<Filmstrip>) {
+		self.data.borrow_mut().filmstrip = Some(filmstrip);
+	}
+
+	/// Hands the thumbnails of the images loaded for display to `thumbnails`
+	pub fn share_thumbnails(&self, thumbnails: Rc<RefCell<ThumbnailCache>>) {
+		self.data.borrow_mut().playback_manager.set_thumbnails(thumbnails);
+	}
+
+	/// Shows the image at `index` of the current folder after it was chosen in the gallery
+	pub fn open_from_gallery(&self, index: usize) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.visible = true;
+		borrowed.playback_manager.request_load(LoadRequest::LoadAtIndex(index));
+		borrowed.render_validity.invalidate();
+	}
+
+	/// Replaces this widget with the gallery of the current folder, or closes the gallery if
+	/// it's open
+	fn toggle_gallery(&self) {
+		let gallery = match self.gallery.borrow().clone() {
+			Some(gallery) => gallery,
+			None => return,
+		};
+		let mut borrowed = self.data.borrow_mut();
+		if gallery.visible() {
+			gallery.set_visible(false);
+			borrowed.visible = true;
+			borrowed.render_validity.invalidate();
+			return;
+		}
+		let paths = match borrowed.playback_manager.image_paths() {
+			Some(paths) if !paths.is_empty() => paths,
+			_ => return,
+		};
+		let selected = borrowed.playback_manager.current_file_index().unwrap_or(0);
+		borrowed.visible = false;
+		borrowed.render_validity.invalidate();
+		drop(borrowed);
+		gallery.show(paths, selected);
+	}
+
 	pub fn jump_to_index(&self, index: u32) {
 		let mut borrowed = self.data.borrow_mut();
 		borrowed.playback_manager.request_load(LoadRequest::LoadAtIndex(index as usize));
 		borrowed.render_validity.invalidate();
 	}
 
+	/// Enters compare mode with the image at `path` in the second pane
+	pub fn compare_with_path<P: Into<PathBuf>>(&self, path: P) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.start_compare(Some(path.into()));
+	}
+
 	pub fn jump_to_path<P: Into<PathBuf>>(&self, path: P) {
 		let mut borrowed = self.data.borrow_mut();
 		borrowed.playback_manager.request_load(LoadRequest::FilePath(path.into()));
 		borrowed.render_validity.invalidate();
 	}
 
-	fn handle_key_input(&self, input_key: &str, modifiers: ModifiersState) {
+	/// Renames the current file to `new_name` in its folder and keeps it selected.
+	///
+	/// Problems, like a file with the same name already existing, are displayed in the rename
+	/// input instead of closing it.
+	pub fn rename_current_file(&self, new_name: &str) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.rename_current_file(new_name);
+	}
+
+	pub fn cancel_rename(&self) {
+		let borrowed = self.data.borrow();
+		borrowed.rename_input.set_visible(false);
+	}
+
+	/// Runs an action or a custom command picked from the command palette or the context menu
+	pub fn run_action(&self, action: &PaletteAction) {
+		if *action == PaletteAction::Action(GALLERY_NAME.into()) {
+			self.toggle_gallery();
+		} else {
+			self.run_actions(Trigger::Palette(action));
+		}
+	}
+
+	fn run_actions(&self, trigger: Trigger) {
 		let mut borrowed = self.data.borrow_mut();
 		macro_rules! triggered {
 			($action_name:ident) => {
-				action_triggered(&borrowed.configuration, $action_name, input_key, modifiers)
+				trigger.action(&borrowed.configuration, $action_name)
 			};
 		}
+		// A key that sends the image to a destination doesn't run any other action
+		if let Trigger::Key { input_key, modifiers } = trigger {
+			if borrowed.send_to_destinations(input_key, modifiers) {
+				return;
+			}
+		}
 		if triggered!(TOGGLE_FULLSCREEN_NAME) {
 			if let Some(window) = borrowed.window.upgrade() {
 				let fullscreen = !window.fullscreen();
@@ -563,7 +1581,11 @@ impl PictureWidget {
 			}
 		}
 		if triggered!(ESCAPE_NAME) {
-			if let Some(window) = borrowed.window.upgrade() {
+			if borrowed.selection.enabled() {
+				borrowed.set_selection_mode(false);
+			} else if borrowed.compare.is_some() {
+				borrowed.toggle_compare();
+			} else if let Some(window) = borrowed.window.upgrade() {
 				if window.fullscreen() {
 					window.set_fullscreen(false);
 					borrowed.bottom_bar.set_visible_if_should_show(true);
@@ -572,6 +1594,16 @@ impl PictureWidget {
 				}
 			}
 		}
+		if triggered!(HELP_NAME) {
+			if let Some(help_screen) = self.help_screen.borrow().as_ref() {
+				help_screen.toggle();
+			}
+		}
+		if triggered!(COMMAND_PALETTE_NAME) {
+			if let Some(command_palette) = self.command_palette.borrow().as_ref() {
+				command_palette.open();
+			}
+		}
 		if triggered!(PLAY_ANIM_NAME) {
 			match borrowed.playback_manager.playback_state() {
 				PlaybackState::Forward => borrowed.playback_manager.pause_playback(),
@@ -592,6 +1624,21 @@ impl PictureWidget {
 		if triggered!(IMG_FIT_BEST_NAME) {
 			borrowed.set_img_size_to_fit(false);
 		}
+		if triggered!(LOCK_VIEW_NAME) {
+			borrowed.toggle_view_lock();
+		}
+		if triggered!(IMG_FIT_WIDTH_NAME) {
+			borrowed.set_img_scaling(ScalingMode::FitWidth);
+		}
+		if triggered!(IMG_FIT_HEIGHT_NAME) {
+			borrowed.set_img_scaling(ScalingMode::FitHeight);
+		}
+		for argument in trigger.arguments(&borrowed.configuration, ZOOM_TO_NAME) {
+			match argument.trim().trim_end_matches('%').parse::<f32>() {
+				Ok(percentage) if percentage > 0.0 => borrowed.zoom_to(percentage / 100.0),
+				_ => eprintln!("Invalid zoom percentage {:?} for {}", argument, ZOOM_TO_NAME),
+			}
+		}
 		if triggered!(IMG_ORIG_NAME) {
 			borrowed.set_img_size_to_orig();
 		}
@@ -601,6 +1648,50 @@ impl PictureWidget {
 		if triggered!(SET_AUTOMATIC_ANTIALIAS_NAME) {
 			borrowed.set_automatic_antialias();
 		}
+		if triggered!(COMPARE_NAME) {
+			borrowed.toggle_compare();
+		}
+		if triggered!(COMPARE_LAYOUT_NAME) {
+			borrowed.cycle_compare_layout();
+		}
+		if triggered!(COMPARE_NEXT_NAME) {
+			borrowed.compare_request_load(LoadRequest::LoadNext);
+		}
+		if triggered!(COMPARE_PREV_NAME) {
+			borrowed.compare_request_load(LoadRequest::LoadPrevious);
+		}
+		if triggered!(PIN_REFERENCE_NAME) {
+			borrowed.toggle_reference();
+		}
+		if triggered!(REFERENCE_MODE_NAME) {
+			borrowed.cycle_reference_mode();
+		}
+		if triggered!(HISTOGRAM_NAME) {
+			borrowed.toggle_histogram();
+		}
+		if triggered!(FILMSTRIP_NAME) {
+			if let Some(filmstrip) = &borrowed.filmstrip {
+				filmstrip.set_visible(!filmstrip.visible());
+			}
+		}
+		if triggered!(METADATA_NAME) {
+			borrowed.toggle_metadata();
+		}
+		if triggered!(CYCLE_CHANNEL_NAME) {
+			borrowed.channel_view = borrowed.channel_view.next();
+			borrowed.render_validity.invalidate();
+		}
+		if triggered!(PREMULTIPLIED_ALPHA_NAME) {
+			borrowed.premultiplied = !borrowed.premultiplied;
+			borrowed.render_validity.invalidate();
+		}
+		if triggered!(CYCLE_BACKGROUND_NAME) {
+			borrowed.cycle_background();
+		}
+		if triggered!(PIXEL_GRID_NAME) {
+			borrowed.pixel_grid = !borrowed.pixel_grid;
+			borrowed.render_validity.invalidate();
+		}
 		if triggered!(PLAY_PRESENT_NAME) {
 			match borrowed.playback_manager.playback_state() {
 				PlaybackState::Present => borrowed.playback_manager.pause_playback(),
@@ -626,6 +1717,50 @@ impl PictureWidget {
 				borrowed.render_validity.invalidate();
 			}
 		}
+		if triggered!(RENAME_NAME) {
+			borrowed.start_rename();
+		}
+		if triggered!(OPEN_FOLDER_NAME) {
+			borrowed.open_containing_folder();
+		}
+		if triggered!(UNDO_NAME) {
+			borrowed.undo_last_move();
+		}
+		if triggered!(SELECT_NAME) {
+			let enabled = !borrowed.selection.enabled();
+			borrowed.set_selection_mode(enabled);
+		}
+		if triggered!(INSPECT_NAME) {
+			borrowed.inspector.toggle();
+			borrowed.render_validity.invalidate();
+		}
+		if triggered!(COPY_COLOR_NAME) {
+			borrowed.copy_color_under_cursor();
+		}
+		if triggered!(EXPORT_NAME) {
+			borrowed.export_current();
+		}
+		if triggered!(VIEW_ROTATE_CW_NAME) {
+			borrowed.transform_view(Orientation::rotated_cw);
+		}
+		if triggered!(VIEW_ROTATE_CCW_NAME) {
+			borrowed.transform_view(Orientation::rotated_ccw);
+		}
+		if triggered!(VIEW_FLIP_H_NAME) {
+			borrowed.transform_view(Orientation::flipped_h);
+		}
+		if triggered!(VIEW_FLIP_V_NAME) {
+			borrowed.transform_view(Orientation::flipped_v);
+		}
+		if triggered!(ROTATE_CW_NAME) {
+			borrowed.rotate_or_flip(FileTransform::RotateCw);
+		}
+		if triggered!(ROTATE_CCW_NAME) {
+			borrowed.rotate_or_flip(FileTransform::RotateCcw);
+		}
+		if triggered!(FLIP_H_NAME) {
+			borrowed.rotate_or_flip(FileTransform::FlipHorizontal);
+		}
 		if triggered!(EXIT) {
 			request_exit();
 		}
@@ -633,9 +1768,14 @@ impl PictureWidget {
 			if let LoadedImgPath::Loaded(path) = borrowed.playback_manager.shown_file_path().clone()
 			{
 				let request_started;
+				let request = ExportRequest {
+					source: path,
+					view_orientation: borrowed.view_orientation,
+					region: borrowed.selection.rect(),
+				};
 				if let Some(clipboard_handler) = &mut borrowed.clipboard_handler {
 					request_started = true;
-					clipboard_handler.request_copy(path);
+					clipboard_handler.request_copy(ClipboardRequest::Image(request));
 					borrowed.copy_notifications.set_started();
 				} else {
 					request_started = false;
@@ -651,10 +1791,10 @@ impl PictureWidget {
 				if let (Some(img_path), Some(folder_path)) = img_and_folder {
 					execute_triggered_commands(
 						borrowed.configuration.clone(),
-						input_key,
-						modifiers,
+						trigger,
 						img_path,
 						folder_path,
+						borrowed.selection_or_image(),
 					);
 				} else {
 					log::error!("Could not convert the image path to utf8. Path: '{:?}'", img_path);
@@ -666,6 +1806,98 @@ impl PictureWidget {
 	}
 }
 
+impl PictureWidget {
+	fn draw_selection(&self, target: &mut Frame, context: &DrawContext) {
+		let data = self.data.borrow();
+		let rect = data.selection.rect().and_then(|selection| data.image_rect_to_window(selection));
+		if let Some(rect) = rect {
+			data.selection.draw(target, context, rect, data.drawn_bounds);
+		}
+	}
+
+	fn draw_compare_overlay(&self, target: &mut Frame, context: &DrawContext) {
+		let data = self.data.borrow();
+		if let Some(compare) = &data.compare {
+			let main_path = data.playback_manager.shown_file_path();
+			compare.draw(target, context, data.drawn_bounds, main_path);
+		}
+	}
+
+	fn draw_reference_summary(&self, target: &mut Frame, context: &DrawContext) {
+		let data = self.data.borrow();
+		if let Some(reference) = &data.reference {
+			reference.draw_summary(target, context, data.main_pane_rect());
+		}
+	}
+
+	/// Draws the drawn channels and the alpha mode in the bottom left corner of the main pane
+	/// unless the image is drawn as usual
+	fn draw_channel_label(&self, target: &mut Frame, context: &DrawContext) {
+		let data = self.data.borrow();
+		if data.channel_view == ChannelView::Rgb && !data.premultiplied {
+			return;
+		}
+		let text = if data.premultiplied {
+			format!("{}   ·   premultiplied alpha", data.channel_view.name())
+		} else {
+			data.channel_view.name().to_owned()
+		};
+		let label_size = overlay::label_size(context, &text);
+		let pane = data.main_pane_rect();
+		let pos =
+			LogicalVector::new(pane.left(), (pane.bottom() - label_size.vec.y).max(pane.top()));
+		overlay::draw_label(target, context, &text, pos, pane, overlay::LABEL_COLOR);
+	}
+
+	fn draw_minimap(&self, target: &mut Frame, context: &DrawContext) {
+		let data = self.data.borrow();
+		let (minimap, scale) = match data.minimap_rect() {
+			Some(minimap) => minimap,
+			None => return,
+		};
+		let (texture, viewport) = match (data.get_texture(), data.minimap_viewport(minimap, scale))
+		{
+			(Some(texture), Some(viewport)) => (texture, viewport),
+			_ => return,
+		};
+		let pane = data.main_pane_rect();
+		minimap::draw(target, context, minimap, viewport, pane, |target| {
+			let view =
+				ImageView { pos: minimap.size * 0.5, texel_size: scale * context.dpi_scale_factor };
+			draw_tex_grid(&data, target, context, texture, None, view, minimap, minimap);
+		});
+	}
+
+	fn draw_histogram(&self, target: &mut Frame, context: &DrawContext) {
+		let data = self.data.borrow();
+		if let Some(histogram) = &data.histogram {
+			histogram.draw(target, context, data.main_pane_rect());
+		}
+	}
+
+	fn draw_metadata(&self, target: &mut Frame, context: &DrawContext) {
+		let data = self.data.borrow();
+		let mut area = data.main_pane_rect();
+		if data.reference.is_some() {
+			// Below the summary of the reference
+			let top = (area.top() + reference::SUMMARY_HEIGHT).min(area.bottom());
+			area = rect_from_edges(area.left(), top, area.right(), area.bottom());
+		}
+		data.metadata.draw(target, context, area);
+	}
+
+	fn draw_inspector(&self, target: &mut Frame, context: &DrawContext) {
+		let data = self.data.borrow();
+		if !data.inspector.enabled() {
+			return;
+		}
+		if let Some((pos, color)) = data.pixel_under_cursor() {
+			let bounds = data.drawn_bounds;
+			data.inspector.draw(target, context, bounds, data.last_mouse_pos, pos, color);
+		}
+	}
+}
+
 impl Widget for PictureWidget {
 	fn before_draw(&self, window: &Window) -> NextUpdate {
 		let mut data = self.data.borrow_mut();
@@ -682,22 +1914,43 @@ impl Widget for PictureWidget {
 		}
 		let now = Instant::now();
 		let prev_texture = data.playback_manager.image_texture();
+		let prev_path = data.playback_manager.shown_file_path().clone();
+		let prev_relative_center = data.relative_center();
 		data.next_update = data.playback_manager.update_image(window);
 		let new_texture = data.playback_manager.image_texture();
+		if prev_path != *data.playback_manager.shown_file_path() {
+			data.clear_selection();
+			let keep_view_orientation = data
+				.configuration
+				.borrow()
+				.image
+				.as_ref()
+				.and_then(|i| i.keep_view_orientation)
+				.unwrap_or(false);
+			if !keep_view_orientation && !data.view_locked {
+				data.view_orientation = Orientation::Deg0;
+			}
+			if let (true, Some(relative_center)) = (data.view_locked, prev_relative_center) {
+				data.set_relative_center(relative_center);
+			}
+		}
+		if let Some(compare) = &mut data.compare {
+			let (changed, next_update) = compare.update(window);
+			data.next_update = data.next_update.aggregate(next_update);
+			if changed {
+				data.render_validity.invalidate();
+			}
+		}
 		let curr_file_index = data.playback_manager.current_file_index();
 		let curr_dir_len = data.playback_manager.current_dir_len();
 		if let (Some(curr_file_index), Some(curr_dir_len)) = (curr_file_index, curr_dir_len) {
 			// dbg!(curr_file_index);
 			// dbg!(curr_dir_len);
 			data.bottom_bar.slider.set_steps(curr_dir_len as u32, curr_file_index as u32);
+			data.update_filmstrip(curr_file_index);
 		}
 		//data.slider.set_step_bg(data.playback_manager.cached_from_dir());
-		let playback_state = data.playback_manager.playback_state();
-		data.set_window_title_filename(
-			window,
-			playback_state,
-			data.playback_manager.shown_file_path(),
-		);
+		data.update_title_and_status(window, curr_file_index, curr_dir_len);
 		if prev_texture.is_none() != new_texture.is_none() {
 			data.render_validity.invalidate();
 		} else if let (Some(prev_tex), Some(new_tex)) = (prev_texture, new_texture) {
@@ -723,13 +1976,23 @@ impl Widget for PictureWidget {
 			data.render_validity.invalidate();
 			data.next_update = NextUpdate::Soonest;
 		}
+		let next_reference_update = data.update_reference(now);
+		data.next_update = data.next_update.aggregate(next_reference_update);
+		let next_panel_update = data.update_file_panels(now);
+		data.next_update = data.next_update.aggregate(next_panel_update);
 		let next_copy_noti_update = data.copy_notifications.update();
 		data.next_update = data.next_update.aggregate(next_copy_noti_update);
+		let next_export_update = data.update_exports(now);
+		data.next_update = data.next_update.aggregate(next_export_update);
+		let (message_hidden, next_message_update) = data.message.update(now);
+		if message_hidden {
+			data.render_validity.invalidate();
+		}
+		data.next_update = data.next_update.aggregate(next_message_update);
 		data.next_update
 	}
 
 	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
-		let texture;
 		{
 			let mut data = self.data.borrow_mut();
 			if !data.visible {
@@ -737,13 +2000,40 @@ impl Widget for PictureWidget {
 			}
 			data.update_image_transform(context.dpi_scale_factor);
 			data.apply_camera_movement(context.dpi_scale_factor);
-			texture = data.get_texture();
 		}
-		if let Some(texture) = texture {
+		{
 			let data = self.data.borrow();
-			draw_tex_grid(data, target, context, texture);
+			if let Some((texture, reference)) = data.main_pane_textures() {
+				draw_tex_grid(
+					&data,
+					target,
+					context,
+					texture,
+					reference.as_ref(),
+					data.image_view(),
+					data.view_bounds(),
+					data.main_pane_rect(),
+				);
+			}
+			let compare_texture = data.get_compare_texture();
+			if let (Some(texture), Some(bounds), Some(clip)) =
+				(compare_texture, data.compare_bounds(), data.compare_pane_rect())
+			{
+				let view = data.image_view();
+				draw_tex_grid(&data, target, context, texture, None, view, bounds, clip);
+			}
 		}
+		self.draw_compare_overlay(target, context);
+		self.draw_selection(target, context);
+		self.draw_inspector(target, context);
+		self.draw_reference_summary(target, context);
+		self.draw_histogram(target, context);
+		self.draw_metadata(target, context);
+		self.draw_channel_label(target, context);
+		self.draw_minimap(target, context);
 		let borrowed = self.data.borrow();
+		borrowed.message.draw(target, context, borrowed.drawn_bounds);
+		borrowed.rename_input.draw(target, context)?;
 		Ok(borrowed.next_update)
 	}
 
@@ -751,9 +2041,50 @@ impl Widget for PictureWidget {
 		let mut borrowed = self.data.borrow_mut();
 		borrowed.default_layout(available_space);
 		borrowed.hover = borrowed.drawn_bounds.contains(borrowed.last_mouse_pos);
+		borrowed.rename_input.layout(borrowed.drawn_bounds);
 	}
 
 	fn handle_event(&self, event: &Event) {
+		// The command palette is a sibling that gets the same keys
+		let palette_visible = self.command_palette.borrow().as_ref().is_some_and(|p| p.visible());
+		if let (true, EventKind::KeyInput { .. }) = (palette_visible, &event.kind) {
+			return;
+		}
+		// The context menu is a sibling too and closes on a click outside of it
+		let menu_open = self.context_menu.borrow().as_ref().is_some_and(|m| m.visible());
+		if menu_open
+			&& matches!(event.kind, EventKind::KeyInput { .. } | EventKind::MouseButton { .. })
+		{
+			return;
+		}
+		let help_screen = self.help_screen.borrow().clone();
+		if let (Some(help_screen), EventKind::KeyInput { ref input }) = (help_screen, &event.kind) {
+			// The help screen handles the keys itself, except the one that closes it
+			if help_screen.visible() {
+				let key = input.key_without_modifiers();
+				let input_key_str =
+					char_to_input_key(&virtual_keycode_to_string(&key).to_lowercase());
+				let configuration = self.data.borrow().configuration.clone();
+				if input.state == ElementState::Pressed
+					&& action_triggered(&configuration, HELP_NAME, &input_key_str, event.modifiers)
+				{
+					help_screen.toggle();
+				}
+				return;
+			}
+		}
+		// The gallery is toggled from here even while this widget is hidden behind it
+		if let EventKind::KeyInput { ref input } = event.kind {
+			let key = input.key_without_modifiers();
+			let input_key_str = char_to_input_key(&virtual_keycode_to_string(&key).to_lowercase());
+			let configuration = self.data.borrow().configuration.clone();
+			if input.state == ElementState::Pressed
+				&& action_triggered(&configuration, GALLERY_NAME, &input_key_str, event.modifiers)
+			{
+				self.toggle_gallery();
+				return;
+			}
+		}
 		if !self.data.borrow().visible {
 			return;
 		}
@@ -761,6 +2092,32 @@ impl Widget for PictureWidget {
 			EventKind::MouseMove => {
 				let mut borrowed = self.data.borrow_mut();
 				borrowed.hover = borrowed.drawn_bounds.contains(event.cursor_pos);
+				if let Some(pos) = borrowed.minimap.drag_to(event.cursor_pos) {
+					borrowed.center_minimap_pos(pos);
+					borrowed.last_mouse_pos = event.cursor_pos;
+					return;
+				}
+				let bounds = borrowed.drawn_bounds;
+				if let Some(compare) = &mut borrowed.compare {
+					if compare.drag_divider(bounds, event.cursor_pos) {
+						borrowed.last_mouse_pos = event.cursor_pos;
+						borrowed.render_validity.invalidate();
+						return;
+					}
+				}
+				if borrowed.inspector.enabled() {
+					borrowed.render_validity.invalidate();
+				}
+				if borrowed.selection.enabled() {
+					let cursor_pos = borrowed.to_main_pane(event.cursor_pos);
+					let cursor = borrowed.window_to_image(cursor_pos);
+					let image_size = borrowed.get_texture().map(|t| t.oriented_dimensions());
+					if let (Some(cursor), Some(image_size)) = (cursor, image_size) {
+						if borrowed.selection.drag_to(cursor, image_size) {
+							borrowed.render_validity.invalidate();
+						}
+					}
+				}
 				if borrowed.panning_2d || borrowed.panning_hor || borrowed.panning_vert {
 					let mut delta = event.cursor_pos - borrowed.last_mouse_pos;
 					if !borrowed.panning_2d {
@@ -783,7 +2140,35 @@ impl Widget for PictureWidget {
 			EventKind::MouseButton { state, button, .. } => match button {
 				MouseButton::Left => {
 					let mut borrowed = self.data.borrow_mut();
-					if state == ElementState::Pressed {
+					let pressed = state == ElementState::Pressed;
+					if !pressed && borrowed.minimap.release() {
+						return;
+					}
+					let minimap = borrowed.minimap_rect().and_then(|(minimap, scale)| {
+						let viewport = borrowed.minimap_viewport(minimap, scale)?;
+						minimap.contains(event.cursor_pos).then_some(viewport)
+					});
+					if let (true, Some(viewport)) = (pressed, minimap) {
+						let pos = borrowed.minimap.press(event.cursor_pos, viewport);
+						borrowed.center_minimap_pos(pos);
+						return;
+					}
+					let bounds = borrowed.drawn_bounds;
+					let grab = pressed && borrowed.hover;
+					if let Some(compare) = &mut borrowed.compare {
+						if compare.grab_divider(bounds, event.cursor_pos, grab) {
+							return;
+						}
+					}
+					if borrowed.selection.enabled() {
+						if state == ElementState::Pressed && borrowed.hover {
+							let cursor_pos = borrowed.to_main_pane(event.cursor_pos);
+							let anchor = borrowed.window_to_image(cursor_pos);
+							borrowed.selection.start_drag(anchor);
+						} else {
+							borrowed.selection.end_drag();
+						}
+					} else if state == ElementState::Pressed {
 						if borrowed.hover {
 							borrowed.click = true;
 							borrowed.panning_2d = true
@@ -811,16 +2196,34 @@ impl Widget for PictureWidget {
 					borrowed.render_validity.invalidate();
 				}
 				MouseButton::Right => {
-					let borrowed = self.data.borrow();
 					let pressed = state == ElementState::Pressed;
-					borrowed.left_to_pan_hint.set_visible(pressed);
+					let (right_button, hover) = {
+						let borrowed = self.data.borrow();
+						let right_button = borrowed.configuration.borrow().right_button();
+						(right_button, borrowed.hover)
+					};
+					match right_button {
+						RightButton::Menu if !pressed && hover => {
+							if let Some(context_menu) = self.context_menu.borrow().as_ref() {
+								context_menu.open(event.cursor_pos);
+							}
+						}
+						RightButton::PanHint => {
+							self.data.borrow().left_to_pan_hint.set_visible(pressed);
+						}
+						RightButton::Action(name) if pressed && hover => {
+							self.run_action(&PaletteAction::Action(name));
+						}
+						_ => {}
+					}
 				}
 				_ => {}
 			},
 			EventKind::MouseScroll { delta } => {
 				let mut borrowed = self.data.borrow_mut();
 				let delta = delta.vec.y * 0.375;
-				borrowed.zoom_image(event.cursor_pos, delta);
+				let anchor = borrowed.to_main_pane(event.cursor_pos) - borrowed.view_bounds().pos;
+				borrowed.zoom_image(anchor, delta);
 			}
 			EventKind::KeyInput { ref input } => {
 				let key = input.key_without_modifiers();
@@ -829,7 +2232,8 @@ impl Widget for PictureWidget {
 				let input_key_str = virtual_keycode_to_string(&key).to_lowercase();
 				let input_key_str = char_to_input_key(&input_key_str);
 				if is_pressed {
-					self.handle_key_input(input_key_str.as_str(), event.modifiers);
+					let input_key = input_key_str.as_str();
+					self.run_actions(Trigger::Key { input_key, modifiers: event.modifiers });
 				}
 				// Panning is a special snowflake
 				let mut borrowed = self.data.borrow_mut();
@@ -919,12 +2323,22 @@ impl Widget for PictureWidget {
 			}
 			EventKind::DroppedFile(ref path) => {
 				let mut borrowed = self.data.borrow_mut();
-				borrowed.playback_manager.request_load(LoadRequest::FilePath(path.clone()));
+				let over_compare_pane =
+					borrowed.compare_pane_rect().is_some_and(|r| r.contains(event.cursor_pos));
+				if over_compare_pane {
+					borrowed.compare_request_load(LoadRequest::FilePath(path.clone()));
+				} else {
+					borrowed.playback_manager.request_load(LoadRequest::FilePath(path.clone()));
+				}
 				borrowed.hover_state = HoverState::None;
 				borrowed.render_validity.invalidate();
 			}
 			EventKind::HoveredFile(ref path) => {
 				let mut borrowed = self.data.borrow_mut();
+				if borrowed.compare.is_some() {
+					// The file is loaded into the pane that it's dropped on
+					return;
+				}
 				match borrowed.hover_state {
 					HoverState::None => {
 						let curr_path = borrowed.playback_manager.shown_file_path().clone();
@@ -969,8 +2383,9 @@ impl Widget for PictureWidget {
 		}
 	}
 
-	// No children for a button
-	fn children(&self, _children: &mut Vec<Rc<dyn Widget>>) {}
+	fn children(&self, children: &mut Vec<Rc<dyn Widget>>) {
+		children.push(self.data.borrow().rename_input.clone());
+	}
 
 	fn placement(&self) -> WidgetPlacement {
 		self.data.borrow().placement
@@ -981,7 +2396,9 @@ impl Widget for PictureWidget {
 	}
 
 	fn set_valid_ref(&self, render_validity: RenderValidity) {
-		self.data.borrow_mut().render_validity = render_validity;
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.rename_input.set_valid_ref(render_validity.clone());
+		borrowed.render_validity = render_validity;
 	}
 }
 
@@ -993,26 +2410,55 @@ impl Drop for PictureWidget {
 	}
 }
 
+/// Returns true if both paths point to the same file. This is the case for example when
+/// only the letter case differs on a case-insensitive file system.
+fn is_same_file(a: &path::Path, b: &path::Path) -> bool {
+	match (fs::canonicalize(a), fs::canonicalize(b)) {
+		(Ok(a), Ok(b)) => a == b,
+		_ => false,
+	}
+}
+
+fn image_sampler(tex: &SrgbTexture2d, filter: MagnifySamplerFilter) -> Sampler<'_, SrgbTexture2d> {
+	tex.sampled()
+		.minify_filter(gelatin::glium::uniforms::MinifySamplerFilter::LinearMipmapLinear)
+		.wrap_function(gelatin::glium::uniforms::SamplerWrapFunction::Clamp)
+		.magnify_filter(filter)
+}
+
+/// Draws the image positioned relative to `bounds`. Nothing is drawn outside of `clip`.
+///
+/// The difference to `reference` is drawn instead of the image if it's specified. It must have
+/// the same size as `texture`.
+#[allow(clippy::too_many_arguments)]
 fn draw_tex_grid(
-	data: Ref<PictureWidgetData>,
+	data: &PictureWidgetData,
 	target: &mut Frame,
 	context: &DrawContext,
 	texture: AnimationFrameTexture,
+	reference: Option<&AnimationFrameTexture>,
+	view: ImageView,
+	bounds: LogicalRect,
+	clip: LogicalRect,
 ) {
-	let size = data.drawn_bounds.size.vec;
+	let size = bounds.size.vec;
 	let projection_transform = gelatin::cgmath::ortho(0.0, size.x, size.y, 0.0, -1.0, 1.0);
 
-	let viewport_rect = context.logical_rect_to_viewport(&data.drawn_bounds);
-	let image_draw_params =
-		gelatin::glium::DrawParameters { viewport: Some(viewport_rect), ..Default::default() };
+	let viewport_rect = context.logical_rect_to_viewport(&bounds);
+	let scissor_rect = context.logical_rect_to_viewport(&clip);
+	let image_draw_params = gelatin::glium::DrawParameters {
+		viewport: Some(viewport_rect),
+		scissor: Some(scissor_rect),
+		..Default::default()
+	};
 
 	let img_phys_w = texture.w as f32;
 	let img_phys_h = texture.h as f32;
 	let img_height_over_width = img_phys_h / img_phys_w;
-	let image_display_width = data.img_texel_size * img_phys_w / context.dpi_scale_factor;
+	let image_display_width = view.texel_size * img_phys_w / context.dpi_scale_factor;
 	let image_display_height = image_display_width * img_height_over_width;
 	// Model tranform
-	let img_pyhs_pos = data.img_pos.vec * context.dpi_scale_factor;
+	let img_pyhs_pos = view.pos.vec * context.dpi_scale_factor;
 	let img_phys_siz = {
 		let img_phys_w = image_display_width * context.dpi_scale_factor;
 		let img_phys_h = image_display_height * context.dpi_scale_factor;
@@ -1048,6 +2494,9 @@ fn draw_tex_grid(
 
 	// let img_logical_w = img_w / context.dpi_scale_factor;
 	// let img_logical_h = img_h / context.dpi_scale_factor;
+	let pixel_grid_alpha = data.pixel_grid_alpha(view.texel_size);
+	let (bg_color_a, bg_color_b, bg_square_size) =
+		data.background_checkerboard(context.dpi_scale_factor);
 	let cell_phy_step = texture.cell_step_size;
 	for cell_tex in texture.tex_grid.iter() {
 		let (cell_phys_w, cell_phys_h) = cell_tex.tex.dimensions();
@@ -1075,28 +2524,34 @@ fn draw_tex_grid(
 		// Projection tranform
 		let transform = projection_transform * transform;
 
-		let sampler = cell_tex
-			.tex
-			.sampled()
-			.minify_filter(gelatin::glium::uniforms::MinifySamplerFilter::LinearMipmapLinear)
-			.wrap_function(gelatin::glium::uniforms::SamplerWrapFunction::Clamp);
-
 		let filter = match data.antialiasing {
-			Antialias::Auto if data.img_texel_size < AA_TEXEL_SIZE_THRESHOLD => {
+			Antialias::Auto if view.texel_size < AA_TEXEL_SIZE_THRESHOLD => {
 				MagnifySamplerFilter::Linear
 			}
 			Antialias::Auto | Antialias::Never => MagnifySamplerFilter::Nearest,
 			Antialias::Always => MagnifySamplerFilter::Linear,
 		};
-		let sampler = sampler.magnify_filter(filter);
+		let sampler = image_sampler(&cell_tex.tex, filter);
+		// The cells of images with the same size line up
+		let ref_cell = reference.and_then(|reference| {
+			reference.tex_grid.iter().find(|c| c.col == cell_tex.col && c.row == cell_tex.row)
+		});
+		let ref_sampler = image_sampler(ref_cell.map_or(&cell_tex.tex, |c| &c.tex), filter);
 
 		// building the uniforms
-		let lod_level = ((1.0 / data.img_texel_size).log2().max(0.0) + 0.125).floor();
+		let lod_level = ((1.0 / view.texel_size).log2().max(0.0) + 0.125).floor();
 		let uniforms = uniform! {
 			matrix: Into::<[[f32; 4]; 4]>::into(transform),
-			bright_shade: data.bright_shade,
+			bg_color_a: bg_color_a,
+			bg_color_b: bg_color_b,
+			bg_square_size: bg_square_size,
 			tex: sampler,
+			ref_tex: ref_sampler,
+			show_difference: ref_cell.is_some(),
 			lod_level: lod_level,
+			pixel_grid_alpha: pixel_grid_alpha,
+			channel: data.channel_view.shader_index(),
+			premultiplied: data.premultiplied,
 		};
 		target
 			.draw(
@@ -0,0 +1,164 @@
+//! A pinned image that the shown images are compared against
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use gelatin::glium::Frame;
+use gelatin::misc::{LogicalRect, LogicalVector};
+use gelatin::{DrawContext, NextUpdate};
+
+use crate::image_cache::{image_loader::Orientation, AnimationFrameTexture};
+use crate::image_diff::{self, DiffInput, DiffOutput};
+use crate::parallel_action::ParallelAction;
+
+use super::overlay;
+
+/// How long the reference and the image are shown in turns in flicker mode
+const FLICKER_INTERVAL: Duration = Duration::from_millis(500);
+/// The height of the summary drawn by `Reference::draw_summary`, in logical pixels
+pub const SUMMARY_HEIGHT: f32 = 28.0;
+
+/// How the pinned reference image is used
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ReferenceMode {
+	/// The difference between the image and the reference is drawn as a heatmap
+	Difference,
+	/// The image and the reference are shown in turns
+	Flicker,
+	/// The image is drawn as usual, only the statistics of the difference are shown
+	Statistics,
+}
+
+/// An image that other images are compared against
+pub struct Reference {
+	path: PathBuf,
+	texture: AnimationFrameTexture,
+	mode: ReferenceMode,
+	/// The flicker starts with the image at this time
+	mode_start: Instant,
+	diff_action: ParallelAction<DiffInput, DiffOutput>,
+	/// The image that the statistics are being computed for
+	pending: Option<PathBuf>,
+	stats: Option<DiffOutput>,
+}
+
+impl Reference {
+	/// Pins the image at `path` whose texture is `texture`
+	pub fn new(path: PathBuf, texture: AnimationFrameTexture) -> Self {
+		Reference {
+			diff_action: ParallelAction::new(image_diff::diff_worker(path.clone())),
+			path,
+			texture,
+			mode: ReferenceMode::Difference,
+			mode_start: Instant::now(),
+			pending: None,
+			stats: None,
+		}
+	}
+
+	pub fn cycle_mode(&mut self) {
+		self.mode = match self.mode {
+			ReferenceMode::Difference => ReferenceMode::Flicker,
+			ReferenceMode::Flicker => ReferenceMode::Statistics,
+			ReferenceMode::Statistics => ReferenceMode::Difference,
+		};
+		self.mode_start = Instant::now();
+	}
+
+	/// Collects the statistics computed on the worker thread, starts computing them for the
+	/// image at `path` if needed and schedules the next flicker. Returns true if the widget
+	/// has to be drawn again.
+	pub fn update(&mut self, path: Option<PathBuf>, now: Instant) -> (bool, NextUpdate) {
+		let mut redraw = false;
+		let mut next_update = NextUpdate::Latest;
+		if let Some(output) = self.diff_action.try_get_output() {
+			if self.pending.as_ref() == Some(&output.0) {
+				self.pending = None;
+			}
+			self.stats = Some(output);
+			redraw = true;
+		}
+		if let Some(path) = path {
+			let stats_path = self.stats.as_ref().map(|(path, _)| path);
+			if Some(&path) != stats_path && Some(&path) != self.pending.as_ref() {
+				self.stats = None;
+				self.pending = Some(path.clone());
+				self.diff_action.give_input(path);
+			}
+		}
+		if self.pending.is_some() {
+			next_update =
+				next_update.aggregate(NextUpdate::WaitUntil(now + Duration::from_millis(100)));
+		}
+		if self.mode == ReferenceMode::Flicker {
+			let elapsed = now.duration_since(self.mode_start);
+			let into_phase =
+				Duration::from_nanos((elapsed.as_nanos() % FLICKER_INTERVAL.as_nanos()) as u64);
+			next_update =
+				next_update.aggregate(NextUpdate::WaitUntil(now + FLICKER_INTERVAL - into_phase));
+			redraw = true;
+		}
+		(redraw, next_update)
+	}
+
+	/// Returns the texture to draw instead of `texture` and the reference texture if the
+	/// difference to it should be drawn. `texture` has the view orientation applied already.
+	pub fn textures(
+		&self,
+		texture: AnimationFrameTexture,
+		view_orientation: Orientation,
+		now: Instant,
+	) -> (AnimationFrameTexture, Option<AnimationFrameTexture>) {
+		let mut reference_texture = self.texture.clone();
+		reference_texture.orientation = reference_texture.orientation.followed_by(view_orientation);
+		if self.flicker_shows_reference(now) {
+			return (reference_texture, None);
+		}
+		if self.mode == ReferenceMode::Difference && self.matches(&texture) {
+			return (texture, Some(reference_texture));
+		}
+		(texture, None)
+	}
+
+	/// Returns true if the reference is drawn instead of the image at the moment
+	fn flicker_shows_reference(&self, now: Instant) -> bool {
+		let phase = now.duration_since(self.mode_start).as_millis() / FLICKER_INTERVAL.as_millis();
+		self.mode == ReferenceMode::Flicker && phase % 2 == 1
+	}
+
+	/// Returns true if the pixels of the texture can be compared to the reference on the GPU
+	fn matches(&self, texture: &AnimationFrameTexture) -> bool {
+		let reference = &self.texture;
+		reference.w == texture.w
+			&& reference.h == texture.h
+			&& reference.cell_step_size == texture.cell_step_size
+			&& reference.orientation == texture.orientation
+	}
+
+	/// Draws the comparison mode and the statistics of the difference to the reference in the
+	/// top right corner of `pane`
+	pub fn draw_summary(&self, target: &mut Frame, context: &DrawContext, pane: LogicalRect) {
+		let reference_name = self.path.file_name().unwrap_or_default().to_string_lossy();
+		let mode = match self.mode {
+			ReferenceMode::Difference => format!("Difference to {}", reference_name),
+			ReferenceMode::Flicker if self.flicker_shows_reference(Instant::now()) => {
+				format!("Showing the reference {}", reference_name)
+			}
+			ReferenceMode::Flicker => "Showing the current image".to_owned(),
+			ReferenceMode::Statistics => format!("Reference {}", reference_name),
+		};
+		let stats = match &self.stats {
+			Some((_, Ok(stats))) => {
+				format!("max {}   mean {:.2}   PSNR {:.2} dB", stats.max, stats.mean, stats.psnr)
+			}
+			Some((_, Err(e))) => format!("can not compare: {}", e),
+			None if self.pending.is_some() => "comparing…".to_owned(),
+			None => String::new(),
+		};
+		let text = if stats.is_empty() { mode } else { format!("{}   ·   {}", mode, stats) };
+		let label_size = overlay::label_size(context, &text);
+		let pos =
+			LogicalVector::new((pane.right() - label_size.vec.x).max(pane.left()), pane.top());
+		overlay::draw_label(target, context, &text, pos, pane, overlay::LABEL_COLOR);
+	}
+}
@@ -0,0 +1,146 @@
+//! Selecting a region of the image by dragging over it, for copying, exporting or passing it
+//! to commands
+
+use gelatin::cgmath::Vector2;
+use gelatin::glium::Frame;
+use gelatin::misc::{LogicalRect, LogicalVector};
+use gelatin::DrawContext;
+
+use crate::export::PixelRect;
+
+use super::overlay;
+
+#[derive(Default)]
+pub struct Selection {
+	/// Dragging with the left mouse button selects a region instead of panning
+	enabled: bool,
+	/// The selected region in the pixels of the oriented image
+	rect: Option<PixelRect>,
+	/// The image position where the current drag started
+	anchor: Option<Vector2<f32>>,
+}
+
+impl Selection {
+	pub fn enabled(&self) -> bool {
+		self.enabled
+	}
+
+	/// Turns selection mode on or off, either way the current selection is dropped
+	pub fn set_enabled(&mut self, enabled: bool) {
+		self.enabled = enabled;
+		self.clear();
+	}
+
+	pub fn rect(&self) -> Option<PixelRect> {
+		self.rect
+	}
+
+	pub fn clear(&mut self) {
+		self.rect = None;
+		self.anchor = None;
+	}
+
+	/// Drops the current selection and starts a new one from the point of the oriented image
+	pub fn start_drag(&mut self, anchor: Option<Vector2<f32>>) {
+		self.rect = None;
+		self.anchor = anchor;
+	}
+
+	pub fn end_drag(&mut self) {
+		self.anchor = None;
+	}
+
+	/// Spans the selection from the start of the drag to `cursor` in an image of `image_size`.
+	/// Returns false if there's no drag going on.
+	pub fn drag_to(&mut self, cursor: Vector2<f32>, image_size: (u32, u32)) -> bool {
+		match self.anchor {
+			Some(anchor) => {
+				self.rect = rect_between(anchor, cursor, image_size);
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Draws the outline of the selection and its size. `rect` is the area the selection covers
+	/// in the window, nothing is drawn outside of `bounds`.
+	pub fn draw(
+		&self,
+		target: &mut Frame,
+		context: &DrawContext,
+		rect: LogicalRect,
+		bounds: LogicalRect,
+	) {
+		const BORDER: f32 = 1.0;
+		let selection = match self.rect {
+			Some(selection) => selection,
+			None => return,
+		};
+		overlay::draw_outline(target, context, rect, BORDER, bounds);
+		let text = format!("{} × {}", selection.w, selection.h);
+		let label_size = overlay::label_size(context, &text);
+		// Below the selection if there's space, otherwise inside of it
+		let mut pos = LogicalVector::new(rect.left(), rect.bottom() + 2.0 * BORDER);
+		if pos.vec.y + label_size.vec.y > bounds.bottom() {
+			pos.vec.y = rect.bottom() - label_size.vec.y;
+		}
+		let max = bounds.pos + bounds.size - label_size;
+		pos.vec.x = pos.vec.x.clamp(bounds.left(), max.vec.x.max(bounds.left()));
+		pos.vec.y = pos.vec.y.clamp(bounds.top(), max.vec.y.max(bounds.top()));
+		overlay::draw_label(target, context, &text, pos, bounds, [0.0, 0.0, 0.0, 0.8]);
+	}
+}
+
+/// Returns the rectangle spanned by the two corners clamped to an image of `image_size`, or
+/// `None` if the rectangle doesn't contain a single pixel.
+pub fn rect_between(
+	a: Vector2<f32>,
+	b: Vector2<f32>,
+	(img_w, img_h): (u32, u32),
+) -> Option<PixelRect> {
+	let x0 = a.x.min(b.x).floor().max(0.0) as u32;
+	let y0 = a.y.min(b.y).floor().max(0.0) as u32;
+	let x1 = a.x.max(b.x).ceil().min(img_w as f32).max(0.0) as u32;
+	let y1 = a.y.max(b.y).ceil().min(img_h as f32).max(0.0) as u32;
+	if x1 <= x0 || y1 <= y0 {
+		return None;
+	}
+	Some(PixelRect { x: x0, y: y0, w: x1 - x0, h: y1 - y0 })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rect_covers_the_touched_pixels() {
+		let rect = rect_between(Vector2::new(5.5, 8.2), Vector2::new(1.2, 2.0), (100, 100));
+		assert_eq!(rect, Some(PixelRect { x: 1, y: 2, w: 5, h: 7 }));
+	}
+
+	#[test]
+	fn rect_is_clamped_to_the_image() {
+		let rect = rect_between(Vector2::new(-10.0, 20.0), Vector2::new(150.0, 80.0), (100, 50));
+		assert_eq!(rect, Some(PixelRect { x: 0, y: 20, w: 100, h: 30 }));
+	}
+
+	#[test]
+	fn rect_outside_of_the_image_is_empty() {
+		assert_eq!(rect_between(Vector2::new(-5.0, 0.0), Vector2::new(-1.0, 9.0), (10, 10)), None);
+		assert_eq!(rect_between(Vector2::new(3.0, 4.0), Vector2::new(3.0, 9.0), (10, 10)), None);
+	}
+
+	#[test]
+	fn dragging_needs_a_start() {
+		let mut selection = Selection::default();
+		assert!(!selection.drag_to(Vector2::new(4.0, 4.0), (10, 10)));
+		selection.start_drag(Some(Vector2::new(1.0, 1.0)));
+		assert!(selection.drag_to(Vector2::new(4.0, 4.0), (10, 10)));
+		assert_eq!(selection.rect(), Some(PixelRect { x: 1, y: 1, w: 3, h: 3 }));
+		selection.end_drag();
+		assert!(!selection.drag_to(Vector2::new(8.0, 8.0), (10, 10)));
+		assert_eq!(selection.rect(), Some(PixelRect { x: 1, y: 1, w: 3, h: 3 }));
+		selection.set_enabled(false);
+		assert_eq!(selection.rect(), None);
+	}
+}
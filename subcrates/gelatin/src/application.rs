@@ -12,7 +12,7 @@ use winit::{
 	window::WindowId,
 };
 
-use crate::{window::Window, NextUpdate};
+use crate::{text::Font, window::Window, NextUpdate};
 
 // const MAX_SLEEP_DURATION: std::time::Duration = std::time::Duration::from_millis(4);
 static EXIT_REQUESTED: AtomicBool = AtomicBool::new(false);
@@ -43,11 +43,9 @@ fn aggregate_control_flow<E>(event_loop: &EventLoopWindowTarget<E>, new: Control
 			return true;
 		}
 		ControlFlow::WaitUntil(new_time) => match original {
-			ControlFlow::WaitUntil(orig_time) => {
-				if new_time < orig_time {
-					set_control_flow(event_loop, new);
-					return true;
-				}
+			ControlFlow::WaitUntil(orig_time) if new_time < orig_time => {
+				set_control_flow(event_loop, new);
+				return true;
 			}
 			ControlFlow::Wait => {
 				set_control_flow(event_loop, new);
@@ -87,6 +85,7 @@ where
 	UserEvent: Debug + 'static,
 {
 	pub fn new() -> Self {
+		Font::load_system_default_in_background();
 		Application {
 			event_loop: EventLoopBuilder::<UserEvent>::with_user_event().build().unwrap(),
 			windows: HashMap::new(),
@@ -0,0 +1,498 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use glium::Frame;
+use winit::event::{ElementState, MouseButton};
+use winit::keyboard::{Key, NamedKey};
+
+use crate::add_common_widget_functions;
+use crate::misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement};
+use crate::window::RenderValidity;
+use crate::NextUpdate;
+use crate::{DrawContext, Event, EventKind, Widget, WidgetData, WidgetError};
+
+/// The space above the first and below the last item
+const PADDING: f32 = 4.0;
+/// The space left and right of the text of an item
+const ITEM_PADDING: f32 = 12.0;
+const ITEM_HEIGHT: f32 = 26.0;
+const SEPARATOR_HEIGHT: f32 = 9.0;
+const MIN_WIDTH: f32 = 160.0;
+/// The space between the label and the shortcut of an item
+const SHORTCUT_GAP: f32 = 32.0;
+/// Shown on the right of the items that open a submenu
+const SUBMENU_ARROW: &str = "›";
+const BORDER: f32 = 1.0;
+
+/// An entry of a `ContextMenu`
+pub enum MenuItem {
+	Action {
+		label: String,
+		/// Shown on the right side of the item, eg the key that does the same
+		shortcut: Option<String>,
+		on_click: Rc<dyn Fn()>,
+	},
+	Submenu {
+		label: String,
+		items: Vec<MenuItem>,
+	},
+	/// A horizontal line between two groups of items
+	Separator,
+}
+
+impl MenuItem {
+	/// Feel free to use `RefCell`s within the callback to satisfy the apparent constness
+	/// of the callback.
+	pub fn action<S: Into<String>, T: Fn() + 'static>(label: S, on_click: T) -> MenuItem {
+		MenuItem::Action { label: label.into(), shortcut: None, on_click: Rc::new(on_click) }
+	}
+
+	pub fn submenu<S: Into<String>>(label: S, items: Vec<MenuItem>) -> MenuItem {
+		MenuItem::Submenu { label: label.into(), items }
+	}
+
+	/// Sets the shortcut of an action, other items are returned as they are
+	pub fn with_shortcut(mut self, new_shortcut: Option<String>) -> MenuItem {
+		if let MenuItem::Action { shortcut, .. } = &mut self {
+			*shortcut = new_shortcut;
+		}
+		self
+	}
+
+	fn height(&self) -> f32 {
+		match self {
+			MenuItem::Separator => SEPARATOR_HEIGHT,
+			_ => ITEM_HEIGHT,
+		}
+	}
+}
+
+/// A menu or a submenu that is currently shown
+struct OpenMenu {
+	/// Where the top left corner of the menu goes if the menu fits there
+	pos: LogicalVector,
+	/// Where the right edge goes if the menu doesn't fit on the right side, and where the
+	/// bottom edge goes if it doesn't fit below
+	flip: LogicalVector,
+	/// Calculated when the menu is drawn, because that's when the size of the text is known
+	rect: LogicalRect,
+	highlighted: Option<usize>,
+}
+
+impl OpenMenu {
+	fn new(pos: LogicalVector, flip: LogicalVector) -> OpenMenu {
+		OpenMenu { pos, flip, rect: Default::default(), highlighted: None }
+	}
+}
+
+struct ContextMenuData {
+	placement: WidgetPlacement,
+	drawn_bounds: LogicalRect,
+	visible: bool,
+
+	items: Vec<MenuItem>,
+	/// The menu opened at the cursor followed by the open submenus. The submenu after each
+	/// menu belongs to the highlighted item of that menu.
+	open: Vec<OpenMenu>,
+
+	/// In logical pixels
+	font_size: f32,
+	text_color: [f32; 4],
+	shortcut_color: [f32; 4],
+	bg_color: [f32; 4],
+	highlight_color: [f32; 4],
+
+	render_validity: RenderValidity,
+}
+impl WidgetData for ContextMenuData {
+	fn placement(&mut self) -> &mut WidgetPlacement {
+		&mut self.placement
+	}
+	fn drawn_bounds(&mut self) -> &mut LogicalRect {
+		&mut self.drawn_bounds
+	}
+	fn visible(&mut self) -> &mut bool {
+		&mut self.visible
+	}
+}
+
+impl ContextMenuData {
+	/// The items of the open menu at `level`, 0 being the menu opened at the cursor
+	fn items(&self, level: usize) -> &[MenuItem] {
+		let mut items = self.items.as_slice();
+		for menu in &self.open[..level] {
+			match menu.highlighted.map(|index| &items[index]) {
+				Some(MenuItem::Submenu { items: submenu_items, .. }) => items = submenu_items,
+				_ => return &[],
+			}
+		}
+		items
+	}
+
+	fn item_rect(&self, level: usize, index: usize) -> LogicalRect {
+		let menu = self.open[level].rect;
+		let items = self.items(level);
+		let top = menu.top() + PADDING + items[..index].iter().map(MenuItem::height).sum::<f32>();
+		LogicalRect {
+			pos: LogicalVector::new(menu.left(), top),
+			size: LogicalVector::new(menu.size.vec.x, items[index].height()),
+		}
+	}
+
+	/// The level and the index of the item under `pos`, the submenus are on top
+	fn item_at(&self, pos: LogicalVector) -> Option<(usize, Option<usize>)> {
+		let level =
+			(0..self.open.len()).rev().find(|&level| self.open[level].rect.contains(pos))?;
+		let index = (0..self.items(level).len())
+			.find(|&index| self.item_rect(level, index).contains(pos))
+			.filter(|&index| !matches!(self.items(level)[index], MenuItem::Separator));
+		Some((level, index))
+	}
+
+	/// Highlights an item of the menu at `level` and closes the submenus after it. If the
+	/// item has a submenu, the submenu is opened.
+	fn highlight(&mut self, level: usize, index: Option<usize>) {
+		self.open.truncate(level + 1);
+		self.open[level].highlighted = index;
+		if let Some(index) = index {
+			if let MenuItem::Submenu { .. } = self.items(level)[index] {
+				let menu = self.open[level].rect;
+				let item = self.item_rect(level, index);
+				let pos = LogicalVector::new(menu.right() - BORDER, item.top() - PADDING);
+				let flip = LogicalVector::new(menu.left() + BORDER, item.bottom() + PADDING);
+				self.open.push(OpenMenu::new(pos, flip));
+			}
+		}
+		self.render_validity.invalidate();
+	}
+
+	/// Moves the highlight of the innermost menu by `step` items, skipping the separators
+	fn move_highlight(&mut self, step: isize) {
+		let level = self.open.len() - 1;
+		let items = self.items(level);
+		let count = items.len() as isize;
+		if count == 0 {
+			return;
+		}
+		let mut index = match self.open[level].highlighted {
+			Some(index) => index as isize,
+			None if step > 0 => -1,
+			None => count,
+		};
+		for _ in 0..count {
+			index = (index + step).rem_euclid(count);
+			if !matches!(items[index as usize], MenuItem::Separator) {
+				break;
+			}
+		}
+		// Keyboard navigation doesn't open submenus until Right or Enter is pressed
+		self.open.truncate(level + 1);
+		self.open[level].highlighted = Some(index as usize);
+		self.render_validity.invalidate();
+	}
+
+	fn close(&mut self) {
+		self.visible = false;
+		self.open.clear();
+		self.render_validity.invalidate();
+	}
+
+	/// Opens the submenu of the item or closes the menu and returns the callback of the item
+	fn activate(&mut self, level: usize, index: usize) -> Option<Rc<dyn Fn()>> {
+		match &self.items(level)[index] {
+			MenuItem::Action { on_click, .. } => {
+				let on_click = on_click.clone();
+				self.close();
+				Some(on_click)
+			}
+			MenuItem::Submenu { .. } => {
+				self.highlight(level, Some(index));
+				None
+			}
+			MenuItem::Separator => None,
+		}
+	}
+
+	/// Calculates the size of the menu at `level` and moves it inside of the widget
+	fn place_menu(&mut self, context: &DrawContext, level: usize) {
+		let font_size = self.font_size;
+		let items = self.items(level);
+		let mut width = MIN_WIDTH;
+		for item in items {
+			let item_width = match item {
+				MenuItem::Action { label, shortcut, .. } => {
+					let shortcut_width = shortcut
+						.as_ref()
+						.map_or(0.0, |s| SHORTCUT_GAP + context.text_width(s, font_size));
+					context.text_width(label, font_size) + shortcut_width
+				}
+				MenuItem::Submenu { label, .. } => {
+					context.text_width(label, font_size)
+						+ SHORTCUT_GAP + context.text_width(SUBMENU_ARROW, font_size)
+				}
+				MenuItem::Separator => 0.0,
+			};
+			width = width.max(item_width + 2.0 * ITEM_PADDING);
+		}
+		let height = items.iter().map(MenuItem::height).sum::<f32>() + 2.0 * PADDING;
+
+		let bounds = self.drawn_bounds;
+		let menu = &mut self.open[level];
+		let mut pos = menu.pos;
+		if pos.vec.x + width > bounds.right() {
+			pos.vec.x = menu.flip.vec.x - width;
+		}
+		if pos.vec.y + height > bounds.bottom() {
+			pos.vec.y = menu.flip.vec.y - height;
+		}
+		pos.vec.x = pos.vec.x.min(bounds.right() - width).max(bounds.left());
+		pos.vec.y = pos.vec.y.min(bounds.bottom() - height).max(bounds.top());
+		menu.rect = LogicalRect { pos, size: LogicalVector::new(width, height) };
+	}
+
+	fn draw_menu(&self, target: &mut Frame, context: &DrawContext, level: usize) {
+		let menu = &self.open[level];
+		let rect = menu.rect.align_to_pixels(context.dpi_scale_factor);
+		context.clear_color(target, self.shortcut_color, Some(rect));
+		let inner = LogicalRect {
+			pos: rect.pos + LogicalVector::new(BORDER, BORDER),
+			size: rect.size - LogicalVector::new(2.0 * BORDER, 2.0 * BORDER),
+		};
+		context.clear_color(target, self.bg_color, Some(inner));
+
+		let font_size = self.font_size;
+		let text_h = context.line_height(font_size);
+		for (index, item) in self.items(level).iter().enumerate() {
+			let item_rect = self.item_rect(level, index);
+			let left = item_rect.left() + ITEM_PADDING;
+			let right = item_rect.right() - ITEM_PADDING;
+			let y = item_rect.top() + (item_rect.size.vec.y - text_h) * 0.5;
+			if menu.highlighted == Some(index) {
+				let highlight = LogicalRect {
+					pos: item_rect.pos + LogicalVector::new(BORDER, 0.0),
+					size: item_rect.size - LogicalVector::new(2.0 * BORDER, 0.0),
+				};
+				context.clear_color(target, self.highlight_color, Some(highlight));
+			}
+			let (label, right_text) = match item {
+				MenuItem::Action { label, shortcut, .. } => (label, shortcut.as_deref()),
+				MenuItem::Submenu { label, .. } => (label, Some(SUBMENU_ARROW)),
+				MenuItem::Separator => {
+					let line = LogicalRect {
+						pos: LogicalVector::new(left, item_rect.top() + SEPARATOR_HEIGHT * 0.5),
+						size: LogicalVector::new(right - left, 1.0),
+					};
+					context.clear_color(target, self.shortcut_color, Some(line));
+					continue;
+				}
+			};
+			let pos = LogicalVector::new(left, y);
+			context.draw_text(target, label, font_size, pos, self.text_color, Some(item_rect));
+			if let Some(text) = right_text {
+				let pos = LogicalVector::new(right - context.text_width(text, font_size), y);
+				let color = self.shortcut_color;
+				context.draw_text(target, text, font_size, pos, color, Some(item_rect));
+			}
+		}
+	}
+}
+
+/// A popup menu that is opened at a point, usually at the cursor after a right click.
+///
+/// The widget should cover the area where the menu may appear, and the menu is moved to stay
+/// inside of it. Items are highlighted by hovering them or with the arrow keys, and submenus
+/// open on the side of their item. The menu is closed when an action is picked, on Escape, or
+/// on a click outside of it. While it's open, the key presses are handled whenever the widget
+/// is visible, so the owner is expected to ignore them meanwhile.
+pub struct ContextMenu {
+	data: RefCell<ContextMenuData>,
+}
+
+impl ContextMenu {
+	pub fn new() -> ContextMenu {
+		let placement = WidgetPlacement {
+			width: Length::Stretch { min: 0.0, max: f32::INFINITY },
+			height: Length::Stretch { min: 0.0, max: f32::INFINITY },
+			horizontal_align: Alignment::Center,
+			vertical_align: Alignment::Center,
+			ignore_layout: true,
+			..Default::default()
+		};
+		ContextMenu {
+			data: RefCell::new(ContextMenuData {
+				placement,
+				drawn_bounds: Default::default(),
+				visible: false,
+				items: Vec::new(),
+				open: Vec::new(),
+				font_size: 14.0,
+				text_color: [0.95, 0.95, 0.95, 1.0],
+				shortcut_color: [0.6, 0.6, 0.6, 1.0],
+				bg_color: [0.12, 0.12, 0.12, 0.97],
+				highlight_color: [0.25, 0.45, 0.75, 0.8],
+				render_validity: Default::default(),
+			}),
+		}
+	}
+
+	add_common_widget_functions!(data);
+
+	/// Closes the menu if it's open
+	pub fn set_items(&self, items: Vec<MenuItem>) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.items = items;
+		borrowed.close();
+	}
+
+	/// Shows the menu with its top left corner at `pos`, or with a different corner at `pos`
+	/// if it doesn't fit
+	pub fn open(&self, pos: LogicalVector) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.visible = true;
+		borrowed.open = vec![OpenMenu::new(pos, pos)];
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn close(&self) {
+		self.data.borrow_mut().close();
+	}
+
+	pub fn set_font_size(&self, font_size: f32) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.font_size = font_size;
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn set_text_color(&self, color: [f32; 4]) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.text_color = color;
+		borrowed.render_validity.invalidate();
+	}
+
+	/// The color of the shortcuts, the separators and the border
+	pub fn set_shortcut_color(&self, color: [f32; 4]) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.shortcut_color = color;
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn set_bg_color(&self, color: [f32; 4]) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.bg_color = color;
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn set_highlight_color(&self, color: [f32; 4]) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.highlight_color = color;
+		borrowed.render_validity.invalidate();
+	}
+
+	fn handle_key_input(&self, event: &Event) -> Option<Rc<dyn Fn()>> {
+		let input = match &event.kind {
+			EventKind::KeyInput { input } if input.state == ElementState::Pressed => input,
+			_ => return None,
+		};
+		let mut borrowed = self.data.borrow_mut();
+		let level = borrowed.open.len().checked_sub(1)?;
+		let highlighted = borrowed.open[level].highlighted;
+		match &input.logical_key {
+			Key::Named(NamedKey::ArrowDown) => borrowed.move_highlight(1),
+			Key::Named(NamedKey::ArrowUp) => borrowed.move_highlight(-1),
+			Key::Named(NamedKey::ArrowRight | NamedKey::Enter | NamedKey::Space) => {
+				let index = highlighted?;
+				let submenu = matches!(borrowed.items(level)[index], MenuItem::Submenu { .. });
+				if submenu {
+					borrowed.highlight(level, Some(index));
+					// The first item is highlighted so that the submenu can be used with the keys
+					borrowed.move_highlight(1);
+				} else if input.logical_key != Key::Named(NamedKey::ArrowRight) {
+					return borrowed.activate(level, index);
+				}
+			}
+			Key::Named(NamedKey::ArrowLeft | NamedKey::Escape) if level > 0 => {
+				borrowed.open.truncate(level);
+				borrowed.render_validity.invalidate();
+			}
+			Key::Named(NamedKey::Escape) => borrowed.close(),
+			_ => (),
+		}
+		None
+	}
+}
+
+impl Default for ContextMenu {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Widget for ContextMenu {
+	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
+		let mut borrowed = self.data.borrow_mut();
+		if !borrowed.visible {
+			return Ok(NextUpdate::Latest);
+		}
+		for level in 0..borrowed.open.len() {
+			borrowed.place_menu(context, level);
+			borrowed.draw_menu(target, context, level);
+		}
+		Ok(NextUpdate::Latest)
+	}
+
+	fn layout(&self, available_space: LogicalRect) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.default_layout(available_space);
+	}
+
+	fn handle_event(&self, event: &Event) {
+		if !self.data.borrow().visible {
+			return;
+		}
+		let callback = match event.kind {
+			EventKind::MouseMove => {
+				let mut borrowed = self.data.borrow_mut();
+				if let Some((level, index)) = borrowed.item_at(event.cursor_pos) {
+					// Don't reopen the submenu of the item while the cursor is on the item
+					if borrowed.open[level].highlighted != index {
+						borrowed.highlight(level, index);
+					}
+				}
+				None
+			}
+			EventKind::MouseButton { state: ElementState::Pressed, button } => {
+				let mut borrowed = self.data.borrow_mut();
+				match borrowed.item_at(event.cursor_pos) {
+					Some((level, Some(index))) if button == MouseButton::Left => {
+						borrowed.activate(level, index)
+					}
+					Some(_) => None,
+					None => {
+						borrowed.close();
+						None
+					}
+				}
+			}
+			EventKind::KeyInput { .. } => self.handle_key_input(event),
+			_ => None,
+		};
+		// Calling the callback after the borrow ended so that it may use this widget
+		if let Some(callback) = callback {
+			callback();
+		}
+	}
+
+	fn children(&self, _children: &mut Vec<Rc<dyn Widget>>) {}
+
+	fn placement(&self) -> WidgetPlacement {
+		self.data.borrow().placement
+	}
+
+	fn visible(&self) -> bool {
+		self.data.borrow().visible
+	}
+
+	fn set_valid_ref(&self, render_validity: RenderValidity) {
+		self.data.borrow_mut().render_validity = render_validity;
+	}
+}
@@ -0,0 +1,252 @@
+//! A texture that holds the rasterized glyphs of the text drawn in a window, so that each
+//! glyph is only rasterized once for a font size, and the buffers the glyphs are drawn from.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use ab_glyph::{point, GlyphId};
+use glium::{
+	backend::Facade,
+	index::{IndexBufferSlice, PrimitiveType},
+	texture::{ClientFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat},
+	IndexBuffer, Rect, VertexBuffer,
+};
+
+use crate::{text::Font, Vertex};
+
+/// The width and height of the atlas texture in pixels
+const ATLAS_SIZE: u32 = 1024;
+/// The empty space around each glyph, so that the neighbours don't bleed in
+const GLYPH_PADDING: u32 = 1;
+/// Glyphs are rasterized at this many horizontal positions within a pixel
+const SUBPIXEL_STEPS: f32 = 4.0;
+/// The number of glyphs the text buffers can hold at first
+const INITIAL_QUAD_CAPACITY: usize = 1024;
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+struct GlyphKey {
+	face: usize,
+	id: GlyphId,
+	/// The bits of the font size in physical pixels
+	px_size: u32,
+	subpixel: u8,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct AtlasGlyph {
+	/// The top left corner of the glyph in the atlas
+	x: u32,
+	y: u32,
+	width: u32,
+	height: u32,
+	/// The top left corner of the glyph relative to its position on the baseline
+	offset: [f32; 2],
+}
+
+/// A glyph of a line of text, ready to be drawn
+#[derive(Debug, Copy, Clone)]
+pub struct GlyphQuad {
+	/// The top left corner and the size in physical pixels, relative to the top left corner of
+	/// the line
+	pub pos: [f32; 2],
+	pub size: [f32; 2],
+	/// The top left and the bottom right texture coordinates in the atlas
+	pub tex_min: [f32; 2],
+	pub tex_max: [f32; 2],
+}
+
+/// Glyphs are packed in rows from the top of the texture. When the texture is full, all
+/// glyphs are dropped and the packing starts over.
+pub struct GlyphAtlas {
+	pub texture: Texture2d,
+	/// `None` for glyphs without an outline, eg a space
+	glyphs: HashMap<GlyphKey, Option<AtlasGlyph>>,
+	/// The position where the next glyph goes
+	cursor_x: u32,
+	cursor_y: u32,
+	/// The height of the tallest glyph in the current row
+	row_height: u32,
+}
+
+impl GlyphAtlas {
+	pub fn new<F: Facade>(facade: &F) -> GlyphAtlas {
+		let texture = Texture2d::empty_with_format(
+			facade,
+			UncompressedFloatFormat::U8,
+			MipmapsOption::NoMipmap,
+			ATLAS_SIZE,
+			ATLAS_SIZE,
+		)
+		.unwrap();
+		GlyphAtlas { texture, glyphs: HashMap::new(), cursor_x: 0, cursor_y: 0, row_height: 0 }
+	}
+
+	/// Rasterizes the glyphs of `text` that aren't in the atlas yet and returns where each
+	/// glyph has to be drawn.
+	pub fn layout_line(&mut self, font: &Font, text: &str, px_size: f32) -> Vec<GlyphQuad> {
+		match self.try_layout_line(font, text, px_size) {
+			Some(quads) => quads,
+			None => {
+				// The previous lines are already drawn so their glyphs can be dropped
+				self.clear();
+				self.try_layout_line(font, text, px_size).unwrap_or_default()
+			}
+		}
+	}
+
+	/// Returns `None` if the atlas got full
+	fn try_layout_line(&mut self, font: &Font, text: &str, px_size: f32) -> Option<Vec<GlyphQuad>> {
+		let mut quads = Vec::with_capacity(text.len());
+		let mut result = Some(());
+		font.layout_line(text, px_size, |mut line_glyph, _| {
+			if result.is_none() {
+				return;
+			}
+			let x = line_glyph.glyph.position.x;
+			let pixel = x.floor();
+			let subpixel = ((x - pixel) * SUBPIXEL_STEPS).floor() as u8;
+			let key = GlyphKey {
+				face: line_glyph.face,
+				id: line_glyph.glyph.id,
+				px_size: px_size.to_bits(),
+				subpixel,
+			};
+			let baseline = line_glyph.glyph.position.y;
+			let entry = match self.glyphs.get(&key) {
+				Some(entry) => *entry,
+				None => {
+					line_glyph.glyph.position = point(subpixel as f32 / SUBPIXEL_STEPS, baseline);
+					let entry = match font.outline_glyph(&line_glyph) {
+						Some(outlined) => {
+							let bounds = outlined.px_bounds();
+							let width = bounds.width() as u32;
+							let height = bounds.height() as u32;
+							let mut coverage = vec![0; (width * height) as usize];
+							outlined.draw(|x, y, c| {
+								if x < width && y < height {
+									let value = (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+									coverage[(y * width + x) as usize] = value;
+								}
+							});
+							let offset = [bounds.min.x, bounds.min.y - baseline];
+							match self.insert(coverage, width, height, offset) {
+								Some(glyph) => Some(glyph),
+								None => {
+									result = None;
+									return;
+								}
+							}
+						}
+						None => None,
+					};
+					self.glyphs.insert(key, entry);
+					entry
+				}
+			};
+			if let Some(glyph) = entry {
+				let size = ATLAS_SIZE as f32;
+				quads.push(GlyphQuad {
+					pos: [pixel + glyph.offset[0], baseline + glyph.offset[1]],
+					size: [glyph.width as f32, glyph.height as f32],
+					tex_min: [glyph.x as f32 / size, glyph.y as f32 / size],
+					tex_max: [
+						(glyph.x + glyph.width) as f32 / size,
+						(glyph.y + glyph.height) as f32 / size,
+					],
+				});
+			}
+		});
+		result.map(|_| quads)
+	}
+
+	/// Uploads the coverage of a glyph. Returns `None` if there's no room left for it.
+	fn insert(
+		&mut self,
+		coverage: Vec<u8>,
+		width: u32,
+		height: u32,
+		offset: [f32; 2],
+	) -> Option<AtlasGlyph> {
+		if width + GLYPH_PADDING > ATLAS_SIZE || height + GLYPH_PADDING > ATLAS_SIZE {
+			// Too large to ever fit, it's not drawn
+			return Some(AtlasGlyph { x: 0, y: 0, width: 0, height: 0, offset });
+		}
+		if self.cursor_x + width + GLYPH_PADDING > ATLAS_SIZE {
+			self.cursor_x = 0;
+			self.cursor_y += self.row_height;
+			self.row_height = 0;
+		}
+		if self.cursor_y + height + GLYPH_PADDING > ATLAS_SIZE {
+			return None;
+		}
+		let glyph = AtlasGlyph { x: self.cursor_x, y: self.cursor_y, width, height, offset };
+		if width > 0 && height > 0 {
+			let raw = RawImage2d { data: coverage.into(), width, height, format: ClientFormat::U8 };
+			let rect = Rect { left: glyph.x, bottom: glyph.y, width, height };
+			self.texture.write(rect, raw);
+		}
+		self.cursor_x += width + GLYPH_PADDING;
+		self.row_height = self.row_height.max(height + GLYPH_PADDING);
+		Some(glyph)
+	}
+
+	fn clear(&mut self) {
+		self.glyphs.clear();
+		self.cursor_x = 0;
+		self.cursor_y = 0;
+		self.row_height = 0;
+	}
+}
+
+/// The vertex buffer the glyph quads are written to and the index buffer that draws them.
+///
+/// The lines of text are written one after the other and the writing starts over from the
+/// beginning when the buffer is full. The buffers are only reallocated for a line that has more
+/// glyphs than the buffer can hold.
+pub struct TextBuffers {
+	vertices: VertexBuffer<Vertex>,
+	/// The two triangles of each quad
+	indices: IndexBuffer<u32>,
+	/// The number of quads written since the writing last started over
+	used: usize,
+}
+
+impl TextBuffers {
+	pub fn new<F: Facade>(facade: &F) -> TextBuffers {
+		Self::with_capacity(facade, INITIAL_QUAD_CAPACITY)
+	}
+
+	fn with_capacity<F: Facade>(facade: &F, quads: usize) -> TextBuffers {
+		let vertices = VertexBuffer::empty_dynamic(facade, quads * 4).unwrap();
+		let indices = (0..quads as u32)
+			.flat_map(|quad| [0, 1, 2, 0, 2, 3].map(|i| quad * 4 + i))
+			.collect::<Vec<_>>();
+		let indices = IndexBuffer::new(facade, PrimitiveType::TrianglesList, &indices).unwrap();
+		TextBuffers { vertices, indices, used: 0 }
+	}
+
+	/// Writes the vertices of the quads, four for each, and returns the range of the vertex
+	/// buffer they were written to.
+	pub fn write<F: Facade>(&mut self, facade: &F, vertices: &[Vertex]) -> Range<usize> {
+		let quads = vertices.len() / 4;
+		let capacity = self.vertices.len() / 4;
+		if quads > capacity {
+			*self = Self::with_capacity(facade, quads.next_power_of_two());
+		} else if self.used + quads > capacity {
+			self.used = 0;
+		}
+		let range = self.used * 4..(self.used + quads) * 4;
+		self.vertices.slice(range.clone()).unwrap().write(vertices);
+		self.used += quads;
+		range
+	}
+
+	pub fn vertices(&self) -> &VertexBuffer<Vertex> {
+		&self.vertices
+	}
+
+	/// The indices that draw `quads` quads from the start of a vertex buffer slice
+	pub fn indices(&self, quads: usize) -> IndexBufferSlice<'_, u32> {
+		self.indices.slice(0..quads * 6).unwrap()
+	}
+}
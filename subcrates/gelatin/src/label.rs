@@ -7,6 +7,7 @@ use glium::{uniform, Frame, Surface};
 use crate::add_common_widget_functions;
 use crate::misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement};
 use crate::picture::Picture;
+use crate::text::Font;
 use crate::window::RenderValidity;
 use crate::NextUpdate;
 use crate::{DrawContext, Event, Widget, WidgetData, WidgetError};
@@ -18,6 +19,11 @@ struct LabelData {
 
 	shadow_size: f32,
 	icon: Option<Rc<Picture>>,
+	text: String,
+	/// In logical pixels
+	font_size: f32,
+	/// The text color of the window is used if this is `None`
+	text_color: Option<[f32; 4]>,
 
 	render_validity: RenderValidity,
 }
@@ -33,6 +39,9 @@ impl WidgetData for LabelData {
 	}
 }
 
+/// The space between the icon and the text
+const ICON_TEXT_GAP: f32 = 4.0;
+
 pub struct Label {
 	data: RefCell<LabelData>,
 }
@@ -46,6 +55,9 @@ impl Label {
 				visible: true,
 				shadow_size: 0.0,
 				icon: None,
+				text: String::new(),
+				font_size: 14.0,
+				text_color: None,
 				render_validity: Default::default(),
 			}),
 		}
@@ -59,6 +71,46 @@ impl Label {
 		borrowed.render_validity.invalidate();
 	}
 
+	/// The text is drawn vertically centered and aligned to the left of the label. If there's
+	/// also an icon, then the icon is drawn as a square on the left and the text after it.
+	pub fn set_text<S: AsRef<str>>(&self, text: S) {
+		let mut borrowed = self.data.borrow_mut();
+		if borrowed.text != text.as_ref() {
+			borrowed.text = text.as_ref().to_owned();
+			borrowed.render_validity.invalidate();
+		}
+	}
+
+	pub fn text(&self) -> String {
+		self.data.borrow().text.clone()
+	}
+
+	/// The width of the text in logical pixels when drawn with `dpi_scale_factor`, or zero if
+	/// no font is available. The icon is not included.
+	pub fn text_width(&self, dpi_scale_factor: f32) -> f32 {
+		let borrowed = self.data.borrow();
+		match Font::system_default() {
+			Some(font) => {
+				let px_size = borrowed.font_size * dpi_scale_factor;
+				font.line_width(&borrowed.text, px_size) / dpi_scale_factor
+			}
+			None => 0.0,
+		}
+	}
+
+	pub fn set_font_size(&self, font_size: f32) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.font_size = font_size;
+		borrowed.render_validity.invalidate();
+	}
+
+	/// Overrides the text color of the window, see `Window::set_text_color`
+	pub fn set_text_color(&self, color: [f32; 4]) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.text_color = Some(color);
+		borrowed.render_validity.invalidate();
+	}
+
 	pub fn set_shadow_size(&self, shadow_size: f32) {
 		let mut borrowed = self.data.borrow_mut();
 		borrowed.shadow_size = shadow_size;
@@ -72,6 +124,21 @@ impl Default for Label {
 	}
 }
 
+impl Label {
+	fn draw_text(
+		&self,
+		target: &mut Frame,
+		context: &DrawContext,
+		data: &LabelData,
+		bounds: LogicalRect,
+	) {
+		let text_h = context.line_height(data.font_size);
+		let pos = bounds.pos + LogicalVector::new(0.0, (bounds.size.vec.y - text_h) * 0.5);
+		let color = data.text_color.unwrap_or(context.text_color);
+		context.draw_text(target, &data.text, data.font_size, pos, color, Some(bounds));
+	}
+}
+
 impl Widget for Label {
 	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
 		use glium::{Blend, BlendingFunction, LinearBlendingFactor};
@@ -82,14 +149,22 @@ impl Widget for Label {
 			}
 
 			let aligned_bounds = borrowed.drawn_bounds.align_to_pixels(context.dpi_scale_factor);
+			let mut text_bounds = aligned_bounds;
+			let mut icon_bounds = aligned_bounds;
+			if borrowed.icon.is_some() && !borrowed.text.is_empty() {
+				let icon_size = aligned_bounds.size.vec.y.min(aligned_bounds.size.vec.x);
+				icon_bounds.size = LogicalVector::new(icon_size, icon_size);
+				let text_offset = (icon_size + ICON_TEXT_GAP).min(aligned_bounds.size.vec.x);
+				text_bounds.pos.vec.x += text_offset;
+				text_bounds.size.vec.x -= text_offset;
+			}
 
-			let img_w = aligned_bounds.size.vec.x;
-			let img_h = aligned_bounds.size.vec.y;
+			let img_w = icon_bounds.size.vec.x;
+			let img_h = icon_bounds.size.vec.y;
 
 			// Model tranform
 			let transform = Matrix4::from_nonuniform_scale(img_w, img_h, 1.0);
-			let transform =
-				Matrix4::from_translation(aligned_bounds.pos.vec.extend(0.0)) * transform;
+			let transform = Matrix4::from_translation(icon_bounds.pos.vec.extend(0.0)) * transform;
 			// Projection
 			let transform = context.projection_transform * transform;
 
@@ -132,7 +207,7 @@ impl Widget for Label {
 						&image_draw_params,
 					)
 					.unwrap();
-			} else {
+			} else if borrowed.text.is_empty() {
 				let uniforms = uniform! {
 					matrix: Into::<[[f32; 4]; 4]>::into(transform),
 					color: [1.0f32, 0.1, 0.5, 0.5],
@@ -152,6 +227,9 @@ impl Widget for Label {
 					)
 					.unwrap();
 			}
+			if !borrowed.text.is_empty() {
+				self.draw_text(target, context, &borrowed, text_bounds);
+			}
 		}
 		Ok(NextUpdate::Latest)
 	}
@@ -6,7 +6,8 @@ pub use image;
 pub use winit;
 
 use std::{
-	any::Any, error::Error, fmt, ops::Deref, path::PathBuf, rc::Rc, time::Instant, vec::Vec,
+	any::Any, cell::RefCell, error::Error, fmt, ops::Deref, path::PathBuf, rc::Rc, time::Instant,
+	vec::Vec,
 };
 
 use winit::{
@@ -17,20 +18,29 @@ use winit::{
 
 use cgmath::{Matrix4, Vector3};
 use glium::{
-	glutin::surface::WindowSurface, implement_vertex, uniform, Blend, BlendingFunction, Frame,
-	IndexBuffer, LinearBlendingFactor, Program, Rect, Surface, VertexBuffer,
+	glutin::surface::WindowSurface,
+	implement_vertex,
+	texture::{SrgbTexture2d, Texture2d},
+	uniform, Blend, BlendingFunction, Frame, IndexBuffer, LinearBlendingFactor, Program, Rect,
+	Surface, VertexBuffer,
 };
 
+use glyph_atlas::{GlyphAtlas, TextBuffers};
 use misc::*;
+use text::Font;
 
 pub mod application;
 pub mod button;
+pub mod context_menu;
+pub mod glyph_atlas;
 pub mod label;
 pub mod line_layout_container;
 pub mod misc;
 pub mod picture;
 pub mod shaders;
 pub mod slider;
+pub mod text;
+pub mod text_input;
 pub mod window;
 
 pub type Display = glium::Display<WindowSurface>;
@@ -358,6 +368,12 @@ pub struct DrawContext<'a> {
 	pub textured_program: &'a Program,
 	pub colored_shadowed_program: &'a Program,
 	pub colored_program: &'a Program,
+	pub text_program: &'a Program,
+	pub glyph_atlas: &'a RefCell<GlyphAtlas>,
+	pub text_buffers: &'a RefCell<TextBuffers>,
+	/// The color of the text of the widgets that don't set their own, see
+	/// `Window::set_text_color`
+	pub text_color: [f32; 4],
 	pub viewport: &'a Rect,
 	pub projection_transform: &'a Matrix4<f32>,
 }
@@ -372,6 +388,198 @@ impl<'a> DrawContext<'a> {
 			height: (rect.size.vec.y * dpi_scale) as u32,
 		}
 	}
+	/// The width of `text` in logical pixels when drawn with `font_size`
+	pub fn text_width(&self, text: &str, font_size: f32) -> f32 {
+		match Font::system_default() {
+			Some(font) => {
+				font.line_width(text, font_size * self.dpi_scale_factor) / self.dpi_scale_factor
+			}
+			None => 0.0,
+		}
+	}
+
+	/// The height of a line of text in logical pixels when drawn with `font_size`
+	pub fn line_height(&self, font_size: f32) -> f32 {
+		match Font::system_default() {
+			Some(font) => {
+				font.line_height(font_size * self.dpi_scale_factor).ceil() / self.dpi_scale_factor
+			}
+			None => 0.0,
+		}
+	}
+
+	/// The width and the height of a line of text in logical pixels
+	pub fn text_size(&self, text: &str, font_size: f32) -> LogicalVector {
+		LogicalVector::new(self.text_width(text, font_size), self.line_height(font_size))
+	}
+
+	/// Draws a line of text with its top left corner at `pos`. The font size is in logical
+	/// pixels, the glyphs are rasterized for the DPI scaling of the window.
+	///
+	/// Nothing outside of `clip` is drawn if it's specified.
+	pub fn draw_text(
+		&self,
+		target: &mut Frame,
+		text: &str,
+		font_size: f32,
+		pos: LogicalVector,
+		color: [f32; 4],
+		clip: Option<LogicalRect>,
+	) {
+		let font = match Font::system_default() {
+			Some(font) => font,
+			None => return,
+		};
+		let dpi_scale = self.dpi_scale_factor;
+		let mut atlas = self.glyph_atlas.borrow_mut();
+		let quads = atlas.layout_line(&font, text, font_size * dpi_scale);
+		if quads.is_empty() {
+			return;
+		}
+		// Snapping to physical pixels keeps the glyphs sharp
+		let corner = (pos.vec * dpi_scale).map(f32::round);
+		let mut vertices = Vec::with_capacity(quads.len() * 4);
+		for quad in &quads {
+			let left = (corner.x + quad.pos[0]) / dpi_scale;
+			let top = (corner.y + quad.pos[1]) / dpi_scale;
+			let right = left + quad.size[0] / dpi_scale;
+			let bottom = top + quad.size[1] / dpi_scale;
+			vertices.extend_from_slice(&[
+				Vertex { position: [left, top], tex_coords: quad.tex_min },
+				Vertex { position: [left, bottom], tex_coords: [quad.tex_min[0], quad.tex_max[1]] },
+				Vertex { position: [right, bottom], tex_coords: quad.tex_max },
+				Vertex { position: [right, top], tex_coords: [quad.tex_max[0], quad.tex_min[1]] },
+			]);
+		}
+		let mut buffers = self.text_buffers.borrow_mut();
+		let range = buffers.write(self.display, &vertices);
+		let vertices = buffers.vertices().slice(range).unwrap();
+		let indices = buffers.indices(quads.len());
+		let draw_params = glium::DrawParameters {
+			viewport: Some(*self.viewport),
+			scissor: clip.map(|clip| self.logical_rect_to_viewport(&clip)),
+			blend: Blend {
+				color: BlendingFunction::Addition {
+					source: LinearBlendingFactor::SourceAlpha,
+					destination: LinearBlendingFactor::OneMinusSourceAlpha,
+				},
+				..Default::default()
+			},
+			..Default::default()
+		};
+		let sampler = atlas
+			.texture
+			.sampled()
+			.wrap_function(glium::uniforms::SamplerWrapFunction::Clamp)
+			.minify_filter(glium::uniforms::MinifySamplerFilter::Nearest)
+			.magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest);
+		let uniforms = uniform! {
+			matrix: Into::<[[f32; 4]; 4]>::into(*self.projection_transform),
+			tex: sampler,
+			color: color,
+		};
+		target.draw(vertices, &indices, self.text_program, &uniforms, &draw_params).unwrap();
+	}
+
+	/// Fills `rect` with `color`, using the red channel of `mask` as the opacity. The mask is
+	/// stretched over the rectangle.
+	///
+	/// Nothing outside of `clip` is drawn if it's specified.
+	pub fn draw_mask(
+		&self,
+		target: &mut Frame,
+		mask: &Texture2d,
+		rect: LogicalRect,
+		color: [f32; 4],
+		clip: Option<LogicalRect>,
+	) {
+		let scale = Matrix4::from_nonuniform_scale(rect.size.vec.x, rect.size.vec.y, 1.0);
+		let transform =
+			self.projection_transform * Matrix4::from_translation(rect.pos.vec.extend(0.0)) * scale;
+		let draw_params = glium::DrawParameters {
+			viewport: Some(*self.viewport),
+			scissor: clip.map(|clip| self.logical_rect_to_viewport(&clip)),
+			blend: Blend {
+				color: BlendingFunction::Addition {
+					source: LinearBlendingFactor::SourceAlpha,
+					destination: LinearBlendingFactor::OneMinusSourceAlpha,
+				},
+				..Default::default()
+			},
+			..Default::default()
+		};
+		let sampler = mask
+			.sampled()
+			.wrap_function(glium::uniforms::SamplerWrapFunction::Clamp)
+			.minify_filter(glium::uniforms::MinifySamplerFilter::Nearest)
+			.magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest);
+		let uniforms = uniform! {
+			matrix: Into::<[[f32; 4]; 4]>::into(transform),
+			tex: sampler,
+			color: color,
+		};
+		target
+			.draw(
+				self.unit_quad_vertices,
+				self.unit_quad_indices,
+				self.text_program,
+				&uniforms,
+				&draw_params,
+			)
+			.unwrap();
+	}
+
+	/// Draws `texture` stretched over `rect`.
+	///
+	/// Nothing outside of `clip` is drawn if it's specified.
+	pub fn draw_texture(
+		&self,
+		target: &mut Frame,
+		texture: &SrgbTexture2d,
+		rect: LogicalRect,
+		clip: Option<LogicalRect>,
+	) {
+		let scale = Matrix4::from_nonuniform_scale(rect.size.vec.x, rect.size.vec.y, 1.0);
+		let transform =
+			self.projection_transform * Matrix4::from_translation(rect.pos.vec.extend(0.0)) * scale;
+		let draw_params = glium::DrawParameters {
+			viewport: Some(*self.viewport),
+			scissor: clip.map(|clip| self.logical_rect_to_viewport(&clip)),
+			blend: Blend {
+				color: BlendingFunction::Addition {
+					source: LinearBlendingFactor::SourceAlpha,
+					destination: LinearBlendingFactor::OneMinusSourceAlpha,
+				},
+				..Default::default()
+			},
+			..Default::default()
+		};
+		let sampler = texture
+			.sampled()
+			.wrap_function(glium::uniforms::SamplerWrapFunction::Clamp)
+			.minify_filter(glium::uniforms::MinifySamplerFilter::LinearMipmapLinear)
+			.magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear);
+		let uniforms = uniform! {
+			matrix: Into::<[[f32; 4]; 4]>::into(transform),
+			tex: sampler,
+			bg_color: [0.0f32, 0.0, 0.0, 0.0],
+			texture_size: [rect.size.vec.x, rect.size.vec.y],
+			brighten: 0.0f32,
+			shadow_color: [0.0f32, 0.0, 0.0],
+			// Moves the shadow outside of the texture
+			shadow_offset: 1.0f32,
+		};
+		target
+			.draw(
+				self.unit_quad_vertices,
+				self.unit_quad_indices,
+				self.textured_program,
+				&uniforms,
+				&draw_params,
+			)
+			.unwrap();
+	}
+
 	pub fn clear_color(&self, target: &mut Frame, color: [f32; 4], rect: Option<LogicalRect>) {
 		// Rendering a quad to emulate clear.
 		// This is a workaround for https://github.com/glium/glium/issues/1842
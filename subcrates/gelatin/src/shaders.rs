@@ -7,6 +7,7 @@ pub static VERTEX_140: &str = include_str!("shaders/vertex_140.glsl");
 pub static TEXTURE_SHADOW_F_140: &str = include_str!("shaders/texture_shadow_f_140.glsl");
 pub static COLOR_SHADOW_F_140: &str = include_str!("shaders/color_shadow_f_140.glsl");
 pub static COLOR_F_140: &str = include_str!("shaders/color_f_140.glsl");
+pub static TEXT_F_140: &str = include_str!("shaders/text_f_140.glsl");
 
 /// See [`glium::program::ProgramCreationInput::SourceCode`] for a
 /// detailed description of these fields (note the `SourceCode` variant)
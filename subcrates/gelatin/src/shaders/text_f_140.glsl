@@ -0,0 +1,10 @@
+#version 140
+uniform sampler2D tex;
+uniform vec4 color;
+in vec2 v_tex_coords;
+out vec4 f_color;
+
+void main() {
+    float coverage = texture(tex, v_tex_coords).r;
+    f_color = vec4(color.rgb, color.a * coverage);
+}
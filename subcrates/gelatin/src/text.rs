@@ -0,0 +1,211 @@
+//! Single line text layout using the fonts installed on the system.
+
+use std::cell::OnceCell;
+use std::rc::Rc;
+use std::sync::{Mutex, Once};
+use std::thread::{self, JoinHandle};
+
+use ab_glyph::{
+	point, Font as _, FontArc, FontVec, Glyph, GlyphId, OutlinedGlyph, PxScale, ScaleFont,
+};
+use glium::{
+	backend::Facade,
+	texture::{ClientFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat},
+};
+use image::GrayImage;
+use log::warn;
+
+/// Families that are tried in order when looking for the default font. The first one that's
+/// installed on the system is used.
+static PREFERRED_FAMILIES: &[&str] = &[
+	"Segoe UI",
+	"Helvetica Neue",
+	"DejaVu Sans",
+	"Noto Sans",
+	"Liberation Sans",
+	"Cantarell",
+	"Arial",
+];
+
+/// Families that are used for the characters the default font doesn't have, in this order.
+/// All of them that are installed are loaded.
+static FALLBACK_FAMILIES: &[&str] = &[
+	"DejaVu Sans",
+	"Noto Sans",
+	"Noto Sans CJK SC",
+	"Noto Sans CJK JP",
+	"Microsoft YaHei",
+	"Yu Gothic",
+	"PingFang SC",
+	"Hiragino Sans",
+	"Noto Sans Symbols",
+	"Noto Sans Symbols2",
+	"Segoe UI Symbol",
+	"Arial Unicode MS",
+];
+
+thread_local! {
+	static DEFAULT_FONT: OnceCell<Option<Rc<Font>>> = const { OnceCell::new() };
+}
+
+/// The contents of a font file and the index of a face in it
+type FaceData = (Vec<u8>, u32);
+
+static START_LOADING: Once = Once::new();
+static LOADING_FACES: Mutex<Option<JoinHandle<Vec<FaceData>>>> = Mutex::new(None);
+
+/// A glyph of a line of text, positioned relative to the top left corner of the line
+pub struct LineGlyph {
+	/// The index of the face in the fallback chain the glyph is taken from
+	pub face: usize,
+	pub glyph: Glyph,
+}
+
+/// A font with a chain of fallbacks. Each character is taken from the first face that has
+/// it.
+pub struct Font {
+	faces: Vec<FontArc>,
+}
+
+impl Font {
+	/// Returns the default sans-serif font of the system along with its fallbacks.
+	///
+	/// The fonts are only searched for on the first call, unless
+	/// `load_system_default_in_background` was called before. Returns `None` if no usable font
+	/// was found.
+	pub fn system_default() -> Option<Rc<Font>> {
+		DEFAULT_FONT.with(|font| {
+			font.get_or_init(|| {
+				let loading = LOADING_FACES.lock().unwrap().take();
+				let faces = match loading {
+					Some(handle) => handle.join().unwrap_or_default(),
+					None => find_system_default(),
+				};
+				Font::from_faces(faces).map(Rc::new)
+			})
+			.clone()
+		})
+	}
+
+	/// Starts searching for the default font of the system on a new thread, so that the font
+	/// files are scanned while the window is being created instead of at the first text that's
+	/// drawn. Only the first call has an effect.
+	pub fn load_system_default_in_background() {
+		START_LOADING.call_once(|| {
+			*LOADING_FACES.lock().unwrap() = Some(thread::spawn(find_system_default));
+		});
+	}
+
+	fn from_faces(face_data: Vec<FaceData>) -> Option<Font> {
+		let mut faces = Vec::with_capacity(face_data.len());
+		for (data, index) in face_data {
+			match FontVec::try_from_vec_and_index(data, index) {
+				Ok(face) => faces.push(FontArc::new(face)),
+				Err(error) => warn!("Failed to load a font: {}", error),
+			}
+		}
+		if faces.is_empty() {
+			return None;
+		}
+		Some(Font { faces })
+	}
+
+	/// The distance between the top of the tallest and the bottom of the lowest glyph of the
+	/// primary face
+	pub fn line_height(&self, px_size: f32) -> f32 {
+		let scaled = self.faces[0].as_scaled(PxScale::from(px_size));
+		scaled.ascent() - scaled.descent()
+	}
+
+	/// Returns the width of `text` in pixels when rendered at `px_size`
+	pub fn line_width(&self, text: &str, px_size: f32) -> f32 {
+		let mut width = 0.0;
+		self.layout_line(text, px_size, |_, advance| width = advance);
+		width
+	}
+
+	/// Returns the outline of a glyph from `layout_line`, or `None` if the glyph is empty
+	pub fn outline_glyph(&self, glyph: &LineGlyph) -> Option<OutlinedGlyph> {
+		self.faces[glyph.face].outline_glyph(glyph.glyph.clone())
+	}
+
+	/// Calls `f` for each glyph of the text along with the horizontal position right after the
+	/// glyph. The glyphs are positioned on the baseline of the primary face.
+	pub fn layout_line<F: FnMut(LineGlyph, f32)>(&self, text: &str, px_size: f32, mut f: F) {
+		let scale = PxScale::from(px_size);
+		let baseline = self.faces[0].as_scaled(scale).ascent();
+		let mut caret = 0.0;
+		let mut prev: Option<(usize, GlyphId)> = None;
+		for ch in text.chars() {
+			let (face, id) = self.find_glyph(ch);
+			let scaled = self.faces[face].as_scaled(scale);
+			if let Some((prev_face, prev_id)) = prev {
+				if prev_face == face {
+					caret += scaled.kern(prev_id, id);
+				}
+			}
+			let glyph = id.with_scale_and_position(scale, point(caret, baseline));
+			caret += scaled.h_advance(id);
+			prev = Some((face, id));
+			f(LineGlyph { face, glyph }, caret);
+		}
+	}
+
+	/// Returns the first face of the chain that has a glyph for `ch`. The missing glyph of the
+	/// primary face is used if none of them have it.
+	fn find_glyph(&self, ch: char) -> (usize, GlyphId) {
+		let found = self.faces.iter().enumerate().find_map(|(index, face)| {
+			let id = face.glyph_id(ch);
+			(id.0 != 0).then_some((index, id))
+		});
+		found.unwrap_or_else(|| (0, self.faces[0].glyph_id(ch)))
+	}
+}
+
+/// Returns the data of the default font and its fallbacks
+fn find_system_default() -> Vec<FaceData> {
+	let mut db = fontdb::Database::new();
+	db.load_system_fonts();
+	let mut families =
+		PREFERRED_FAMILIES.iter().map(|name| fontdb::Family::Name(name)).collect::<Vec<_>>();
+	families.push(fontdb::Family::SansSerif);
+	let query = fontdb::Query { families: &families, ..Default::default() };
+	let primary = match db.query(&query).or_else(|| db.faces().next().map(|face| face.id)) {
+		Some(id) => id,
+		None => {
+			warn!("Could not find any font on the system. Text will not be displayed.");
+			return Vec::new();
+		}
+	};
+	let mut ids = vec![primary];
+	for name in FALLBACK_FAMILIES {
+		let query = fontdb::Query { families: &[fontdb::Family::Name(name)], ..Default::default() };
+		match db.query(&query) {
+			Some(id) if !ids.contains(&id) => ids.push(id),
+			_ => (),
+		}
+	}
+	let mut faces = Vec::with_capacity(ids.len());
+	for id in ids {
+		match db.with_face_data(id, |data, index| (data.to_vec(), index)) {
+			Some(face) => faces.push(face),
+			None => {
+				warn!("Failed to load the font {:?}", db.face(id).map(|f| &f.post_script_name))
+			}
+		}
+	}
+	faces
+}
+
+/// Uploads a single channel image, to be drawn with `DrawContext::draw_mask`
+pub fn mask_texture<F: Facade>(facade: &F, image: GrayImage) -> Texture2d {
+	let dimensions = image.dimensions();
+	let raw = RawImage2d {
+		data: image.into_raw().into(),
+		width: dimensions.0,
+		height: dimensions.1,
+		format: ClientFormat::U8,
+	};
+	Texture2d::with_format(facade, raw, UncompressedFloatFormat::U8, MipmapsOption::NoMipmap)
+		.unwrap()
+}
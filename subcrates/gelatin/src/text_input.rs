@@ -0,0 +1,283 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use glium::Frame;
+use winit::event::ElementState;
+use winit::keyboard::{Key, NamedKey};
+
+use crate::add_common_widget_functions;
+use crate::misc::{Alignment, Length, LogicalRect, LogicalVector, WidgetPlacement};
+use crate::window::RenderValidity;
+use crate::NextUpdate;
+use crate::{DrawContext, Event, EventKind, Widget, WidgetData, WidgetError};
+
+const PADDING: f32 = 8.0;
+const CURSOR_WIDTH: f32 = 1.5;
+
+struct TextInputData {
+	placement: WidgetPlacement,
+	drawn_bounds: LogicalRect,
+	visible: bool,
+
+	text: String,
+	/// Byte index into `text`. Always at a char boundary.
+	cursor: usize,
+	/// Displayed after the text but cannot be edited. Useful for file extensions.
+	suffix: String,
+	/// Displayed below the text, for example to explain why the text was rejected.
+	message: Option<String>,
+
+	/// In logical pixels
+	font_size: f32,
+	text_color: [f32; 4],
+	suffix_color: [f32; 4],
+	message_color: [f32; 4],
+	bg_color: [f32; 4],
+
+	on_submit: Option<Rc<dyn Fn(String)>>,
+	on_cancel: Option<Rc<dyn Fn()>>,
+
+	render_validity: RenderValidity,
+}
+impl WidgetData for TextInputData {
+	fn placement(&mut self) -> &mut WidgetPlacement {
+		&mut self.placement
+	}
+	fn drawn_bounds(&mut self) -> &mut LogicalRect {
+		&mut self.drawn_bounds
+	}
+	fn visible(&mut self) -> &mut bool {
+		&mut self.visible
+	}
+}
+
+/// A single line text field.
+///
+/// Key presses are handled whenever the widget is visible, so the owner is expected to
+/// only show it while it should receive the keyboard input.
+pub struct TextInput {
+	data: RefCell<TextInputData>,
+}
+
+impl TextInput {
+	pub fn new() -> TextInput {
+		TextInput {
+			data: RefCell::new(TextInputData {
+				placement: Default::default(),
+				drawn_bounds: Default::default(),
+				visible: true,
+				text: String::new(),
+				cursor: 0,
+				suffix: String::new(),
+				message: None,
+				font_size: 16.0,
+				text_color: [0.95, 0.95, 0.95, 1.0],
+				suffix_color: [0.6, 0.6, 0.6, 1.0],
+				message_color: [1.0, 0.45, 0.4, 1.0],
+				bg_color: [0.1, 0.1, 0.1, 0.9],
+				on_submit: None,
+				on_cancel: None,
+				render_validity: Default::default(),
+			}),
+		}
+	}
+
+	add_common_widget_functions!(data);
+
+	/// Called with the text followed by the suffix when Enter is pressed.
+	pub fn set_on_submit<T: Fn(String) + 'static>(&self, callback: T) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.on_submit = Some(Rc::new(callback));
+	}
+
+	/// Called when Escape is pressed.
+	pub fn set_on_cancel<T: Fn() + 'static>(&self, callback: T) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.on_cancel = Some(Rc::new(callback));
+	}
+
+	/// Replaces the text and moves the cursor to the end of it.
+	pub fn set_text<S: Into<String>>(&self, text: S) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.text = text.into();
+		borrowed.cursor = borrowed.text.len();
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn text(&self) -> String {
+		self.data.borrow().text.clone()
+	}
+
+	pub fn set_suffix<S: Into<String>>(&self, suffix: S) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.suffix = suffix.into();
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn set_message(&self, message: Option<String>) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.message = message;
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn set_font_size(&self, font_size: f32) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.font_size = font_size;
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn set_text_color(&self, color: [f32; 4]) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.text_color = color;
+		borrowed.render_validity.invalidate();
+	}
+
+	pub fn set_bg_color(&self, color: [f32; 4]) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.bg_color = color;
+		borrowed.render_validity.invalidate();
+	}
+}
+
+impl Default for TextInput {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl TextInputData {
+	fn prev_char_boundary(&self) -> usize {
+		self.text[..self.cursor].char_indices().next_back().map_or(0, |(i, _)| i)
+	}
+
+	fn next_char_boundary(&self) -> usize {
+		self.text[self.cursor..]
+			.chars()
+			.next()
+			.map_or(self.cursor, |ch| self.cursor + ch.len_utf8())
+	}
+}
+
+impl Widget for TextInput {
+	fn draw(&self, target: &mut Frame, context: &DrawContext) -> Result<NextUpdate, WidgetError> {
+		let borrowed = self.data.borrow();
+		if !borrowed.visible {
+			return Ok(NextUpdate::Latest);
+		}
+		let bounds = borrowed.drawn_bounds.align_to_pixels(context.dpi_scale_factor);
+		context.clear_color(target, borrowed.bg_color, Some(bounds));
+
+		let font_size = borrowed.font_size;
+		let line_height = context.line_height(font_size);
+
+		let text_area = LogicalRect {
+			pos: bounds.pos + LogicalVector::new(PADDING, PADDING),
+			size: LogicalVector::new(
+				(bounds.size.vec.x - 2.0 * PADDING).max(0.0),
+				line_height.min(bounds.size.vec.y),
+			),
+		};
+		let cursor_x = context.text_width(&borrowed.text[..borrowed.cursor], font_size);
+		// Scroll the text to the left if the cursor would be outside of the text area
+		let scroll = (cursor_x + CURSOR_WIDTH - text_area.size.vec.x).max(0.0);
+		let text_pos = text_area.pos - LogicalVector::new(scroll, 0.0);
+
+		let text = &borrowed.text;
+		context.draw_text(target, text, font_size, text_pos, borrowed.text_color, Some(text_area));
+
+		if !borrowed.suffix.is_empty() {
+			let text_width = context.text_width(&borrowed.text, font_size);
+			let suffix_pos = text_pos + LogicalVector::new(text_width, 0.0);
+			let suffix = &borrowed.suffix;
+			let color = borrowed.suffix_color;
+			context.draw_text(target, suffix, font_size, suffix_pos, color, Some(text_area));
+		}
+
+		let cursor_rect = LogicalRect {
+			pos: text_pos + LogicalVector::new(cursor_x, 0.0),
+			size: LogicalVector::new(CURSOR_WIDTH, line_height),
+		};
+		context.clear_color(target, borrowed.text_color, Some(cursor_rect));
+
+		if let Some(message) = &borrowed.message {
+			let message_pos = text_area.pos + LogicalVector::new(0.0, line_height + PADDING * 0.5);
+			let color = borrowed.message_color;
+			context.draw_text(target, message, font_size, message_pos, color, Some(bounds));
+		}
+		Ok(NextUpdate::Latest)
+	}
+
+	fn layout(&self, available_space: LogicalRect) {
+		let mut borrowed = self.data.borrow_mut();
+		borrowed.default_layout(available_space);
+	}
+
+	fn handle_event(&self, event: &Event) {
+		let input = match &event.kind {
+			EventKind::KeyInput { input } if input.state == ElementState::Pressed => input,
+			_ => return,
+		};
+		let mut submit = None;
+		let mut cancel = None;
+		{
+			let mut borrowed = self.data.borrow_mut();
+			if !borrowed.visible {
+				return;
+			}
+			match &input.logical_key {
+				Key::Named(NamedKey::Enter) => {
+					let text = format!("{}{}", borrowed.text, borrowed.suffix);
+					submit = borrowed.on_submit.clone().map(|callback| (callback, text));
+				}
+				Key::Named(NamedKey::Escape) => cancel = borrowed.on_cancel.clone(),
+				Key::Named(NamedKey::Backspace) => {
+					let start = borrowed.prev_char_boundary();
+					let end = borrowed.cursor;
+					borrowed.text.replace_range(start..end, "");
+					borrowed.cursor = start;
+				}
+				Key::Named(NamedKey::Delete) => {
+					let start = borrowed.cursor;
+					let end = borrowed.next_char_boundary();
+					borrowed.text.replace_range(start..end, "");
+				}
+				Key::Named(NamedKey::ArrowLeft) => borrowed.cursor = borrowed.prev_char_boundary(),
+				Key::Named(NamedKey::ArrowRight) => borrowed.cursor = borrowed.next_char_boundary(),
+				Key::Named(NamedKey::Home) => borrowed.cursor = 0,
+				Key::Named(NamedKey::End) => borrowed.cursor = borrowed.text.len(),
+				_ => {
+					let shortcut = event.modifiers.control_key() || event.modifiers.super_key();
+					if let Some(typed) = input.text.as_ref().filter(|_| !shortcut) {
+						let typed: String = typed.chars().filter(|ch| !ch.is_control()).collect();
+						let cursor = borrowed.cursor;
+						borrowed.text.insert_str(cursor, &typed);
+						borrowed.cursor += typed.len();
+					}
+				}
+			}
+			borrowed.render_validity.invalidate();
+		}
+		// Calling the callbacks after the borrow ended so that they may use this widget
+		if let Some((callback, text)) = submit {
+			callback(text);
+		}
+		if let Some(callback) = cancel {
+			callback();
+		}
+	}
+
+	// No children for a text input
+	fn children(&self, _children: &mut Vec<Rc<dyn Widget>>) {}
+
+	fn placement(&self) -> WidgetPlacement {
+		self.data.borrow().placement
+	}
+
+	fn visible(&self) -> bool {
+		self.data.borrow().visible
+	}
+
+	fn set_valid_ref(&self, render_validity: RenderValidity) {
+		self.data.borrow_mut().render_validity = render_validity;
+	}
+}
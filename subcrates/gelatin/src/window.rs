@@ -42,6 +42,7 @@ use derive_builder::Builder;
 use crate::shaders;
 use crate::{
 	application::Application,
+	glyph_atlas::{GlyphAtlas, TextBuffers},
 	shaders::{shader_from_source, ShaderDescriptor},
 };
 use crate::{
@@ -163,7 +164,10 @@ struct WindowData {
 	cursor_pos: LogicalVector,
 	modifiers: ModifiersState,
 	root_widget: Rc<dyn Widget>,
+	/// Receives the key input instead of the root while it's visible
+	focused_widget: Option<Rc<dyn Widget>>,
 	bg_color: [f32; 4],
+	text_color: [f32; 4],
 
 	global_event_handlers: Vec<Box<EventHandler>>,
 
@@ -173,6 +177,9 @@ struct WindowData {
 	textured_program: Program,
 	colored_shadowed_program: Program,
 	colored_program: Program,
+	text_program: Program,
+	glyph_atlas: RefCell<GlyphAtlas>,
+	text_buffers: RefCell<TextBuffers>,
 }
 
 pub struct Window {
@@ -234,7 +241,7 @@ impl Window {
 		#[cfg(not(any(target_os = "macos", windows)))]
 		let window_builder = if let Some(app_id) = desc.app_id {
 			let is_wayland = std::env::var("XDG_SESSION_TYPE")
-				.map_or(false, |var| var.to_lowercase().contains("wayland"));
+				.is_ok_and(|var| var.to_lowercase().contains("wayland"));
 			if is_wayland {
 				WindowBuilderExtWayland::with_name(window_builder, &app_id, app_id.to_lowercase())
 			} else {
@@ -302,6 +309,20 @@ impl Window {
 		)
 		.unwrap();
 
+		let text_program = shader_from_source(
+			&display,
+			ShaderDescriptor {
+				vertex_shader: shaders::VERTEX_140,
+				fragment_shader: shaders::TEXT_F_140,
+				outputs_srgb: false,
+				..Default::default()
+			},
+		)
+		.unwrap();
+
+		let glyph_atlas = RefCell::new(GlyphAtlas::new(&display));
+		let text_buffers = RefCell::new(TextBuffers::new(&display));
+
 		let resulting_window = Rc::new(Window {
 			data: RefCell::new(WindowData {
 				display,
@@ -316,7 +337,9 @@ impl Window {
 				modifiers: ModifiersState::empty(),
 				render_validity: RenderValidity { validity: Rc::new(Cell::new(false)) },
 				root_widget: Rc::new(crate::line_layout_container::VerticalLayoutContainer::new()),
+				focused_widget: None,
 				bg_color: [0.85, 0.85, 0.85, 1.0],
+				text_color: [0.0, 0.0, 0.0, 1.0],
 
 				global_event_handlers: Vec::new(),
 
@@ -325,6 +348,9 @@ impl Window {
 				textured_program,
 				colored_shadowed_program,
 				colored_program,
+				text_program,
+				glyph_atlas,
+				text_buffers,
 			}),
 		});
 
@@ -414,11 +440,27 @@ impl Window {
 		borrowed.render_validity.invalidate();
 	}
 
+	/// Sends the key input only to `widget` for as long as it's visible, instead of to every
+	/// widget of the tree. The other events still go through the root.
+	pub fn set_focus(&self, widget: Option<Rc<dyn Widget>>) {
+		self.data.borrow_mut().focused_widget = widget;
+	}
+
 	pub fn set_bg_color(&self, color: [f32; 4]) {
 		let mut borrowed = self.data.borrow_mut();
 		borrowed.bg_color = color;
 	}
 
+	/// The color of the text of the widgets that don't set their own color, eg to follow the
+	/// theme
+	pub fn set_text_color(&self, color: [f32; 4]) {
+		let mut borrowed = self.data.borrow_mut();
+		if borrowed.text_color != color {
+			borrowed.text_color = color;
+			borrowed.render_validity.invalidate();
+		}
+	}
+
 	/// This is called when a NewEvents event is received in the application
 	pub fn handle_loop_wake_up(&self) -> NextUpdate {
 		let root_widget = self.data.borrow().root_widget.clone();
@@ -553,8 +595,12 @@ impl Window {
 		}
 
 		if let Some(event) = event {
-			let cloned = self.data.borrow().root_widget.clone();
-			cloned.handle_event(&event);
+			let focused = self.data.borrow().focused_widget.clone();
+			let receiver = match (&event.kind, focused) {
+				(EventKind::KeyInput { .. }, Some(focused)) if focused.visible() => focused,
+				_ => self.data.borrow().root_widget.clone(),
+			};
+			receiver.handle_event(&event);
 			let mut borrowed = self.data.borrow_mut();
 			if !borrowed.render_validity.get() {
 				borrowed.last_event_invalidated = true;
@@ -649,6 +695,10 @@ impl Window {
 			textured_program: &borrowed.textured_program,
 			colored_shadowed_program: &borrowed.colored_shadowed_program,
 			colored_program: &borrowed.colored_program,
+			text_program: &borrowed.text_program,
+			glyph_atlas: &borrowed.glyph_atlas,
+			text_buffers: &borrowed.text_buffers,
+			text_color: borrowed.text_color,
 			viewport: &viewport,
 			projection_transform: &projection_transform,
 		};